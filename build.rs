@@ -0,0 +1,758 @@
+//! Turns the grammar in src/parse/c.grammar into Rust source for the parser:
+//! `NonTerminalType` with the description of each nonterminal, the rule
+//! tables and operator precedence of `parse::rules`, and the nonterminals
+//! `parse::cst` wraps with an accessor for each label of their rules.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+const GRAMMAR: &str = "src/parse/c.grammar";
+
+/// The classes of tokens a rule can name, and a token of each
+const TOKEN_CLASSES: &[(&str, &str)] = &[
+    ("Identifier", "LexItem::Identifier(String::new())"),
+    ("TypedefName", "LexItem::TypedefName(String::new())"),
+    (
+        "NumericLiteral",
+        "LexItem::NumericLiteral(NumberType::default())",
+    ),
+    ("StringLiteral", "LexItem::StringLiteral(Vec::new())"),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Name(String),
+    Quoted(String),
+    Arrow,
+    Colon,
+    Bar,
+    Question,
+    Star,
+    Plus,
+    Percent,
+    LeftParen,
+    RightParen,
+    Epsilon,
+}
+
+/// A part of the right-hand side of a rule
+#[derive(Clone, Debug)]
+enum Item {
+    Name(String),
+    /// An item with the name of the accessor for it
+    Labeled(String, Box<Item>),
+    Quoted(String),
+    Epsilon,
+    /// A choice between sequences
+    Choice(Vec<Vec<Item>>),
+    Optional(Box<Item>),
+    Repeat {
+        item: Box<Item>,
+        at_least_one: bool,
+    },
+    Separated {
+        item: Box<Item>,
+        separator: Box<Item>,
+    },
+}
+
+/// A symbol of a rule after expansion
+#[derive(Clone, Debug, PartialEq)]
+enum Symbol {
+    /// A nonterminal or a class of tokens, with its label if it has one
+    Name(String, Option<String>),
+    Quoted(String),
+}
+
+struct Grammar {
+    /// Every nonterminal in the order they are first defined, with whether
+    /// it only exists for a repetition
+    non_terminals: Vec<(String, bool)>,
+    descriptions: Vec<(String, String)>,
+    rules: Vec<(String, Vec<Symbol>)>,
+    gnu_rules: Vec<(String, Vec<Symbol>)>,
+    c89_rules: Vec<(String, Vec<Symbol>)>,
+    /// The associativity and tokens of each precedence level, loosest first
+    precedence: Vec<(&'static str, Vec<String>)>,
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", GRAMMAR);
+    println!("cargo:rerun-if-changed=build.rs");
+    let source = fs::read_to_string(GRAMMAR).expect("The grammar can be read");
+    let grammar = match read_grammar(&source) {
+        Ok(grammar) => grammar,
+        Err(error) => panic!("{}: {}", GRAMMAR, error),
+    };
+
+    let out = env::var("OUT_DIR").unwrap();
+    let out = Path::new(&out);
+    fs::write(out.join("non_terminals.rs"), non_terminals(&grammar)).unwrap();
+    fs::write(out.join("rules.rs"), rules(&grammar)).unwrap();
+    fs::write(out.join("wrappers.rs"), wrappers(&grammar)).unwrap();
+    match accessors(&grammar) {
+        Ok(code) => fs::write(out.join("accessors.rs"), code).unwrap(),
+        Err(error) => panic!("{}: {}", GRAMMAR, error),
+    }
+}
+
+/// Splits a line into tokens, leaving out comments
+fn tokenize(line: &str, number: usize) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(ch) = chars.next() {
+        tokens.push(match ch {
+            _ if ch.is_whitespace() => continue,
+            '/' if chars.peek() == Some(&'/') => break,
+            '-' if chars.peek() == Some(&'>') => {
+                chars.next();
+                Token::Arrow
+            }
+            ':' => Token::Colon,
+            '|' => Token::Bar,
+            '?' => Token::Question,
+            '*' => Token::Star,
+            '+' => Token::Plus,
+            '%' => Token::Percent,
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            'ε' => Token::Epsilon,
+            '"' => {
+                let mut quoted = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(ch) => quoted.push(ch),
+                        None => return Err(format!("line {}: unclosed quote", number)),
+                    }
+                }
+                Token::Quoted(quoted)
+            }
+            _ if ch.is_ascii_alphabetic() || ch == '_' => {
+                let mut name = ch.to_string();
+                while let Some(&ch) = chars.peek() {
+                    if !ch.is_ascii_alphanumeric() && ch != '_' {
+                        break;
+                    }
+                    name.push(ch);
+                    chars.next();
+                }
+                Token::Name(name)
+            }
+            _ => return Err(format!("line {}: unexpected '{}'", number, ch)),
+        });
+    }
+    Ok(tokens)
+}
+
+/// Parses the right-hand side of a rule, or a group in it
+struct Items<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    line: usize,
+}
+
+impl<'a> Items<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, String> {
+        Err(format!("line {}: {}", self.line, message))
+    }
+
+    /// Sequences separated by `|`
+    fn choice(&mut self) -> Result<Item, String> {
+        let mut sequences = vec![self.sequence()?];
+        while self.peek() == Some(&Token::Bar) {
+            self.position += 1;
+            sequences.push(self.sequence()?);
+        }
+        Ok(Item::Choice(sequences))
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Item>, String> {
+        let mut items = Vec::new();
+        loop {
+            if let (Some(Token::Name(label)), Some(Token::Colon)) =
+                (self.peek(), self.tokens.get(self.position + 1))
+            {
+                self.position += 2;
+                let item = match self.item()? {
+                    Some(item) => item,
+                    None => return self.error(&format!("expected a symbol after '{}:'", label)),
+                };
+                if !item.has_name() {
+                    return self.error(&format!("'{}' labels no name", label));
+                }
+                items.push(Item::Labeled(label.clone(), Box::new(item)));
+                continue;
+            }
+            match self.item()? {
+                Some(item) => items.push(item),
+                None => break,
+            }
+        }
+        if items.is_empty() {
+            return self.error("expected a symbol or ε");
+        }
+        Ok(items)
+    }
+
+    /// A symbol or group with what follows it, if there is one
+    fn item(&mut self) -> Result<Option<Item>, String> {
+        let item = match self.peek() {
+            Some(Token::Name(name)) => Item::Name(name.clone()),
+            Some(Token::Quoted(quoted)) => Item::Quoted(quoted.clone()),
+            Some(Token::Epsilon) => Item::Epsilon,
+            Some(Token::LeftParen) => {
+                self.position += 1;
+                let group = self.choice()?;
+                if self.peek() != Some(&Token::RightParen) {
+                    return self.error("expected ')'");
+                }
+                group
+            }
+            Some(Token::Bar) | Some(Token::RightParen) | None => return Ok(None),
+            Some(token) => return self.error(&format!("unexpected {:?}", token)),
+        };
+        self.position += 1;
+        self.postfix(item).map(Some)
+    }
+
+    fn postfix(&mut self, mut item: Item) -> Result<Item, String> {
+        loop {
+            item = match self.peek() {
+                Some(Token::Question) => Item::Optional(Box::new(item)),
+                Some(Token::Star) | Some(Token::Plus) => Item::Repeat {
+                    at_least_one: self.peek() == Some(&Token::Plus),
+                    item: Box::new(item),
+                },
+                Some(Token::Percent) => {
+                    self.position += 1;
+                    let separator = match self.peek() {
+                        Some(Token::Name(name)) => Item::Name(name.clone()),
+                        Some(Token::Quoted(quoted)) => Item::Quoted(quoted.clone()),
+                        _ => return self.error("expected a separator after '%'"),
+                    };
+                    Item::Separated {
+                        item: Box::new(item),
+                        separator: Box::new(separator),
+                    }
+                }
+                _ => return Ok(item),
+            };
+            self.position += 1;
+        }
+    }
+}
+
+impl Item {
+    /// Whether this has a name in it, which a label could be for
+    fn has_name(&self) -> bool {
+        match self {
+            Item::Name(_) => true,
+            Item::Quoted(_) | Item::Epsilon => false,
+            Item::Labeled(_, item) | Item::Optional(item) => item.has_name(),
+            Item::Choice(sequences) => sequences.iter().flatten().any(Item::has_name),
+            Item::Repeat { item, .. } | Item::Separated { item, .. } => item.has_name(),
+        }
+    }
+}
+
+/// Expands the optional items and choices of `item` into the sequences it
+/// stands for. Repetitions become rules of new nonterminals.
+fn expand(item: &Item, lhs: &str, grammar: &mut Grammar, section: &str) -> Vec<Vec<Symbol>> {
+    match item {
+        Item::Name(name) => vec![vec![Symbol::Name(name.clone(), None)]],
+        // Every name the item expands to is labeled, so a label can be for
+        // one of several tokens
+        Item::Labeled(label, item) => {
+            let mut expanded = expand(item, lhs, grammar, section);
+            for symbol in expanded.iter_mut().flatten() {
+                if let Symbol::Name(_, named) = symbol {
+                    named.get_or_insert_with(|| label.clone());
+                }
+            }
+            expanded
+        }
+        Item::Quoted(quoted) => vec![vec![Symbol::Quoted(quoted.clone())]],
+        Item::Epsilon => vec![Vec::new()],
+        Item::Choice(sequences) => sequences
+            .iter()
+            .flat_map(|sequence| {
+                sequence.iter().fold(vec![Vec::new()], |prefixes, item| {
+                    let expanded = expand(item, lhs, grammar, section);
+                    prefixes
+                        .iter()
+                        .flat_map(|prefix| {
+                            expanded.iter().map(move |rest| {
+                                let mut sequence: Vec<Symbol> = prefix.clone();
+                                sequence.extend(rest.iter().cloned());
+                                sequence
+                            })
+                        })
+                        .collect()
+                })
+            })
+            .collect(),
+        Item::Optional(item) => {
+            let mut expanded = expand(item, lhs, grammar, section);
+            expanded.push(Vec::new());
+            expanded
+        }
+        Item::Repeat { item, at_least_one } => {
+            let repetition = hidden(lhs, grammar);
+            let rest = Symbol::Name(repetition.clone(), None);
+            let items = expand(item, lhs, grammar, section);
+            let mut rules: Vec<Vec<Symbol>> = match at_least_one {
+                true => items.clone(),
+                false => vec![Vec::new()],
+            };
+            rules.extend(items.iter().map(|item| {
+                let mut rule = vec![rest.clone()];
+                rule.extend(item.iter().cloned());
+                rule
+            }));
+            add_rules(&repetition, rules, grammar, section);
+            vec![vec![rest]]
+        }
+        Item::Separated { item, separator } => {
+            let repetition = hidden(lhs, grammar);
+            let rest = Symbol::Name(repetition.clone(), None);
+            let items = expand(item, lhs, grammar, section);
+            let separators = expand(separator, lhs, grammar, section);
+            let mut rules = items.clone();
+            for separator in &separators {
+                rules.extend(items.iter().map(|item| {
+                    let mut rule = vec![rest.clone()];
+                    rule.extend(separator.iter().cloned());
+                    rule.extend(item.iter().cloned());
+                    rule
+                }));
+            }
+            add_rules(&repetition, rules, grammar, section);
+            vec![vec![rest]]
+        }
+    }
+}
+
+/// A new nonterminal for a repetition in a rule of `lhs`
+fn hidden(lhs: &str, grammar: &mut Grammar) -> String {
+    let prefix = format!("{}Repetition", lhs);
+    let count = grammar
+        .non_terminals
+        .iter()
+        .filter(|(name, hidden)| *hidden && name.starts_with(&prefix))
+        .count();
+    let name = format!("{}{}", prefix, count + 1);
+    grammar.non_terminals.push((name.clone(), true));
+    name
+}
+
+/// Adds rules to those of the section they are in
+fn add_rules(lhs: &str, sequences: Vec<Vec<Symbol>>, grammar: &mut Grammar, section: &str) {
+    let rules = match section {
+        "gnu" => &mut grammar.gnu_rules,
+        "c89" => &mut grammar.c89_rules,
+        _ => &mut grammar.rules,
+    };
+    rules.extend(sequences.into_iter().map(|rhs| (lhs.to_string(), rhs)));
+}
+
+fn read_grammar(source: &str) -> Result<Grammar, String> {
+    let mut grammar = Grammar {
+        non_terminals: Vec::new(),
+        descriptions: Vec::new(),
+        rules: Vec::new(),
+        gnu_rules: Vec::new(),
+        c89_rules: Vec::new(),
+        precedence: Vec::new(),
+    };
+
+    // Each statement with the line it starts on, joined with the lines which
+    // continue it
+    let mut statements: Vec<(usize, Option<String>, Vec<Token>)> = Vec::new();
+    let mut section = None;
+    for (index, line) in source.lines().enumerate() {
+        let number = index + 1;
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            section = Some(trimmed[1..trimmed.len() - 1].to_string());
+            continue;
+        }
+        let tokens = tokenize(line, number)?;
+        if tokens.is_empty() {
+            continue;
+        }
+        match statements.last_mut() {
+            Some((_, _, statement)) if line.starts_with(char::is_whitespace) => {
+                statement.extend(tokens)
+            }
+            _ => statements.push((number, section.clone(), tokens)),
+        }
+    }
+
+    // Nonterminals are those with rules
+    let mut defined: HashMap<String, ()> = HashMap::new();
+    for (_, section, tokens) in &statements {
+        if let (Some("c" | "gnu" | "c89"), [Token::Name(lhs), Token::Arrow, ..]) =
+            (section.as_deref(), &tokens[..])
+        {
+            if defined.insert(lhs.clone(), ()).is_none() {
+                grammar.non_terminals.push((lhs.clone(), false));
+            }
+        }
+    }
+
+    for (line, section, tokens) in statements {
+        match section.as_deref() {
+            Some("descriptions") => {
+                let colon = tokens.iter().position(|token| *token == Token::Colon);
+                let (names, description) = match (colon, tokens.last()) {
+                    (Some(colon), Some(Token::Quoted(description))) => {
+                        (&tokens[..colon], description.clone())
+                    }
+                    _ => return Err(format!("line {}: expected names, ':' and a string", line)),
+                };
+                for name in names {
+                    match name {
+                        Token::Name(name) if defined.contains_key(name) => grammar
+                            .descriptions
+                            .push((name.clone(), description.clone())),
+                        _ => return Err(format!("line {}: {:?} is not a nonterminal", line, name)),
+                    }
+                }
+            }
+            Some("precedence") => {
+                let associativity = match tokens.first() {
+                    Some(Token::Name(name)) if name == "left" => "Left",
+                    Some(Token::Name(name)) if name == "right" => "Right",
+                    Some(Token::Name(name)) if name == "nonassoc" => "NonAssociative",
+                    _ => return Err(format!("line {}: expected left, right or nonassoc", line)),
+                };
+                let mut level = Vec::new();
+                for token in &tokens[1..] {
+                    let token = match token {
+                        Token::Quoted(token) => token,
+                        _ => return Err(format!("line {}: expected a quoted token", line)),
+                    };
+                    let declared = grammar.precedence.iter().flat_map(|(_, tokens)| tokens);
+                    if declared.chain(&level).any(|other| other == token) {
+                        return Err(format!(
+                            "line {}: \"{}\" already has a precedence",
+                            line, token
+                        ));
+                    }
+                    level.push(token.clone());
+                }
+                if level.is_empty() {
+                    return Err(format!("line {}: expected tokens", line));
+                }
+                grammar.precedence.push((associativity, level));
+            }
+            Some(section @ ("c" | "gnu" | "c89")) => {
+                let lhs = match &tokens[..] {
+                    [Token::Name(lhs), Token::Arrow, ..] => lhs.clone(),
+                    _ => return Err(format!("line {}: expected a nonterminal and '->'", line)),
+                };
+                let mut items = Items {
+                    tokens: &tokens[2..],
+                    position: 0,
+                    line,
+                };
+                let choice = items.choice()?;
+                if items.position != items.tokens.len() {
+                    return items.error("unexpected ')'");
+                }
+                let sequences = expand(&choice, &lhs, &mut grammar, section);
+                add_rules(&lhs, sequences, &mut grammar, section);
+            }
+            _ => return Err(format!("line {}: outside of a section", line)),
+        }
+    }
+
+    // Every name is either a nonterminal or a class of tokens
+    let known: Vec<&str> = grammar
+        .non_terminals
+        .iter()
+        .map(|(name, _)| &**name)
+        .collect();
+    let all_rules = grammar
+        .rules
+        .iter()
+        .chain(&grammar.gnu_rules)
+        .chain(&grammar.c89_rules);
+    for (lhs, rhs) in all_rules {
+        for symbol in rhs {
+            if let Symbol::Name(name, _) = symbol {
+                let class = TOKEN_CLASSES.iter().any(|(class, _)| class == name);
+                if !class && !known.contains(&&**name) {
+                    return Err(format!("{} in a rule of {} has no rules", name, lhs));
+                }
+            }
+        }
+    }
+    match grammar.non_terminals.first() {
+        Some((name, _)) if name == "Start" => Ok(grammar),
+        _ => Err("the first rule must be of Start".to_string()),
+    }
+}
+
+const GENERATED: &str = "// Generated by build.rs from src/parse/c.grammar\n\n";
+
+fn non_terminals(grammar: &Grammar) -> String {
+    let mut code = String::from(GENERATED);
+    code.push_str(
+        "#[derive(Debug, PartialEq, Clone, Copy, Hash, Eq)]\npub enum NonTerminalType {\n",
+    );
+    for (name, _) in &grammar.non_terminals {
+        let _ = writeln!(code, "    {},", name);
+    }
+    code.push_str("}\n\nimpl NonTerminalType {\n");
+    code.push_str(
+        "    /// What this construct is called in error messages, if it is worth mentioning\n",
+    );
+    code.push_str("    pub fn description(self) -> Option<&'static str> {\n");
+    code.push_str("        match self {\n");
+    for (name, description) in &grammar.descriptions {
+        let _ = writeln!(
+            code,
+            "            NonTerminalType::{} => Some({:?}),",
+            name, description
+        );
+    }
+    code.push_str("            _ => None,\n        }\n    }\n\n");
+    code.push_str(
+        "    /// Whether this only holds the items of a repetition, which belong to\n    \
+         /// the node it is in\n",
+    );
+    code.push_str("    pub fn is_hidden(self) -> bool {\n");
+    let hidden: Vec<String> = grammar
+        .non_terminals
+        .iter()
+        .filter(|(_, hidden)| *hidden)
+        .map(|(name, _)| format!("NonTerminalType::{}", name))
+        .collect();
+    match hidden.is_empty() {
+        true => code.push_str("        false\n"),
+        false => {
+            let _ = writeln!(code, "        matches!(self, {})", hidden.join(" | "));
+        }
+    }
+    code.push_str("    }\n}\n");
+    code
+}
+
+fn rule_list(rules: &[(String, Vec<Symbol>)]) -> String {
+    let mut code = String::from("    vec![\n");
+    for (lhs, rhs) in rules {
+        let symbols: Vec<String> = rhs
+            .iter()
+            .map(|symbol| match symbol {
+                Symbol::Quoted(quoted) => format!("RuleType::from({:?})", quoted),
+                Symbol::Name(name, _) => {
+                    match TOKEN_CLASSES.iter().find(|(class, _)| class == name) {
+                        Some((_, token)) => format!("RuleType::Terminal(discriminant(&{}))", token),
+                        None => format!("RuleType::NonTerminal(NonTerminalType::{})", name),
+                    }
+                }
+            })
+            .collect();
+        let _ = writeln!(
+            code,
+            "        (NonTerminalType::{}, vec![{}]),",
+            lhs,
+            symbols.join(", ")
+        );
+    }
+    code.push_str("    ]\n");
+    code
+}
+
+fn rules(grammar: &Grammar) -> String {
+    let mut code = String::from(GENERATED);
+    code.push_str("pub fn get_rules() -> Vec<(NonTerminalType, Vec<RuleType>)> {\n");
+    code.push_str(&rule_list(&grammar.rules));
+    code.push_str("}\n\n/// The GNU extensions to C11 that system headers rely on\n");
+    code.push_str("pub fn get_gnu_rules() -> Vec<(NonTerminalType, Vec<RuleType>)> {\n");
+    code.push_str(&rule_list(&grammar.gnu_rules));
+    code.push_str("}\n\n/// The C89 forms that later standards removed\n");
+    code.push_str("pub fn get_c89_rules() -> Vec<(NonTerminalType, Vec<RuleType>)> {\n");
+    code.push_str(&rule_list(&grammar.c89_rules));
+    code.push_str(
+        "}\n\n/// The associativity and tokens of each precedence level, loosest first\n",
+    );
+    code.push_str("pub fn get_precedence() -> Vec<(Associativity, Vec<RuleType>)> {\n");
+    code.push_str("    vec![\n");
+    for (associativity, tokens) in &grammar.precedence {
+        let tokens: Vec<String> = tokens
+            .iter()
+            .map(|token| format!("RuleType::from({:?})", token))
+            .collect();
+        let _ = writeln!(
+            code,
+            "        (Associativity::{}, vec![{}]),",
+            associativity,
+            tokens.join(", ")
+        );
+    }
+    code.push_str("    ]\n}\n");
+    code
+}
+
+fn wrappers(grammar: &Grammar) -> String {
+    let mut code = String::from(GENERATED);
+    code.push_str("wrappers! {\n");
+    for (name, hidden) in &grammar.non_terminals {
+        if !hidden {
+            let _ = writeln!(code, "    {}", name);
+        }
+    }
+    code.push_str("}\n");
+    code
+}
+
+/// Where a label is in one rule
+struct Labeled<'a> {
+    kind: &'a str,
+    /// How many children of the same kind come before it
+    index: usize,
+    /// Whether the rule goes on with its own nonterminal, unlabeled, which
+    /// makes it a rule of a list
+    in_list: bool,
+}
+
+/// The accessor a label stands for in the syntax of `accessors!`
+fn accessor(
+    lhs: &str,
+    label: &str,
+    found: &[Labeled],
+    rules: usize,
+    items: &HashMap<&str, &str>,
+) -> Result<String, String> {
+    let is_class = |kind: &str| TOKEN_CLASSES.iter().any(|(class, _)| *class == kind);
+    let mut kinds: Vec<&str> = Vec::new();
+    for labeled in found {
+        if !kinds.contains(&labeled.kind) {
+            kinds.push(labeled.kind);
+        }
+    }
+    if kinds.iter().any(|kind| is_class(kind)) {
+        if !kinds.iter().all(|kind| is_class(kind)) {
+            return Err(format!("{} of {} is for tokens and nodes", label, lhs));
+        }
+        let patterns: Vec<String> = kinds
+            .iter()
+            .map(|kind| format!("LexItem::{}(_)", kind))
+            .collect();
+        return Ok(format!("token {}", patterns.join(" | ")));
+    }
+    let kind = match kinds[..] {
+        [kind] => kind,
+        _ => {
+            return Err(format!(
+                "{} of {} is for {}",
+                label,
+                lhs,
+                kinds.join(" and ")
+            ))
+        }
+    };
+    // The items of a repetition are children of the node it is in
+    if let Some(item) = items.get(kind) {
+        return match is_class(item) {
+            true => Err(format!("{} of {} repeats tokens", label, lhs)),
+            false => Ok(format!("Vec<{}>", item)),
+        };
+    }
+    if found.iter().any(|labeled| labeled.in_list) {
+        return Ok(format!("Vec<{}>", kind));
+    }
+    let index = found[0].index;
+    if found.iter().any(|labeled| labeled.index != index) {
+        return Err(format!("{} of {} is not always the same child", label, lhs));
+    }
+    Ok(match found.len() == rules {
+        true => format!("{}[{}]", kind, index),
+        false => format!("Option<{}>[{}]", kind, index),
+    })
+}
+
+/// The `accessors!` for the labels in the rules of each nonterminal. A label
+/// is for a child which every rule has, or which only some rules have, or
+/// for the items of a list, which is either a repetition or recursive.
+fn accessors(grammar: &Grammar) -> Result<String, String> {
+    let all_rules: Vec<&(String, Vec<Symbol>)> = grammar
+        .rules
+        .iter()
+        .chain(&grammar.gnu_rules)
+        .chain(&grammar.c89_rules)
+        .collect();
+    // Every rule of a repetition ends in one of its items
+    let mut items: HashMap<&str, &str> = HashMap::new();
+    for (lhs, rhs) in &all_rules {
+        if let Some(Symbol::Name(item, _)) = rhs.last() {
+            if item != lhs && grammar.non_terminals.contains(&(lhs.clone(), true)) {
+                items.insert(lhs, item);
+            }
+        }
+    }
+
+    let mut code = String::from(GENERATED);
+    code.push_str("accessors! {\n");
+    for (lhs, hidden) in &grammar.non_terminals {
+        if *hidden {
+            continue;
+        }
+        let rules: Vec<&Vec<Symbol>> = all_rules
+            .iter()
+            .filter(|(name, _)| name == lhs)
+            .map(|(_, rhs)| rhs)
+            .collect();
+        let mut labels: Vec<(&str, Vec<Labeled>)> = Vec::new();
+        for rhs in &rules {
+            let in_list = rhs
+                .iter()
+                .any(|symbol| *symbol == Symbol::Name(lhs.clone(), None));
+            let mut seen = Vec::new();
+            for (position, symbol) in rhs.iter().enumerate() {
+                let (kind, label) = match symbol {
+                    Symbol::Name(kind, Some(label)) => (kind, label),
+                    _ => continue,
+                };
+                if seen.contains(&label) {
+                    return Err(format!("{} is twice in a rule of {}", label, lhs));
+                }
+                seen.push(label);
+                let index = rhs[..position]
+                    .iter()
+                    .filter(|symbol| matches!(symbol, Symbol::Name(other, _) if other == kind))
+                    .count();
+                let labeled = Labeled {
+                    kind,
+                    index,
+                    in_list,
+                };
+                match labels.iter_mut().find(|(name, _)| name == label) {
+                    Some((_, found)) => found.push(labeled),
+                    None => labels.push((label, vec![labeled])),
+                }
+            }
+        }
+        if labels.is_empty() {
+            continue;
+        }
+        let _ = writeln!(code, "    {} {{", lhs);
+        for (label, found) in &labels {
+            let accessor = accessor(lhs, label, found, rules.len(), &items)?;
+            let _ = writeln!(code, "        {}: {},", label, accessor);
+        }
+        code.push_str("    }\n");
+    }
+    code.push_str("}\n");
+    Ok(code)
+}
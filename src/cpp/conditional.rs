@@ -0,0 +1,264 @@
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+
+/// The configurations in which a piece of conditionally compiled code is there
+#[derive(Clone, Debug, PartialEq)]
+pub enum PresenceCondition {
+    Always,
+    /// `#ifdef NAME` or `#if defined(NAME)`
+    Defined(String),
+    /// Any other `#if` expression, kept as written
+    Expression(String),
+    Not(Box<PresenceCondition>),
+    /// All of the conditions, none of which is `Always` or another `And`
+    And(Vec<PresenceCondition>),
+}
+
+impl PresenceCondition {
+    pub fn and(&self, other: &PresenceCondition) -> PresenceCondition {
+        use PresenceCondition::*;
+        let parts = |condition: &PresenceCondition| match condition {
+            Always => Vec::new(),
+            And(conditions) => conditions.clone(),
+            condition => vec![condition.clone()],
+        };
+        let mut conditions = parts(self);
+        conditions.extend(parts(other));
+        match conditions.len() {
+            0 => Always,
+            1 => conditions.pop().unwrap(),
+            _ => And(conditions),
+        }
+    }
+
+    pub fn not(&self) -> PresenceCondition {
+        match self {
+            PresenceCondition::Not(condition) => (**condition).clone(),
+            condition => PresenceCondition::Not(Box::new(condition.clone())),
+        }
+    }
+
+    /// The condition as an operand of `!` or `&&`
+    fn operand(&self) -> String {
+        match self {
+            PresenceCondition::Expression(_) | PresenceCondition::And(_) => format!("({})", self),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl Display for PresenceCondition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PresenceCondition::Always => write!(f, "1"),
+            PresenceCondition::Defined(name) => write!(f, "defined({})", name),
+            PresenceCondition::Expression(expression) => write!(f, "{}", expression),
+            PresenceCondition::Not(condition) => write!(f, "!{}", condition.operand()),
+            PresenceCondition::And(conditions) => {
+                let operands: Vec<String> = conditions.iter().map(|c| c.operand()).collect();
+                write!(f, "{}", operands.join(" && "))
+            }
+        }
+    }
+}
+
+/// Part of a source file with its conditional directives kept, see
+/// `Preprocessor::keep_conditionals`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Segment {
+    /// Lines outside any directive, by character offsets
+    Text(Range<usize>),
+    /// An `#if`, `#ifdef` or `#ifndef` with its `#elif` and `#else` branches.
+    /// Without an `#else` there is an empty branch for when none of the others
+    /// are there, so exactly one branch is in each configuration.
+    Conditional(Vec<Branch>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Branch {
+    /// When this branch is there, given that the conditional is
+    pub condition: PresenceCondition,
+    pub segments: Vec<Segment>,
+}
+
+/// An `#if` whose `#endif` hasn't been reached
+struct Open {
+    branches: Vec<Branch>,
+    /// The tests of the branches so far as written, `Always` for `#else`
+    tests: Vec<PresenceCondition>,
+}
+
+impl Open {
+    /// When the branch with the test `index` is there: its test holds and none
+    /// of the ones before it do
+    fn condition(&self, index: usize) -> PresenceCondition {
+        let test = self.tests.get(index).unwrap_or(&PresenceCondition::Always);
+        self.tests[..index]
+            .iter()
+            .fold(PresenceCondition::Always, |condition, previous| {
+                condition.and(&previous.not())
+            })
+            .and(test)
+    }
+
+    fn has_else(&self) -> bool {
+        self.tests.last() == Some(&PresenceCondition::Always)
+    }
+
+    /// Finishes the open branch with `segments`
+    fn branch(&mut self, segments: Vec<Segment>) {
+        let condition = self.condition(self.branches.len());
+        self.branches.push(Branch {
+            condition,
+            segments,
+        });
+    }
+
+    fn close(mut self, segments: Vec<Segment>) -> Segment {
+        self.branch(segments);
+        if !self.has_else() {
+            self.branch(Vec::new());
+        }
+        Segment::Conditional(self.branches)
+    }
+}
+
+/// Splits preprocessed text into the lines outside conditional directives and
+/// the branches of each conditional. Directive lines are in no segment.
+pub fn conditional_segments(text: &str) -> Vec<Segment> {
+    // The segments of the file, then of the open branch of each open conditional
+    let mut levels: Vec<Vec<Segment>> = vec![Vec::new()];
+    let mut open: Vec<Open> = Vec::new();
+    let mut start = 0;
+    for line in text.split_inclusive('\n') {
+        let range = start..start + line.chars().count();
+        start = range.end;
+        let (name, rest) = match directive(line) {
+            Some(directive) => directive,
+            None => {
+                let segments = levels.last_mut().unwrap();
+                match segments.last_mut() {
+                    Some(Segment::Text(text)) if text.end == range.start => text.end = range.end,
+                    _ => segments.push(Segment::Text(range)),
+                }
+                continue;
+            }
+        };
+        let test = match name {
+            "ifdef" => PresenceCondition::Defined(rest.to_string()),
+            "ifndef" => PresenceCondition::Defined(rest.to_string()).not(),
+            _ => condition(rest),
+        };
+        match (name, open.last_mut()) {
+            ("if", _) | ("ifdef", _) | ("ifndef", _) => {
+                levels.push(Vec::new());
+                open.push(Open {
+                    branches: Vec::new(),
+                    tests: vec![test],
+                });
+            }
+            ("elif", Some(conditional)) | ("else", Some(conditional))
+                if !conditional.has_else() =>
+            {
+                conditional.branch(levels.pop().unwrap());
+                conditional.tests.push(match name {
+                    "elif" => test,
+                    _ => PresenceCondition::Always,
+                });
+                levels.push(Vec::new());
+            }
+            ("endif", Some(_)) => {
+                let segments = levels.pop().unwrap();
+                let conditional = open.pop().unwrap().close(segments);
+                levels.last_mut().unwrap().push(conditional);
+            }
+            // Stray directives are left out
+            _ => {}
+        }
+    }
+    // as are missing `#endif`s
+    while let Some(conditional) = open.pop() {
+        let segments = levels.pop().unwrap();
+        let conditional = conditional.close(segments);
+        levels.last_mut().unwrap().push(conditional);
+    }
+    levels.pop().unwrap()
+}
+
+/// The name and the rest of a conditional directive line
+fn directive(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    let line = match line.strip_prefix('#') {
+        Some(line) => line,
+        None => line.strip_prefix("%:")?,
+    };
+    let line = line.trim_start();
+    let end = line
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(line.len());
+    let (name, rest) = line.split_at(end);
+    match name {
+        "if" | "ifdef" | "ifndef" | "elif" | "else" | "endif" => Some((name, rest.trim())),
+        _ => None,
+    }
+}
+
+/// The presence condition of an `#if` or `#elif` expression
+fn condition(expression: &str) -> PresenceCondition {
+    let expression = expression.split_whitespace().collect::<Vec<_>>().join(" ");
+    if let Some(name) = defined(&expression) {
+        return PresenceCondition::Defined(name.to_string());
+    }
+    match expression.strip_prefix('!').and_then(defined) {
+        Some(name) => PresenceCondition::Defined(name.to_string()).not(),
+        None => PresenceCondition::Expression(expression),
+    }
+}
+
+/// The name in `defined NAME` or `defined(NAME)`
+fn defined(expression: &str) -> Option<&str> {
+    let name = expression.trim().strip_prefix("defined")?.trim();
+    let name = match name.strip_prefix('(') {
+        Some(name) => name.strip_suffix(')')?.trim(),
+        None => name,
+    };
+    let is_identifier = name.chars().next().is_some_and(|c| !c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    match is_identifier {
+        true => Some(name),
+        false => None,
+    }
+}
+
+/// Every way of choosing a branch of each conditional in `segments`, as the
+/// condition for it and the text that is there, up to `limit` of them
+pub fn configurations(
+    segments: &[Segment],
+    limit: usize,
+) -> Vec<(PresenceCondition, Vec<Range<usize>>)> {
+    let mut chosen = vec![(PresenceCondition::Always, Vec::new())];
+    for segment in segments {
+        let choices = match segment {
+            Segment::Text(range) => vec![(PresenceCondition::Always, vec![range.clone()])],
+            Segment::Conditional(branches) => branches
+                .iter()
+                .flat_map(|branch| {
+                    configurations(&branch.segments, limit)
+                        .into_iter()
+                        .map(move |(condition, text)| (branch.condition.and(&condition), text))
+                })
+                .collect(),
+        };
+        chosen = chosen
+            .iter()
+            .flat_map(|(condition, text)| {
+                choices.iter().map(move |(choice, more)| {
+                    let text: Vec<Range<usize>> = text.iter().chain(more).cloned().collect();
+                    (condition.and(choice), text)
+                })
+            })
+            .take(limit)
+            .collect();
+    }
+    chosen
+}
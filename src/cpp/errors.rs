@@ -0,0 +1,64 @@
+use crate::cpp::tokens::SourcePosition;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreprocessorError {
+    pub error_type: PreprocessorErrorType,
+    /// Where the directive starts
+    pub position: SourcePosition,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PreprocessorErrorType {
+    /// `#error` with its message
+    Error(String),
+    /// A directive of the standard which μcc does not implement, such as `#include`
+    UnsupportedDirective(String),
+    /// A directive name which is not a directive at all
+    InvalidDirective(String),
+    /// `#elif`, `#else` or `#endif` without an open `#if`
+    UnmatchedDirective(String),
+    /// `#elif` or `#else` after the `#else` of the same conditional
+    AfterElse(String),
+    /// An `#if` with no `#endif` before the end of the source
+    UnterminatedConditional,
+    /// A directive which needs a macro name and has none
+    MissingMacroName(String),
+    /// The expression of an `#if` or `#elif` could not be evaluated
+    InvalidExpression,
+    DivisionByZero,
+}
+
+impl Display for PreprocessorErrorType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PreprocessorErrorType::Error(message) => write!(f, "#error {}", message),
+            PreprocessorErrorType::UnsupportedDirective(name) => {
+                write!(f, "#{} is not supported", name)
+            }
+            PreprocessorErrorType::InvalidDirective(name) => {
+                write!(f, "invalid preprocessing directive #{}", name)
+            }
+            PreprocessorErrorType::UnmatchedDirective(name) => write!(f, "#{} without #if", name),
+            PreprocessorErrorType::AfterElse(name) => write!(f, "#{} after #else", name),
+            PreprocessorErrorType::UnterminatedConditional => {
+                f.write_str("unterminated conditional directive")
+            }
+            PreprocessorErrorType::MissingMacroName(name) => {
+                write!(f, "#{} needs a macro name", name)
+            }
+            PreprocessorErrorType::InvalidExpression => {
+                f.write_str("invalid expression in conditional directive")
+            }
+            PreprocessorErrorType::DivisionByZero => {
+                f.write_str("division by zero in conditional directive")
+            }
+        }
+    }
+}
+
+impl Display for PreprocessorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.error_type.fmt(f)
+    }
+}
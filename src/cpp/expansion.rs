@@ -0,0 +1,325 @@
+use crate::cpp::preprocessor::{MacroExpansion, Preprocessor};
+use crate::cpp::tokens::{spell, tokenize_str, PpToken, PpTokenKind};
+use std::collections::{HashSet, VecDeque};
+
+/// The arguments of a function-like macro invocation
+struct Invocation {
+    raw: Vec<Vec<PpToken>>,
+    expanded: Vec<Vec<PpToken>>,
+    close: PpToken,
+    newlines: Vec<PpToken>,
+}
+
+impl<T: Iterator<Item = char>> Preprocessor<T> {
+    /// Returns the next token of `queue` that is not a macro invocation, expanding
+    /// every invocation before it. If `refill` is set, lines are read from the
+    /// source whenever `queue` runs out.
+    pub(super) fn expand_next(
+        &mut self,
+        queue: &mut VecDeque<PpToken>,
+        refill: bool,
+    ) -> Option<PpToken> {
+        loop {
+            let token = match queue.pop_front() {
+                Some(token) => token,
+                None if refill && self.fill_line(queue) => continue,
+                None => return None,
+            };
+            if token.kind != PpTokenKind::Identifier || token.hide_set.contains(&token.text) {
+                return Some(token);
+            }
+
+            if let Some(replacement) = self.replacements.get(&token.text).cloned() {
+                let mut hide_set = token.hide_set.clone();
+                hide_set.insert(token.text.clone());
+                let result = substitute(&token, &replacement, &[], None, hide_set);
+
+                self.trace(MacroExpansion {
+                    name: token.text.clone(),
+                    position: token.position,
+                    arguments: None,
+                    expanded_arguments: None,
+                    replacement: spell(&result),
+                });
+                result.into_iter().rev().for_each(|t| queue.push_front(t));
+            } else if let Some(function) = self.functions.get(&token.text) {
+                let parameters = function.variables.clone();
+                let variadic = function.variadic;
+                let replacement = function.replacement.clone();
+
+                let invocation = match self.read_arguments(queue, refill, &parameters, variadic) {
+                    Ok(invocation) => invocation,
+                    Err(consumed) => {
+                        consumed.into_iter().rev().for_each(|t| queue.push_front(t));
+                        return Some(token);
+                    }
+                };
+
+                let mut hide_set: HashSet<String> = token
+                    .hide_set
+                    .intersection(&invocation.close.hide_set)
+                    .cloned()
+                    .collect();
+                hide_set.insert(token.text.clone());
+
+                let mut name = token.clone();
+                name.origin = token.origin.start..token.origin.end.max(invocation.close.origin.end);
+                let result = substitute(
+                    &name,
+                    &replacement,
+                    &parameters,
+                    Some(&invocation),
+                    hide_set,
+                );
+
+                self.trace(MacroExpansion {
+                    name: token.text.clone(),
+                    position: token.position,
+                    arguments: Some(invocation.raw.iter().map(|arg| spell(arg)).collect()),
+                    expanded_arguments: Some(
+                        invocation.expanded.iter().map(|arg| spell(arg)).collect(),
+                    ),
+                    replacement: spell(&result),
+                });
+                // Newlines swallowed by the invocation go after it to keep the line count
+                result
+                    .into_iter()
+                    .chain(invocation.newlines)
+                    .rev()
+                    .for_each(|t| queue.push_front(t));
+            } else {
+                return Some(token);
+            }
+        }
+    }
+
+    fn trace(&mut self, expansion: MacroExpansion) {
+        if let Some(tracer) = &mut self.tracer {
+            tracer(&expansion);
+        }
+    }
+
+    /// Fully expands a list of tokens without reading any more of the source
+    pub(super) fn expand_all(&mut self, tokens: &[PpToken]) -> Vec<PpToken> {
+        let mut queue: VecDeque<PpToken> = tokens.iter().cloned().collect();
+        let mut expanded = Vec::new();
+        while let Some(token) = self.expand_next(&mut queue, false) {
+            expanded.push(token);
+        }
+        expanded
+    }
+
+    /// Reads the parenthesised arguments of a function-like macro. If the name
+    /// is not followed by arguments, every token read is returned as the error.
+    fn read_arguments(
+        &mut self,
+        queue: &mut VecDeque<PpToken>,
+        refill: bool,
+        parameters: &[String],
+        variadic: bool,
+    ) -> Result<Invocation, Vec<PpToken>> {
+        let mut consumed = Vec::new();
+        let mut raw = vec![Vec::new()];
+        let mut newlines = Vec::new();
+        let mut depth = 0;
+
+        let close = loop {
+            let token = match queue.pop_front() {
+                Some(token) => token,
+                None if refill && self.fill_line(queue) => continue,
+                None => return Err(consumed),
+            };
+            consumed.push(token.clone());
+
+            if raw.len() == 1 && raw[0].is_empty() && depth == 0 {
+                if token.is("(") {
+                    depth = 1;
+                    continue;
+                } else if !token.is_whitespace() {
+                    queue.push_front(consumed.pop().unwrap());
+                    return Err(consumed);
+                }
+            }
+
+            if token.kind == PpTokenKind::Newline {
+                newlines.push(token.clone());
+            }
+            if depth == 0 {
+                continue;
+            } else if token.is("(") {
+                depth += 1;
+            } else if token.is(")") {
+                depth -= 1;
+                if depth == 0 {
+                    break token;
+                }
+            } else if token.is(",") && depth == 1 && (!variadic || raw.len() <= parameters.len()) {
+                raw.push(Vec::new());
+                continue;
+            }
+            raw.last_mut().unwrap().push(token);
+        };
+
+        for argument in &mut raw {
+            while argument.first().is_some_and(PpToken::is_whitespace) {
+                argument.remove(0);
+            }
+            while argument.last().is_some_and(PpToken::is_whitespace) {
+                argument.pop();
+            }
+        }
+
+        let count = parameters.len() + usize::from(variadic);
+        if raw.len() == 1 && raw[0].is_empty() && count == 0 {
+            raw.clear();
+        } else if variadic && raw.len() == parameters.len() {
+            raw.push(Vec::new());
+        }
+        if raw.len() != count {
+            return Err(consumed);
+        }
+
+        let expanded = raw.iter().map(|arg| self.expand_all(arg)).collect();
+        Ok(Invocation {
+            raw,
+            expanded,
+            close,
+            newlines,
+        })
+    }
+}
+
+/// Builds the replacement list of a macro invocation, handling `#` and `##`
+fn substitute(
+    name: &PpToken,
+    replacement: &str,
+    parameters: &[String],
+    invocation: Option<&Invocation>,
+    hide_set: HashSet<String>,
+) -> Vec<PpToken> {
+    let body = tokenize_str(replacement, name.position);
+    let parameter = |token: &PpToken| {
+        if token.kind != PpTokenKind::Identifier {
+            None
+        } else if token.text == "__VA_ARGS__" {
+            Some(parameters.len())
+        } else {
+            parameters.iter().position(|p| *p == token.text)
+        }
+    };
+    let next_solid = |index: usize| body[index..].iter().find(|t| !t.is_whitespace());
+
+    let mut substituted: Vec<PpToken> = Vec::new();
+    let mut index = 0;
+    while index < body.len() {
+        let token = &body[index];
+        index += 1;
+
+        let invocation = match invocation {
+            Some(invocation) => invocation,
+            None => {
+                substituted.push(token.clone());
+                continue;
+            }
+        };
+
+        if token.is("#") || token.is("%:") {
+            let argument = next_solid(index).and_then(parameter);
+            if let Some(argument) = argument {
+                index += body[index..]
+                    .iter()
+                    .position(|t| !t.is_whitespace())
+                    .unwrap()
+                    + 1;
+                substituted.push(stringify(&invocation.raw[argument], name));
+                continue;
+            }
+        }
+
+        match parameter(token) {
+            Some(argument) => {
+                let pasted = next_solid(index).is_some_and(|t| t.is("##"))
+                    || substituted
+                        .iter()
+                        .rfind(|t| !t.is_whitespace())
+                        .is_some_and(|t| t.is("##"));
+                let tokens = if pasted {
+                    &invocation.raw[argument]
+                } else {
+                    &invocation.expanded[argument]
+                };
+                if tokens.is_empty() {
+                    substituted.push(PpToken::new(
+                        PpTokenKind::Placemarker,
+                        String::new(),
+                        name.position,
+                    ));
+                }
+                substituted.extend(tokens.iter().cloned());
+            }
+            None => substituted.push(token.clone()),
+        }
+    }
+
+    let mut result: Vec<PpToken> = Vec::new();
+    let mut tokens = substituted.into_iter();
+    while let Some(token) = tokens.next() {
+        if !token.is("##") || result.is_empty() {
+            result.push(token);
+            continue;
+        }
+        while result.last().is_some_and(PpToken::is_whitespace) {
+            result.pop();
+        }
+        let left = result.pop().unwrap();
+        match tokens.by_ref().find(|t| !t.is_whitespace()) {
+            Some(right) => result.extend(paste(&left, &right)),
+            None => result.push(left),
+        }
+    }
+
+    result
+        .into_iter()
+        .filter(|token| token.kind != PpTokenKind::Placemarker)
+        .map(|mut token| {
+            token.origin = name.origin.clone();
+            token.position = name.position;
+            token.hide_set.extend(hide_set.iter().cloned());
+            token
+        })
+        .collect()
+}
+
+fn stringify(tokens: &[PpToken], name: &PpToken) -> PpToken {
+    let mut text = String::from("\"");
+    for token in tokens {
+        if token.kind == PpTokenKind::Literal {
+            text.extend(token.text.chars().flat_map(|ch| match ch {
+                '"' | '\\' => vec!['\\', ch],
+                _ => vec![ch],
+            }));
+        } else if token.is_whitespace() {
+            if !text.ends_with(' ') {
+                text.push(' ');
+            }
+        } else {
+            text.push_str(&token.text);
+        }
+    }
+    text.push('"');
+    PpToken::new(PpTokenKind::Literal, text, name.position)
+}
+
+fn paste(left: &PpToken, right: &PpToken) -> Vec<PpToken> {
+    let text = format!("{}{}", left.text, right.text);
+    if text.is_empty() {
+        return vec![left.clone()];
+    }
+    tokenize_str(&text, left.position)
+        .into_iter()
+        .map(|mut token| {
+            token.hide_set = left.hide_set.clone();
+            token
+        })
+        .collect()
+}
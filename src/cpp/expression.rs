@@ -0,0 +1,242 @@
+use crate::cpp::errors::PreprocessorErrorType;
+use crate::cpp::preprocessor::Preprocessor;
+use crate::cpp::tokens::{PpToken, PpTokenKind};
+
+/// The binary operators of conditional directives, loosest first
+const BINARY: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", ">", "<=", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+type EvalResult = Result<i64, PreprocessorErrorType>;
+
+impl<T: Iterator<Item = char>> Preprocessor<T> {
+    /// Evaluates the expression of an `#if` or `#elif`. `defined` is replaced
+    /// before macros are expanded, and every identifier left afterwards is 0.
+    pub(super) fn evaluate(&mut self, tokens: &[PpToken]) -> Result<bool, PreprocessorErrorType> {
+        let mut replaced = Vec::new();
+        let mut index = 0;
+        while index < tokens.len() {
+            let token = &tokens[index];
+            index += 1;
+            if token.kind != PpTokenKind::Identifier || token.text != "defined" {
+                replaced.push(token.clone());
+                continue;
+            }
+            let parenthesized = tokens.get(index).is_some_and(|token| token.is("("));
+            if parenthesized {
+                index += 1;
+            }
+            let name = match tokens.get(index) {
+                Some(name) if name.kind == PpTokenKind::Identifier => &name.text,
+                _ => return Err(PreprocessorErrorType::InvalidExpression),
+            };
+            index += 1;
+            if parenthesized {
+                if !tokens.get(index).is_some_and(|token| token.is(")")) {
+                    return Err(PreprocessorErrorType::InvalidExpression);
+                }
+                index += 1;
+            }
+            let defined = self.replacements.contains_key(name) || self.functions.contains_key(name);
+            let value = if defined { "1" } else { "0" };
+            replaced.push(PpToken::new(
+                PpTokenKind::Number,
+                value.to_string(),
+                token.position,
+            ));
+        }
+
+        let expanded = self.expand_all(&replaced);
+        let tokens: Vec<&PpToken> = expanded
+            .iter()
+            .filter(|token| !token.is_whitespace())
+            .collect();
+        let mut evaluator = Evaluator {
+            tokens: &tokens,
+            index: 0,
+            unevaluated: 0,
+        };
+        let value = evaluator.conditional()?;
+        if evaluator.index != tokens.len() {
+            return Err(PreprocessorErrorType::InvalidExpression);
+        }
+        Ok(value != 0)
+    }
+}
+
+/// Evaluates fully expanded tokens with the precedence of C expressions
+struct Evaluator<'a> {
+    tokens: &'a [&'a PpToken],
+    index: usize,
+    /// How many operands we are inside whose value is never used, like the
+    /// right side of `0 && …`, where dividing by zero is not an error
+    unevaluated: usize,
+}
+
+impl<'a> Evaluator<'a> {
+    fn peek(&self) -> Option<&'a PpToken> {
+        self.tokens.get(self.index).copied()
+    }
+
+    fn eat(&mut self, punctuator: &str) -> bool {
+        let found = self.peek().is_some_and(|token| token.is(punctuator));
+        if found {
+            self.index += 1;
+        }
+        found
+    }
+
+    /// Parses an operand whose value is only used if `used`
+    fn operand(&mut self, used: bool, parse: impl FnOnce(&mut Self) -> EvalResult) -> EvalResult {
+        if !used {
+            self.unevaluated += 1;
+        }
+        let value = parse(self);
+        if !used {
+            self.unevaluated -= 1;
+        }
+        value
+    }
+
+    fn conditional(&mut self) -> EvalResult {
+        let condition = self.binary(0)?;
+        if !self.eat("?") {
+            return Ok(condition);
+        }
+        let then = self.operand(condition != 0, Self::conditional)?;
+        if !self.eat(":") {
+            return Err(PreprocessorErrorType::InvalidExpression);
+        }
+        let otherwise = self.operand(condition == 0, Self::conditional)?;
+        Ok(if condition != 0 { then } else { otherwise })
+    }
+
+    fn binary(&mut self, level: usize) -> EvalResult {
+        let operators = match BINARY.get(level) {
+            Some(operators) => *operators,
+            None => return self.unary(),
+        };
+        let mut left = self.binary(level + 1)?;
+        while let Some(&operator) = self
+            .peek()
+            .and_then(|token| operators.iter().find(|&&operator| token.is(operator)))
+        {
+            self.index += 1;
+            let used = match operator {
+                "&&" => left != 0,
+                "||" => left == 0,
+                _ => true,
+            };
+            let right = self.operand(used, |evaluator| evaluator.binary(level + 1))?;
+            left = self.apply(operator, left, right)?;
+        }
+        Ok(left)
+    }
+
+    fn apply(&self, operator: &str, left: i64, right: i64) -> EvalResult {
+        Ok(match operator {
+            "||" => (left != 0 || right != 0) as i64,
+            "&&" => (left != 0 && right != 0) as i64,
+            "|" => left | right,
+            "^" => left ^ right,
+            "&" => left & right,
+            "==" => (left == right) as i64,
+            "!=" => (left != right) as i64,
+            "<" => (left < right) as i64,
+            ">" => (left > right) as i64,
+            "<=" => (left <= right) as i64,
+            ">=" => (left >= right) as i64,
+            "<<" => left.wrapping_shl(right as u32),
+            ">>" => left.wrapping_shr(right as u32),
+            "+" => left.wrapping_add(right),
+            "-" => left.wrapping_sub(right),
+            "*" => left.wrapping_mul(right),
+            "/" | "%" if right == 0 => {
+                if self.unevaluated == 0 {
+                    return Err(PreprocessorErrorType::DivisionByZero);
+                }
+                0
+            }
+            "/" => left.wrapping_div(right),
+            _ => left.wrapping_rem(right),
+        })
+    }
+
+    fn unary(&mut self) -> EvalResult {
+        if self.eat("+") {
+            self.unary()
+        } else if self.eat("-") {
+            Ok(self.unary()?.wrapping_neg())
+        } else if self.eat("!") {
+            Ok((self.unary()? == 0) as i64)
+        } else if self.eat("~") {
+            Ok(!self.unary()?)
+        } else if self.eat("(") {
+            let value = self.conditional()?;
+            if !self.eat(")") {
+                return Err(PreprocessorErrorType::InvalidExpression);
+            }
+            Ok(value)
+        } else {
+            let token = self
+                .peek()
+                .ok_or(PreprocessorErrorType::InvalidExpression)?;
+            self.index += 1;
+            match token.kind {
+                PpTokenKind::Identifier => Ok(0),
+                PpTokenKind::Number => integer(&token.text),
+                PpTokenKind::Literal => character(&token.text),
+                _ => Err(PreprocessorErrorType::InvalidExpression),
+            }
+        }
+    }
+}
+
+/// The value of an integer constant, ignoring its suffix
+fn integer(text: &str) -> EvalResult {
+    let digits = text.trim_end_matches(['u', 'U', 'l', 'L']);
+    let (digits, radix) = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        (hex, 16)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        (&digits[1..], 8)
+    } else {
+        (digits, 10)
+    };
+    u64::from_str_radix(digits, radix)
+        .map(|value| value as i64)
+        .map_err(|_| PreprocessorErrorType::InvalidExpression)
+}
+
+/// The value of a character constant with at most a simple escape sequence
+fn character(text: &str) -> EvalResult {
+    let inner = text
+        .strip_prefix('\'')
+        .and_then(|text| text.strip_suffix('\''))
+        .ok_or(PreprocessorErrorType::InvalidExpression)?;
+    let mut chars = inner.chars();
+    let value = match (chars.next(), chars.next(), chars.next()) {
+        (Some('\\'), Some(escape), None) => match escape {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' | '\'' | '"' | '?' => escape,
+            _ => return Err(PreprocessorErrorType::InvalidExpression),
+        },
+        (Some(ch), None, None) if ch != '\\' => ch,
+        _ => return Err(PreprocessorErrorType::InvalidExpression),
+    };
+    Ok(value as i64)
+}
@@ -1,2 +1,10 @@
 #[allow(dead_code)]
+pub mod conditional;
+pub mod errors;
+mod expansion;
+mod expression;
+#[allow(dead_code)]
 pub mod preprocessor;
+#[cfg(test)]
+mod tests;
+mod tokens;
@@ -1,24 +1,495 @@
-use std::collections::HashMap;
+use crate::cpp::errors::{PreprocessorError, PreprocessorErrorType};
+pub use crate::cpp::tokens::SourcePosition;
+use crate::cpp::tokens::{needs_separator, spell, tokenize, PpToken, PpTokenKind, SourceChar};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Display, Formatter};
+use std::iter::Peekable;
+use std::ops::Range;
 use std::path::PathBuf;
 
 pub struct PreprocessorFunction {
-    variables: Vec<String>,
-    replacement: String,
+    pub(super) variables: Vec<String>,
+    pub(super) variadic: bool,
+    pub(super) replacement: String,
 }
 
+/// One step of macro expansion, as reported by `--trace-macros`
+#[derive(Clone, Debug, PartialEq)]
+pub struct MacroExpansion {
+    pub name: String,
+    pub position: SourcePosition,
+    /// The arguments as written, `None` for object-like macros
+    pub arguments: Option<Vec<String>>,
+    /// The arguments after they were fully macro-expanded on their own
+    pub expanded_arguments: Option<Vec<String>>,
+    /// The replacement list after substitution, before it is rescanned
+    pub replacement: String,
+}
+
+impl Display for MacroExpansion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (&self.arguments, &self.expanded_arguments) {
+            (Some(arguments), Some(expanded)) => {
+                writeln!(
+                    f,
+                    "{}: expanding `{}({})`",
+                    self.position,
+                    self.name,
+                    arguments.join(", ")
+                )?;
+                writeln!(f, "    pre-expanded arguments: `{}`", expanded.join("`, `"))?;
+            }
+            _ => writeln!(f, "{}: expanding `{}`", self.position, self.name)?,
+        }
+        write!(f, "    replacement: `{}`", self.replacement)
+    }
+}
+
+type Tracer = Box<dyn FnMut(&MacroExpansion)>;
+
+/// The directives of conditional compilation
+const CONDITIONALS: &[&str] = &["if", "ifdef", "ifndef", "elif", "else", "endif"];
+
+/// An `#if`, `#ifdef` or `#ifndef` whose `#endif` has not been read yet
+struct Conditional {
+    /// Whether the lines around the conditional are kept
+    outer: bool,
+    /// Whether the lines of the current branch are kept
+    active: bool,
+    /// Whether any branch so far was kept, so that no later one can be
+    taken: bool,
+    has_else: bool,
+    position: SourcePosition,
+}
+
+/// Expands macros in a stream of characters.
+///
+/// `#define`, `#undef`, the conditional directives and `#error` are understood,
+/// `#pragma` and line markers are ignored and every other directive, including
+/// `#include`, is reported in `errors`. Directive lines and lines joined by a
+/// macro invocation are replaced with empty lines so that line numbers stay the
+/// same as in the original source.
 pub struct Preprocessor<T: Iterator<Item = char>> {
-    replacements: HashMap<String, String>,
-    functions: HashMap<String, PreprocessorFunction>,
+    pub(super) replacements: HashMap<String, String>,
+    pub(super) functions: HashMap<String, PreprocessorFunction>,
     search_path: Vec<PathBuf>,
     local_path: Vec<PathBuf>,
     lookahead: Vec<char>,
-    it: T,
+    it: Peekable<T>,
+    position: SourcePosition,
+    pending: VecDeque<PpToken>,
+    pub(super) tracer: Option<Tracer>,
+    last_emitted: Option<(char, Range<usize>)>,
+    keep_conditionals: bool,
+    conditionals: Vec<Conditional>,
+    /// How deep inside conditionals kept by `keep_conditionals` we are
+    kept_depth: usize,
+    errors: Vec<PreprocessorError>,
+}
+
+impl<T: Iterator<Item = char>> Preprocessor<T> {
+    pub fn new(it: T) -> Preprocessor<T> {
+        Preprocessor {
+            replacements: HashMap::new(),
+            functions: HashMap::new(),
+            search_path: Vec::new(),
+            local_path: Vec::new(),
+            lookahead: Vec::new(),
+            it: it.peekable(),
+            position: SourcePosition {
+                character: 0,
+                line: 1,
+                column: 1,
+            },
+            pending: VecDeque::new(),
+            tracer: None,
+            last_emitted: None,
+            keep_conditionals: false,
+            conditionals: Vec::new(),
+            kept_depth: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Calls `tracer` for every macro expansion step
+    pub fn trace_macros(mut self, tracer: impl FnMut(&MacroExpansion) + 'static) -> Self {
+        self.tracer = Some(Box::new(tracer));
+        self
+    }
+
+    /// Keeps the lines of `#if`, `#ifdef`, `#ifndef`, `#elif`, `#else` and
+    /// `#endif` as they are, so that every branch can be parsed. See
+    /// `cpp::conditional`.
+    pub fn keep_conditionals(mut self) -> Self {
+        self.keep_conditionals = true;
+        self
+    }
+
+    /// The errors in the directives read so far, which are complete once every
+    /// character has been read
+    pub fn errors(&self) -> &[PreprocessorError] {
+        &self.errors
+    }
+
+    fn error(&mut self, error_type: PreprocessorErrorType, position: SourcePosition) {
+        self.errors.push(PreprocessorError {
+            error_type,
+            position,
+        });
+    }
+
+    /// Whether the lines being read are outside every skipped conditional branch
+    fn active(&self) -> bool {
+        self.conditionals
+            .last()
+            .is_none_or(|conditional| conditional.active)
+    }
+
+    fn next_source_char(&mut self) -> Option<SourceChar> {
+        let ch = self.it.next()?;
+        let source_char = SourceChar {
+            ch,
+            position: self.position,
+        };
+        self.position.character += 1;
+        if ch == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
+        }
+        Some(source_char)
+    }
+
+    /// Reads one logical line, joining continued lines and replacing comments
+    /// with a single space. Also returns how many newlines were consumed.
+    fn read_line(&mut self) -> Option<(Vec<SourceChar>, usize)> {
+        let mut line = Vec::new();
+        let mut newlines = 0;
+
+        while let Some(ch) = self.next_source_char() {
+            match (ch.ch, self.it.peek()) {
+                ('\n', _) => {
+                    newlines += 1;
+                    break;
+                }
+                ('\\', Some('\n')) => {
+                    self.next_source_char();
+                    newlines += 1;
+                }
+                ('/', Some('/')) => {
+                    while self.it.peek().is_some_and(|&c| c != '\n') {
+                        self.next_source_char();
+                    }
+                }
+                ('/', Some('*')) => {
+                    self.next_source_char();
+                    let mut last = ' ';
+                    while let Some(c) = self.next_source_char() {
+                        if c.ch == '\n' {
+                            newlines += 1;
+                        } else if last == '*' && c.ch == '/' {
+                            break;
+                        }
+                        last = c.ch;
+                    }
+                    line.push(SourceChar { ch: ' ', ..ch });
+                }
+                ('"', _) | ('\'', _) => {
+                    line.push(ch);
+                    while let Some(&c) = self.it.peek() {
+                        if c == '\n' {
+                            break;
+                        }
+                        let c = self.next_source_char().unwrap();
+                        line.push(c);
+                        if c.ch == ch.ch {
+                            break;
+                        } else if c.ch == '\\' && self.it.peek().is_some_and(|&c| c != '\n') {
+                            line.push(self.next_source_char().unwrap());
+                        }
+                    }
+                }
+                _ => line.push(ch),
+            }
+        }
+
+        if line.is_empty() && newlines == 0 {
+            None
+        } else {
+            Some((line, newlines))
+        }
+    }
+
+    /// Reads the next line into `queue`, running it as a directive if it is one.
+    /// Returns false at the end of the source.
+    pub(super) fn fill_line(&mut self, queue: &mut VecDeque<PpToken>) -> bool {
+        let (line, newlines) = match self.read_line() {
+            Some(line) => line,
+            None => {
+                for conditional in std::mem::take(&mut self.conditionals) {
+                    let error_type = PreprocessorErrorType::UnterminatedConditional;
+                    self.error(error_type, conditional.position);
+                }
+                return false;
+            }
+        };
+        let tokens = tokenize(&line);
+
+        let is_directive = tokens
+            .iter()
+            .find(|token| !token.is_whitespace())
+            .is_some_and(|token| token.is("#") || token.is("%:"));
+        let name = tokens
+            .iter()
+            .filter(|token| !token.is_whitespace())
+            .nth(1)
+            .map(|name| name.text.as_str());
+        let kept = self.keep_conditionals && name.is_some_and(|name| CONDITIONALS.contains(&name));
+        if is_directive && kept {
+            self.kept_depth = match name {
+                Some("if") | Some("ifdef") | Some("ifndef") => self.kept_depth + 1,
+                Some("endif") => self.kept_depth.saturating_sub(1),
+                _ => self.kept_depth,
+            };
+            // Nothing in the line is expanded
+            queue.extend(tokens.into_iter().map(|mut token| {
+                token.hide_set.insert(token.text.clone());
+                token
+            }));
+        } else if is_directive {
+            self.directive(&tokens);
+        } else if self.active() {
+            queue.extend(tokens);
+        }
+
+        let end = PpToken::new(PpTokenKind::Newline, "\n".to_string(), self.position);
+        queue.extend((0..newlines).map(|_| end.clone()));
+        true
+    }
+
+    fn directive(&mut self, tokens: &[PpToken]) {
+        let mut tokens = tokens.iter().filter(|token| !token.is_whitespace());
+        let position = match tokens.next() {
+            Some(hash) => hash.position,
+            None => return,
+        };
+        // A `#` on its own is the null directive
+        let name = match tokens.next() {
+            Some(name) => name,
+            None => return,
+        };
+        let rest: Vec<PpToken> = tokens.cloned().collect();
+
+        if CONDITIONALS.contains(&name.text.as_str()) {
+            self.conditional(&name.text, &rest, position);
+            return;
+        }
+        if !self.active() {
+            return;
+        }
+        match name.text.as_str() {
+            "define" => self.define(rest),
+            "undef" => {
+                if let Some(name) = rest.first() {
+                    self.replacements.remove(&name.text);
+                    self.functions.remove(&name.text);
+                }
+            }
+            // Line markers, including GCC's `# 1 "file.c"`, only change the
+            // positions in diagnostics, which are always those of this source
+            "pragma" | "line" => {}
+            _ if name.kind == PpTokenKind::Number => {}
+            // With `keep_conditionals` the branch might never be compiled
+            "error" if self.kept_depth > 0 => {}
+            "error" => {
+                let message = replacement_text(&rest);
+                self.error(PreprocessorErrorType::Error(message), position);
+            }
+            "include" => {
+                let error_type = PreprocessorErrorType::UnsupportedDirective(name.text.clone());
+                self.error(error_type, position);
+            }
+            _ => {
+                let error_type = PreprocessorErrorType::InvalidDirective(name.text.clone());
+                self.error(error_type, position);
+            }
+        }
+    }
+
+    /// Runs `#if`, `#ifdef`, `#ifndef`, `#elif`, `#else` or `#endif`
+    fn conditional(&mut self, name: &str, rest: &[PpToken], position: SourcePosition) {
+        if let "if" | "ifdef" | "ifndef" = name {
+            let outer = self.active();
+            // The condition of a skipped conditional is not even evaluated
+            let active = outer && self.test(name, rest, position);
+            self.conditionals.push(Conditional {
+                outer,
+                active,
+                taken: active,
+                has_else: false,
+                position,
+            });
+            return;
+        }
+
+        let (outer, taken, has_else) = match self.conditionals.last() {
+            Some(conditional) => (conditional.outer, conditional.taken, conditional.has_else),
+            None => {
+                let error_type = PreprocessorErrorType::UnmatchedDirective(name.to_string());
+                self.error(error_type, position);
+                return;
+            }
+        };
+        if name == "endif" {
+            self.conditionals.pop();
+            return;
+        }
+        if has_else {
+            self.error(PreprocessorErrorType::AfterElse(name.to_string()), position);
+            return;
+        }
+        let active = outer && !taken && (name == "else" || self.test(name, rest, position));
+        if let Some(conditional) = self.conditionals.last_mut() {
+            conditional.active = active;
+            conditional.taken |= active;
+            conditional.has_else = name == "else";
+        }
+    }
+
+    /// Whether the condition of an `#if`, `#ifdef`, `#ifndef` or `#elif` holds.
+    /// A condition with an error does not.
+    fn test(&mut self, name: &str, rest: &[PpToken], position: SourcePosition) -> bool {
+        let result = match (name, rest.first()) {
+            ("ifdef", Some(macro_name)) | ("ifndef", Some(macro_name))
+                if macro_name.kind == PpTokenKind::Identifier =>
+            {
+                let defined = self.replacements.contains_key(&macro_name.text)
+                    || self.functions.contains_key(&macro_name.text);
+                Ok(defined == (name == "ifdef"))
+            }
+            ("ifdef", _) | ("ifndef", _) => {
+                Err(PreprocessorErrorType::MissingMacroName(name.to_string()))
+            }
+            _ => self.evaluate(rest),
+        };
+        result.unwrap_or_else(|error_type| {
+            self.error(error_type, position);
+            false
+        })
+    }
+
+    fn define(&mut self, tokens: Vec<PpToken>) {
+        let name = match tokens.first() {
+            Some(name) if name.kind == PpTokenKind::Identifier => name.clone(),
+            _ => return,
+        };
+        let is_function = tokens
+            .get(1)
+            .is_some_and(|paren| paren.is("(") && paren.origin.start == name.origin.end);
+
+        self.replacements.remove(&name.text);
+        self.functions.remove(&name.text);
+
+        if is_function {
+            let close = match tokens.iter().position(|token| token.is(")")) {
+                Some(close) => close,
+                None => return,
+            };
+            let mut variables = Vec::new();
+            let mut variadic = false;
+            for token in &tokens[2..close] {
+                match token.kind {
+                    PpTokenKind::Identifier => variables.push(token.text.clone()),
+                    _ if token.is("...") => variadic = true,
+                    _ => {}
+                }
+            }
+            let replacement = replacement_text(&tokens[close + 1..]);
+            self.functions.insert(
+                name.text,
+                PreprocessorFunction {
+                    variables,
+                    variadic,
+                    replacement,
+                },
+            );
+        } else {
+            let replacement = replacement_text(&tokens[1..]);
+            self.replacements.insert(name.text, replacement);
+        }
+    }
+
+    /// Returns the next fully expanded token of the source
+    fn next_token(&mut self) -> Option<PpToken> {
+        let mut pending = std::mem::take(&mut self.pending);
+        let token = self.expand_next(&mut pending, true);
+        self.pending = pending;
+        token
+    }
+
+    fn emit(&mut self, token: PpToken) {
+        let first = match token.text.chars().next() {
+            Some(first) => first,
+            None => return,
+        };
+        let mut text = String::new();
+        if let Some((last, origin)) = &self.last_emitted {
+            if *origin != token.origin && needs_separator(*last, first) {
+                text.push(' ');
+            }
+        }
+        text.push_str(&token.text);
+
+        self.last_emitted = if token.is_whitespace() {
+            None
+        } else {
+            text.chars().last().map(|last| (last, token.origin))
+        };
+        self.lookahead.extend(text.chars().rev());
+    }
+}
+
+/// Rebuilds the replacement list of a directive, which had its whitespace removed
+fn replacement_text(tokens: &[PpToken]) -> String {
+    let mut text = String::new();
+    let mut last_end = tokens.first().map(|token| token.origin.start);
+    for token in tokens {
+        if last_end != Some(token.origin.start) {
+            text.push(' ');
+        }
+        text.push_str(&token.text);
+        last_end = Some(token.origin.end);
+    }
+    text
 }
 
 impl<T: Iterator<Item = char>> Iterator for Preprocessor<T> {
     type Item = char;
 
     fn next(&mut self) -> Option<Self::Item> {
-        unimplemented!()
+        while self.lookahead.is_empty() {
+            let token = self.next_token()?;
+            self.emit(token);
+        }
+        self.lookahead.pop()
     }
 }
+
+/// Returns the fully macro-expanded text of everything that `range` of `source`
+/// expands to, where `range` is in characters. A macro invocation that overlaps
+/// the range is included completely, and an empty range selects whatever is at
+/// its position, which is what an editor needs to show an expansion on hover.
+pub fn expand_range(source: &str, range: Range<usize>) -> String {
+    let mut preprocessor = Preprocessor::new(source.chars());
+    let end = range.end.max(range.start + 1);
+
+    let mut tokens = Vec::new();
+    while let Some(token) = preprocessor.next_token() {
+        if token.origin.start < end && range.start < token.origin.end {
+            tokens.push(token);
+        }
+    }
+
+    spell(&tokens).trim_start().to_string()
+}
@@ -0,0 +1,253 @@
+use crate::cpp::conditional::{
+    conditional_segments, configurations, Branch, PresenceCondition, Segment,
+};
+use crate::cpp::errors::PreprocessorErrorType::*;
+use crate::cpp::preprocessor::{expand_range, MacroExpansion, Preprocessor};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn preprocess(text: &str) -> String {
+    Preprocessor::new(text.chars()).collect()
+}
+
+fn trace(text: &str) -> Vec<MacroExpansion> {
+    let steps = Rc::new(RefCell::new(Vec::new()));
+    let log = steps.clone();
+    Preprocessor::new(text.chars())
+        .trace_macros(move |step| log.borrow_mut().push(step.clone()))
+        .for_each(drop);
+    let steps = steps.borrow().clone();
+    steps
+}
+
+#[test]
+fn test_no_macros() {
+    let text = "int main() {\n    return 0;\n}\n";
+    assert_eq!(preprocess(text), text);
+}
+
+#[test]
+fn test_object_macro() {
+    assert_eq!(
+        preprocess("#define SIZE 10\nint a[SIZE];\n"),
+        "\nint a[10];\n"
+    );
+}
+
+#[test]
+fn test_function_macro() {
+    assert_eq!(
+        preprocess("#define MAX(a, b) ((a) > (b) ? (a) : (b))\nMAX(x, y + 1)\n"),
+        "\n((x) > (y + 1) ? (x) : (y + 1))\n"
+    );
+}
+
+#[test]
+fn test_function_macro_without_arguments() {
+    assert_eq!(preprocess("#define f(x) x\nint f;\n"), "\nint f;\n");
+}
+
+#[test]
+fn test_recursive_macro() {
+    assert_eq!(preprocess("#define foo foo + 1\nfoo\n"), "\nfoo + 1\n");
+}
+
+#[test]
+fn test_stringify_and_paste() {
+    assert_eq!(
+        preprocess("#define str(x) #x\n#define cat(a, b) a ## b\nstr(\"hi\" 1) cat(x, 1)\n"),
+        "\n\n\"\\\"hi\\\" 1\" x1\n"
+    );
+}
+
+#[test]
+fn test_variadic_macro() {
+    assert_eq!(
+        preprocess("#define call(f, ...) f(__VA_ARGS__)\ncall(g, 1, 2)\n"),
+        "\ng(1, 2)\n"
+    );
+}
+
+#[test]
+fn test_multiline_invocation_keeps_lines() {
+    assert_eq!(preprocess("#define id(x) x\nid(1,\n)\n"), "\nid(1,\n)\n");
+    assert_eq!(preprocess("#define id(x) x\nid(1\n)\n2\n"), "\n1\n\n2\n");
+}
+
+#[test]
+fn test_undef_and_comments() {
+    assert_eq!(
+        preprocess("#define A 1 /* one */\n#undef A\nA // still A\n"),
+        "\n\nA \n"
+    );
+}
+
+#[test]
+fn test_expansions_do_not_merge_tokens() {
+    assert_eq!(preprocess("#define neg -1\n-neg\n"), "\n- -1\n");
+}
+
+#[test]
+fn test_trace_argument_pre_expansion() {
+    let steps = trace("#define ONE 1\n#define twice(x) x + x\ntwice(ONE)\n");
+    assert_eq!(
+        steps
+            .iter()
+            .map(|step| step.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["ONE", "twice"]
+    );
+    let twice = &steps[1];
+    assert_eq!(twice.arguments, Some(vec!["ONE".to_string()]));
+    assert_eq!(twice.expanded_arguments, Some(vec!["1".to_string()]));
+    assert_eq!(twice.replacement, "1 + 1");
+    assert_eq!((twice.position.line, twice.position.column), (3, 1));
+}
+
+#[test]
+fn test_trace_rescan() {
+    let steps = trace("#define A B\n#define B 2\nx = A;\n");
+    assert_eq!(steps.len(), 2);
+    assert_eq!(
+        (steps[0].name.as_str(), steps[0].replacement.as_str()),
+        ("A", "B")
+    );
+    assert_eq!(
+        (steps[1].name.as_str(), steps[1].replacement.as_str()),
+        ("B", "2")
+    );
+    assert_eq!(steps[1].position, steps[0].position);
+    assert_eq!(steps[0].position.column, 5);
+}
+
+#[test]
+fn test_expand_range() {
+    let text = "#define SQUARE(x) ((x) * (x))\nint y = SQUARE(3) + 1;\n";
+    let call = text.find("SQUARE(3)").unwrap();
+    assert_eq!(expand_range(text, call..call), "((3) * (3))");
+    assert_eq!(expand_range(text, call + 3..call + 4), "((3) * (3))");
+    assert_eq!(
+        expand_range(text, call - 2..text.len()),
+        "= ((3) * (3)) + 1;"
+    );
+}
+
+#[test]
+fn test_keep_conditionals() {
+    let text = "#define DEBUG 1\n#if DEBUG\nint x = DEBUG;\n#endif\n#line 3\n";
+    let kept: String = Preprocessor::new(text.chars())
+        .keep_conditionals()
+        .collect();
+    assert_eq!(kept, "\n#if DEBUG\nint x = 1;\n#endif\n\n");
+    assert_eq!(preprocess(text), "\n\nint x = 1;\n\n\n");
+}
+
+#[test]
+fn test_conditional_segments() {
+    let text = "a\n#if A > 1\nb\n#elif defined B\nc\n#else\nd\n#endif\n#ifndef C\ne\n#endif\n";
+    let line = |content: &str| {
+        let start = text.find(&format!("\n{}\n", content)).unwrap() + 1;
+        vec![Segment::Text(start..start + 2)]
+    };
+    let a = PresenceCondition::Expression("A > 1".to_string());
+    let b = PresenceCondition::Defined("B".to_string());
+    let c = PresenceCondition::Defined("C".to_string());
+    let branch = |condition: PresenceCondition, segments| Branch {
+        condition,
+        segments,
+    };
+    assert_eq!(
+        conditional_segments(text),
+        vec![
+            Segment::Text(0..2),
+            Segment::Conditional(vec![
+                branch(a.clone(), line("b")),
+                branch(a.not().and(&b), line("c")),
+                branch(a.not().and(&b.not()), line("d")),
+            ]),
+            Segment::Conditional(vec![
+                branch(c.not(), line("e")),
+                branch(c.clone(), Vec::new()),
+            ]),
+        ]
+    );
+    assert_eq!(a.not().and(&b.not()).to_string(), "!(A > 1) && !defined(B)");
+
+    let chosen = configurations(&conditional_segments(text), 64);
+    assert_eq!(chosen.len(), 6);
+    let (condition, ranges) = &chosen[0];
+    assert_eq!(*condition, a.and(&c.not()));
+    assert_eq!(ranges.len(), 3);
+    assert_eq!(configurations(&conditional_segments(text), 4).len(), 4);
+}
+
+#[test]
+fn test_nested_and_unterminated_conditionals() {
+    let text = "#ifdef A\n#ifdef B\nx\n#endif\n#else\n#endif\n#endif\n#ifdef C\ny\n";
+    let segments = conditional_segments(text);
+    assert_eq!(segments.len(), 2);
+    let chosen = configurations(&segments, 64);
+    let conditions: Vec<String> = chosen.iter().map(|(c, _)| c.to_string()).collect();
+    assert_eq!(
+        conditions,
+        [
+            "defined(A) && defined(B) && defined(C)",
+            "defined(A) && defined(B) && !defined(C)",
+            "defined(A) && !defined(B) && defined(C)",
+            "defined(A) && !defined(B) && !defined(C)",
+            "!defined(A) && defined(C)",
+            "!defined(A) && !defined(C)",
+        ]
+    );
+}
+
+#[test]
+fn test_conditional_directives() {
+    let text = "#define A 2\n#ifdef A\na\n#else\nnot_a\n#endif\n#ifndef B\nb\n#endif\n";
+    assert_eq!(preprocess(text), "\n\na\n\n\n\n\nb\n\n");
+    let text =
+        "#if defined(B) || A > 3\nx\n#elif (A << 1) == 4 && !defined B\ny\n#elif 1\nz\n#endif\n";
+    assert_eq!(
+        preprocess(&format!("#define A 2\n{}", text)),
+        "\n\n\n\ny\n\n\n\n"
+    );
+    let nested = "#if 0\n#if 1 / 0\nx\n#else\ny\n#endif\n#elif 0 && 1 / 0\nz\n#else\nw\n#endif\n";
+    assert_eq!(preprocess(nested), "\n\n\n\n\n\n\n\n\nw\n\n");
+}
+
+#[test]
+fn test_directive_errors() {
+    let errors = |text: &str| {
+        let mut preprocessor = Preprocessor::new(text.chars());
+        preprocessor.by_ref().for_each(drop);
+        preprocessor
+            .errors()
+            .iter()
+            .map(|error| (error.position.line, error.error_type.clone()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        errors("#include <stdio.h>\n#if 0\n#error skipped\n#endif\n#error too old\n"),
+        vec![
+            (1, UnsupportedDirective("include".to_string())),
+            (5, Error("too old".to_string())),
+        ]
+    );
+    assert_eq!(
+        errors("#pragma once\n# 1 \"file.c\"\n#\n#endif\n#if 1\n#else\n#else\n#endif\n"),
+        vec![
+            (4, UnmatchedDirective("endif".to_string())),
+            (7, AfterElse("else".to_string()))
+        ]
+    );
+    assert_eq!(
+        errors("#foo\n#if 1 +\n#elif 1 / 0\n#endif\n#ifdef\n#endif\n#if 1\n"),
+        vec![
+            (1, InvalidDirective("foo".to_string())),
+            (2, InvalidExpression),
+            (3, DivisionByZero),
+            (5, MissingMacroName("ifdef".to_string())),
+            (7, UnterminatedConditional),
+        ]
+    );
+}
@@ -0,0 +1,174 @@
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+
+/// A position in the original, unpreprocessed source.
+///
+/// `character` is a character offset like `lex::errors::Location`, `line` and
+/// `column` start at 1.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SourcePosition {
+    pub character: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for SourcePosition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(super) struct SourceChar {
+    pub(super) ch: char,
+    pub(super) position: SourcePosition,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(super) enum PpTokenKind {
+    Identifier,
+    Number,
+    Literal,
+    Punctuator,
+    Whitespace,
+    Newline,
+    /// Stands in for an empty macro argument next to a `##`
+    Placemarker,
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct PpToken {
+    pub(super) kind: PpTokenKind,
+    pub(super) text: String,
+    /// The source characters this token was produced from. Tokens produced by
+    /// a macro expansion cover the whole outermost invocation.
+    pub(super) origin: Range<usize>,
+    pub(super) position: SourcePosition,
+    /// Macros which may not be expanded again from this token
+    pub(super) hide_set: HashSet<String>,
+}
+
+impl PpToken {
+    pub(super) fn new(kind: PpTokenKind, text: String, origin: SourcePosition) -> PpToken {
+        PpToken {
+            kind,
+            origin: origin.character..origin.character + text.chars().count(),
+            text,
+            position: origin,
+            hide_set: HashSet::new(),
+        }
+    }
+
+    pub(super) fn is(&self, punctuator: &str) -> bool {
+        self.kind == PpTokenKind::Punctuator && self.text == punctuator
+    }
+
+    pub(super) fn is_whitespace(&self) -> bool {
+        self.kind == PpTokenKind::Whitespace || self.kind == PpTokenKind::Newline
+    }
+}
+
+// Longest punctuators first so that the first match is the longest one
+const PUNCTUATORS: &[&str] = &[
+    "%:%:", "...", "<<=", ">>=", "->", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||",
+    "*=", "/=", "%=", "+=", "-=", "&=", "^=", "|=", "##", "<:", ":>", "<%", "%>", "%:",
+];
+
+fn is_identifier_start(ch: char) -> bool {
+    ch == '_' || ch.is_alphabetic()
+}
+
+fn is_identifier_continue(ch: char) -> bool {
+    ch == '_' || ch.is_alphanumeric()
+}
+
+/// Splits one logical line of source into preprocessing tokens.
+pub(super) fn tokenize(line: &[SourceChar]) -> Vec<PpToken> {
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    while index < line.len() {
+        let start = index;
+        let ch = line[index].ch;
+        let next = line.get(index + 1).map(|c| c.ch);
+        index += 1;
+
+        let kind = if ch.is_whitespace() {
+            while index < line.len() && line[index].ch.is_whitespace() {
+                index += 1;
+            }
+            PpTokenKind::Whitespace
+        } else if is_identifier_start(ch) {
+            while index < line.len() && is_identifier_continue(line[index].ch) {
+                index += 1;
+            }
+            PpTokenKind::Identifier
+        } else if ch.is_ascii_digit() || (ch == '.' && next.is_some_and(|c| c.is_ascii_digit())) {
+            while index < line.len() {
+                let c = line[index].ch;
+                if "eEpP".contains(c) && line.get(index + 1).is_some_and(|c| "+-".contains(c.ch)) {
+                    index += 2;
+                } else if c == '.' || is_identifier_continue(c) {
+                    index += 1;
+                } else {
+                    break;
+                }
+            }
+            PpTokenKind::Number
+        } else if ch == '"' || ch == '\'' {
+            while index < line.len() && line[index].ch != ch {
+                if line[index].ch == '\\' {
+                    index += 1;
+                }
+                index += 1;
+            }
+            index = (index + 1).min(line.len());
+            PpTokenKind::Literal
+        } else {
+            let rest: String = line[start..line.len().min(start + 4)]
+                .iter()
+                .map(|c| c.ch)
+                .collect();
+            if let Some(punctuator) = PUNCTUATORS.iter().find(|p| rest.starts_with(*p)) {
+                index = start + punctuator.len();
+            }
+            PpTokenKind::Punctuator
+        };
+
+        let text = line[start..index].iter().map(|c| c.ch).collect();
+        tokens.push(PpToken::new(kind, text, line[start].position));
+    }
+
+    tokens
+}
+
+/// Tokenizes text which has no position of its own, such as a macro body.
+pub(super) fn tokenize_str(text: &str, position: SourcePosition) -> Vec<PpToken> {
+    let chars: Vec<SourceChar> = text.chars().map(|ch| SourceChar { ch, position }).collect();
+    tokenize(&chars)
+}
+
+/// Spells out a token list, collapsing whitespace as `#` stringification does.
+pub(super) fn spell(tokens: &[PpToken]) -> String {
+    let mut text = String::new();
+    for token in tokens {
+        match token.kind {
+            PpTokenKind::Whitespace | PpTokenKind::Newline => {
+                if !text.is_empty() && !text.ends_with(' ') {
+                    text.push(' ');
+                }
+            }
+            PpTokenKind::Placemarker => {}
+            _ => text.push_str(&token.text),
+        }
+    }
+    text.trim_end().to_string()
+}
+
+/// Whether two tokens would lex differently if written without a space between them.
+pub(super) fn needs_separator(left: char, right: char) -> bool {
+    let punctuation = |c: char| c.is_ascii_punctuation() && !"()[]{},;\"'".contains(c);
+    (is_identifier_continue(left) && is_identifier_continue(right))
+        || (punctuation(left) && punctuation(right))
+}
@@ -48,6 +48,7 @@ pub(crate) const LITERAL_TOKENS: &[(&str, LexItem)] = &[
     (":", LexItem::Colon),
     (",", LexItem::Comma),
     (".", LexItem::Period),
+    ("...", LexItem::Ellipsis),
     ("?", LexItem::Question),
     ("auto\x00", LexItem::Auto),
     ("break\x00", LexItem::Break),
@@ -65,11 +66,9 @@ pub(crate) const LITERAL_TOKENS: &[(&str, LexItem)] = &[
     ("for\x00", LexItem::For),
     ("goto\x00", LexItem::Goto),
     ("if\x00", LexItem::If),
-    ("inline\x00", LexItem::Inline),
     ("int\x00", LexItem::Int),
     ("long\x00", LexItem::Long),
     ("register\x00", LexItem::Register),
-    ("restrict\x00", LexItem::Restrict),
     ("return\x00", LexItem::Return),
     ("short\x00", LexItem::Short),
     ("signed\x00", LexItem::Signed),
@@ -86,9 +85,58 @@ pub(crate) const LITERAL_TOKENS: &[(&str, LexItem)] = &[
     ("_Bool\x00", LexItem::Bool),
     ("_Complex\x00", LexItem::Complex),
     ("_Imaginary\x00", LexItem::Imaginary),
+    ("_Noreturn\x00", LexItem::Noreturn),
+    ("_Thread_local\x00", LexItem::ThreadLocal),
+    ("_Atomic\x00", LexItem::Atomic),
+    ("_Static_assert\x00", LexItem::StaticAssert),
+    ("_Generic\x00", LexItem::Generic),
+    ("_Alignas\x00", LexItem::Alignas),
+    ("_Alignof\x00", LexItem::Alignof),
+];
+
+/// Keywords that C99 added, which are identifiers in C89
+pub(crate) const C99_TOKENS: &[(&str, LexItem)] = &[
+    ("inline\x00", LexItem::Inline),
+    ("restrict\x00", LexItem::Restrict),
+];
+
+/// The spellings of the C99 keywords which C89 code can use, as they are
+/// reserved there
+pub(crate) const C89_TOKENS: &[(&str, LexItem)] = &[
+    ("__inline\x00", LexItem::Inline),
+    ("__restrict\x00", LexItem::Restrict),
+];
+
+/// Keywords of the GNU dialects, including the alternate spellings of standard
+/// keywords which glibc headers use. The first spelling of each is its name.
+pub(crate) const GNU_TOKENS: &[(&str, LexItem)] = &[
+    ("__attribute__\x00", LexItem::Attribute),
+    ("__attribute\x00", LexItem::Attribute),
+    ("__asm__\x00", LexItem::Asm),
+    ("__asm\x00", LexItem::Asm),
+    ("asm\x00", LexItem::Asm),
+    ("__extension__\x00", LexItem::Extension),
+    ("__typeof__\x00", LexItem::Typeof),
+    ("__typeof\x00", LexItem::Typeof),
+    ("typeof\x00", LexItem::Typeof),
+    ("__builtin_va_list\x00", LexItem::BuiltinVaList),
+    ("__int128\x00", LexItem::Int128),
+    ("__restrict\x00", LexItem::Restrict),
+    ("__restrict__\x00", LexItem::Restrict),
+    ("__inline\x00", LexItem::Inline),
+    ("__inline__\x00", LexItem::Inline),
+    ("__const\x00", LexItem::Const),
+    ("__const__\x00", LexItem::Const),
+    ("__volatile\x00", LexItem::Volatile),
+    ("__volatile__\x00", LexItem::Volatile),
+    ("__signed\x00", LexItem::Signed),
+    ("__signed__\x00", LexItem::Signed),
+    ("__alignof\x00", LexItem::Alignof),
+    ("__alignof__\x00", LexItem::Alignof),
+    ("__thread\x00", LexItem::ThreadLocal),
 ];
 
-pub(super) const INVALID_IDENTIFIER_CHARS: &str = " !\"#%&'()*+,-./;;<=>?@[\\]^`{|}~";
+pub(super) const INVALID_IDENTIFIER_CHARS: &str = " \n\t\r!\"#%&'()*+,-./:;<=>?@[\\]^`{|}~";
 
 pub(super) fn is_identifier_char(ch: char) -> bool {
     !INVALID_IDENTIFIER_CHARS.chars().any(|c| c == ch)
@@ -8,7 +8,30 @@ pub struct Location {
     pub character: usize,
 }
 
-#[derive(Clone, Debug)]
+impl Location {
+    /// The 1-based line and column of this location in `source`
+    pub fn line_column(self, source: &str) -> (usize, usize) {
+        source
+            .chars()
+            .take(self.character)
+            .fold((1, 1), |(line, column), ch| {
+                if ch == '\n' {
+                    (line + 1, 1)
+                } else {
+                    (line, column + 1)
+                }
+            })
+    }
+}
+
+/// The characters from `start` up to, but not including, `end`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct LexError {
     pub error_type: LexErrorType,
     pub location: Location,
@@ -57,4 +80,14 @@ pub type LexResult = Result<LexSuccess, LexError>;
 pub struct LexSuccess {
     pub item: LexItem,
     pub location: Location,
+    pub end: Location,
+}
+
+impl LexSuccess {
+    pub fn span(&self) -> Span {
+        Span {
+            start: self.location,
+            end: self.end,
+        }
+    }
 }
@@ -4,6 +4,7 @@ use crate::lex::errors::LexSuccess;
 use crate::lex::errors::Location;
 use crate::lex::types::LexItem;
 use crate::lex::Lexer;
+use std::fmt::{self, Display, Formatter};
 use std::iter::FromIterator;
 use std::str::Chars;
 
@@ -16,9 +17,9 @@ pub(super) struct SourceChar {
 #[derive(Debug)]
 pub(super) struct SourceString(Vec<(String, Location)>);
 
-impl ToString for SourceString {
-    fn to_string(&self) -> String {
-        self.0.iter().map(|(s, _)| s.as_str()).collect()
+impl Display for SourceString {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|(s, _)| f.write_str(s))
     }
 }
 
@@ -61,9 +62,7 @@ impl<'a> ExactChars<'a> {
 }
 
 impl SourceString {
-    pub(super) fn char_iter<'a>(
-        &'a self,
-    ) -> impl Iterator<Item = SourceChar> + DoubleEndedIterator + 'a {
+    pub(super) fn char_iter<'a>(&'a self) -> impl DoubleEndedIterator<Item = SourceChar> + 'a {
         self.0.iter().flat_map(|(s, l)| {
             ExactChars::new(s.chars())
                 .enumerate()
@@ -147,7 +146,7 @@ impl<It: Iterator<Item = char>> Lexer<It> {
 
         if next
             .as_ref()
-            .map_or(false, |s| s.to_string().as_str() == "//")
+            .is_some_and(|s| s.to_string().as_str() == "//")
         {
             while self.next_char()?.ch != '\n' {}
             self.next_after_whitespace()
@@ -166,10 +165,18 @@ impl<It: Iterator<Item = char>> Lexer<It> {
         s.char_iter().rev().for_each(|ch| self.nextnt(ch));
     }
 
+    /// The location of the next character that will be read
+    fn position(&self) -> Location {
+        self.lookahead
+            .last()
+            .map_or(self.character, |ch| ch.location)
+    }
+
     pub(super) fn ok_token(&self, token: LexItem) -> LexSuccess {
         LexSuccess {
             item: token,
             location: self.start_char,
+            end: self.position(),
         }
     }
 
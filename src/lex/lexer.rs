@@ -1,10 +1,11 @@
-use super::constants::LITERAL_TOKENS;
+use super::constants::{C89_TOKENS, C99_TOKENS, GNU_TOKENS, LITERAL_TOKENS};
 use super::types::LexItem;
 use crate::lex::constants::is_identifier_char;
 use crate::lex::errors::LexResult;
 use crate::lex::errors::Location;
 use crate::lex::iterator_util::SourceString;
 use crate::lex::Lexer;
+use crate::standard::Standard;
 use std::char;
 use std::iter::Iterator;
 
@@ -12,22 +13,43 @@ impl<It> Lexer<It>
 where
     It: Iterator<Item = char>,
 {
+    #[allow(dead_code)]
     pub fn new(src: It) -> Lexer<It> {
+        Lexer::with_standard(src, Standard::default())
+    }
+
+    /// A lexer which also recognises the keywords of `standard`
+    pub fn with_standard(src: It, standard: Standard) -> Lexer<It> {
         Lexer {
             source: src.enumerate(),
             lookahead: Vec::new(),
             character: Location { character: 0 },
             start_char: Location { character: 0 },
+            standard,
         }
     }
 
+    /// Every token spelled out in full, which keywords depend on the standard
+    fn literal_tokens(&self) -> impl Iterator<Item = &'static (&'static str, LexItem)> {
+        let keywords = match self.standard {
+            Standard::C89 => C89_TOKENS,
+            Standard::C11 | Standard::Gnu11 => C99_TOKENS,
+        };
+        let gnu: &'static [(&str, LexItem)] = if self.standard.is_gnu() {
+            GNU_TOKENS
+        } else {
+            &[]
+        };
+        LITERAL_TOKENS.iter().chain(keywords).chain(gnu)
+    }
+
     fn next_regular_token(&mut self) -> Option<LexResult> {
         let mut token = SourceString::new();
         token.push(self.next_after_whitespace()?);
 
         loop {
-            let partial_matches: Vec<&(&str, LexItem)> = LITERAL_TOKENS
-                .iter()
+            let partial_matches: Vec<&(&str, LexItem)> = self
+                .literal_tokens()
                 .filter(|(key, _)| key.trim_end_matches('\x00').starts_with(&token.to_string()))
                 .collect();
 
@@ -45,8 +67,8 @@ where
                 }
             }
 
-            let largest_match = LITERAL_TOKENS
-                .iter()
+            let largest_match = self
+                .literal_tokens()
                 .filter(|(key, _)| {
                     token.to_string().starts_with(key.trim_end_matches('\x00'))
                         && if key.ends_with('\x00') {
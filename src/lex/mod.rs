@@ -1,5 +1,6 @@
 use crate::lex::errors::Location;
 use crate::lex::iterator_util::SourceChar;
+use crate::standard::Standard;
 use std::iter::Enumerate;
 
 pub(crate) mod constants;
@@ -13,6 +14,8 @@ pub struct Lexer<It: Iterator<Item = char>> {
     lookahead: Vec<SourceChar>,
     character: Location,
     start_char: Location,
+    /// Which keywords are recognised
+    standard: Standard,
 }
 
 // Implementations of lexer
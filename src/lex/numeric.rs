@@ -34,7 +34,7 @@ impl<It: Iterator<Item = char>> Lexer<It> {
                 }
                 'U' | 'L' | 'u' | 'l' => {
                     self.nextnt(ch);
-                    self.parse_type_specifier(0)
+                    self.parse_type_specifier(0, false)
                 }
                 _ => {
                     self.nextnt(ch);
@@ -49,7 +49,7 @@ impl<It: Iterator<Item = char>> Lexer<It> {
     fn parse_num_radix(&mut self, num: String, radix: u8) -> LexResult {
         if !num.is_empty() {
             if let Ok(n) = u128::from_str_radix(&num, u32::from(radix)) {
-                self.parse_type_specifier(n)
+                self.parse_type_specifier(n, radix == 10)
             } else {
                 Err(self.error_token(LexErrorType::LargeNumericLiteral))
             }
@@ -58,7 +58,10 @@ impl<It: Iterator<Item = char>> Lexer<It> {
         }
     }
 
-    fn parse_type_specifier(&mut self, num: u128) -> LexResult {
+    /// Reads the suffix of a literal with the value `num`. A value too large
+    /// for the type the suffix asks for has the first larger one it fits in,
+    /// which is always signed for a decimal literal without `u`.
+    fn parse_type_specifier(&mut self, num: u128, decimal: bool) -> LexResult {
         let mut signed = true;
         let mut size = 32usize;
         while let Some(ch) = self.next_char() {
@@ -76,6 +79,28 @@ impl<It: Iterator<Item = char>> Lexer<It> {
                 }
             }
         }
+        if size > 128 {
+            return Err(self.error_token(LexErrorType::InvalidSize(size)));
+        }
+        let mut candidates = Vec::new();
+        let mut width = size;
+        while width <= 128 {
+            if signed {
+                candidates.push((width, true));
+            }
+            if !signed || !decimal {
+                candidates.push((width, false));
+            }
+            width <<= 1;
+        }
+        let fits = |&(width, signed): &(usize, bool)| {
+            let bits = width - usize::from(signed);
+            bits >= 128 || num >> bits == 0
+        };
+        let (size, signed) = match candidates.into_iter().find(fits) {
+            Some(found) => found,
+            None => return Err(self.error_token(LexErrorType::LargeNumericLiteral)),
+        };
         let nt = match (size, signed) {
             (8, false) => NumberType::UnsignedChar(num as u8),
             (8, true) => NumberType::SignedChar(num as i8),
@@ -85,7 +110,7 @@ impl<It: Iterator<Item = char>> Lexer<It> {
             (32, true) => NumberType::SignedInt(num as i32),
             (64, false) => NumberType::UnsignedLong(num as u64),
             (64, true) => NumberType::SignedLong(num as i64),
-            (128, false) => NumberType::UnsignedLongLong(num as u128),
+            (128, false) => NumberType::UnsignedLongLong(num),
             (128, true) => NumberType::SignedLongLong(num as i128),
             _ => return Err(self.error_token(LexErrorType::InvalidSize(size))),
         };
@@ -1,6 +1,7 @@
 use super::types::{LexItem, NumberType};
 use super::Lexer;
 use crate::lex::errors::LexErrorType;
+use crate::standard::Standard;
 
 #[cfg(test)]
 fn test_lexer_str(s: &str, tokens: &[LexItem]) {
@@ -149,6 +150,20 @@ fn test_lexer_nonint_literal() {
         ],
     )
 }
+
+#[test]
+fn test_lexer_large_literal() {
+    // A literal has the first type from its suffix on that its value fits in
+    test_lexer_str(
+        "2147483648 0x80000000 0x100000000 4294967295u",
+        &[
+            LexItem::NumericLiteral(NumberType::SignedLong(2_147_483_648)),
+            LexItem::NumericLiteral(NumberType::UnsignedInt(0x8000_0000)),
+            LexItem::NumericLiteral(NumberType::SignedLong(0x1_0000_0000)),
+            LexItem::NumericLiteral(NumberType::UnsignedInt(4_294_967_295)),
+        ],
+    )
+}
 #[test]
 fn test_lexer_identifier() {
     test_lexer_str(
@@ -264,6 +279,21 @@ fn test_lexer_location() {
     );
 }
 
+#[test]
+fn test_lexer_span() {
+    let s = "while (x1 >>= 0x1f) \"ab\"";
+    let spans: Vec<&str> = Lexer::new(s.chars())
+        .map(|res| {
+            let span = res.unwrap().span();
+            &s[span.start.character..span.end.character]
+        })
+        .collect();
+    assert_eq!(
+        spans,
+        vec!["while", "(", "x1", ">>=", "0x1f", ")", "\"ab\""]
+    );
+}
+
 #[test]
 fn test_lexer_comment() {
     test_lexer_str("//hi!!", &[]);
@@ -360,3 +390,82 @@ fn test_invalid_hex_byte_escape() {
 fn test_multiline_nextnt() {
     test_no_panic("\"\\Ut\n\n");
 }
+
+#[test]
+fn test_identifier_ends_at_whitespace_and_colon() {
+    test_lexer_str(
+        "else\n\tn\r\nfail:",
+        &[
+            LexItem::Else,
+            LexItem::Identifier("n".to_string()),
+            LexItem::Identifier("fail".to_string()),
+            LexItem::Colon,
+        ],
+    );
+}
+
+#[test]
+fn test_lexer_ellipsis() {
+    test_lexer_str(
+        "[1 ... 5].x",
+        &[
+            LexItem::LeftBracket,
+            LexItem::NumericLiteral(NumberType::SignedInt(1)),
+            LexItem::Ellipsis,
+            LexItem::NumericLiteral(NumberType::SignedInt(5)),
+            LexItem::RightBracket,
+            LexItem::Period,
+            LexItem::Identifier("x".to_string()),
+        ],
+    );
+}
+
+#[test]
+fn test_lexer_gnu_keywords() {
+    let text = "__attribute__ __asm__ asm __extension__ __typeof__ __restrict __inline__ \
+                __builtin_va_list __int128 __restrict_arg";
+    let lexer = Lexer::with_standard(text.chars(), Standard::Gnu11);
+    let tokens: Vec<LexItem> = lexer.map(|res| res.unwrap().item).collect();
+    assert_eq!(
+        tokens,
+        vec![
+            LexItem::Attribute,
+            LexItem::Asm,
+            LexItem::Asm,
+            LexItem::Extension,
+            LexItem::Typeof,
+            LexItem::Restrict,
+            LexItem::Inline,
+            LexItem::BuiltinVaList,
+            LexItem::Int128,
+            LexItem::Identifier("__restrict_arg".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_lexer_c99_keywords_are_identifiers_in_c89() {
+    let text = "inline restrict __inline __restrict";
+    let lexer = Lexer::with_standard(text.chars(), Standard::C89);
+    let tokens: Vec<LexItem> = lexer.map(|res| res.unwrap().item).collect();
+    assert_eq!(
+        tokens,
+        vec![
+            LexItem::Identifier("inline".to_string()),
+            LexItem::Identifier("restrict".to_string()),
+            LexItem::Inline,
+            LexItem::Restrict,
+        ]
+    );
+}
+
+#[test]
+fn test_lexer_gnu_keywords_are_identifiers_in_c11() {
+    test_lexer_str(
+        "asm __int128",
+        &[
+            LexItem::Identifier("asm".to_string()),
+            LexItem::Identifier("__int128".to_string()),
+        ],
+    );
+}
@@ -29,6 +29,9 @@ pub enum LexItem {
     NumericLiteral(NumberType),
 
     Identifier(String),
+    /// An identifier declared by a typedef in scope. Never produced by the lexer,
+    /// the parser reclassifies identifiers using its typedef table.
+    TypedefName(String),
 
     // Operations
     Plus,
@@ -82,6 +85,7 @@ pub enum LexItem {
     Colon,
     Comma,
     Period,
+    Ellipsis,
     Question,
 
     // Keywords
@@ -122,4 +126,19 @@ pub enum LexItem {
     Bool,
     Complex,
     Imaginary,
+    Noreturn,
+    ThreadLocal,
+    Atomic,
+    StaticAssert,
+    Generic,
+    Alignas,
+    Alignof,
+
+    // GNU keywords
+    Attribute,
+    Asm,
+    Extension,
+    Typeof,
+    BuiltinVaList,
+    Int128,
 }
@@ -1,19 +1,29 @@
-#![recursion_limit = "1000"]
+#![allow(mixed_script_confusables)]
 mod cpp;
 mod lex;
 mod parse;
+mod standard;
 mod untyped_ast;
 
+use crate::cpp::conditional::PresenceCondition;
+use crate::cpp::preprocessor::Preprocessor;
 use crate::lex::Lexer;
-use crate::parse::parser::parse;
+use crate::parse::dump::{dump, TreeFormat};
+use crate::parse::errors::Found;
+use crate::parse::parser::parse_recovering_with;
+use crate::parse::variability::parse_variability;
+use crate::standard::Standard;
 #[cfg(not(fuzzing))]
 use std::env;
 #[cfg(not(fuzzing))]
 use std::fs::File;
 #[cfg(not(fuzzing))]
 use std::io::Read;
+#[cfg(not(fuzzing))]
+use std::process;
 
-use crate::untyped_ast::build_untyped_ast;
+use crate::untyped_ast::build_untyped_ast_with;
+use crate::untyped_ast::errors::Severity;
 #[cfg(fuzzing)]
 //use afl::fuzz;
 use honggfuzz::fuzz;
@@ -21,21 +31,118 @@ use honggfuzz::fuzz;
 /// A super simple main function which lexes
 #[cfg(not(fuzzing))]
 fn main() -> std::io::Result<()> {
-    if env::args().len() != 2 {
-        eprintln!("Usage: μcc <filename>");
-        return Ok(());
+    let mut trace_macros = false;
+    let mut grammar_debug = false;
+    let mut all_branches = false;
+    let mut tree_format: Option<TreeFormat> = None;
+    let mut standard = Standard::default();
+    let mut filename = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--trace-macros" => trace_macros = true,
+            "--grammar-debug" => grammar_debug = true,
+            "--all-branches" => all_branches = true,
+            _ if arg.starts_with("--dump-tree=") => match arg["--dump-tree=".len()..].parse() {
+                Ok(parsed) => tree_format = Some(parsed),
+                Err(error) => {
+                    eprintln!("error: {}", error);
+                    process::exit(1);
+                }
+            },
+            _ if arg.starts_with("-std=") => match arg["-std=".len()..].parse() {
+                Ok(parsed) => standard = parsed,
+                Err(error) => {
+                    eprintln!("error: {}", error);
+                    process::exit(1);
+                }
+            },
+            _ if filename.is_none() && !arg.starts_with('-') => filename = Some(arg),
+            _ => {
+                eprintln!("error: unrecognized argument '{}'", arg);
+                process::exit(1);
+            }
+        }
     }
-    let filename = env::args().nth(1).unwrap();
-    let mut file = File::open(filename).unwrap();
+    let filename = match filename {
+        Some(filename) => filename,
+        None => {
+            eprintln!(
+                "Usage: μcc [--trace-macros] [--grammar-debug] [--all-branches] \
+                 [--dump-tree=dot|sexp|indented] [-std=c11|gnu11|c89] <filename>"
+            );
+            process::exit(1);
+        }
+    };
+    let mut file = File::open(&filename).unwrap();
     let mut code = String::new();
     file.read_to_string(&mut code)?;
 
-    let chars = code.chars();
-    let tokens = Lexer::new(chars);
+    let mut preprocessor = Preprocessor::new(code.chars());
+    if all_branches {
+        preprocessor = preprocessor.keep_conditionals();
+    }
+    if trace_macros {
+        preprocessor = preprocessor.trace_macros(|expansion| eprintln!("{}", expansion));
+    }
+    let preprocessed: String = preprocessor.by_ref().collect();
+    for error in preprocessor.errors() {
+        eprintln!("{}:{}: error: {}", filename, error.position, error);
+    }
+    if !preprocessor.errors().is_empty() {
+        process::exit(1);
+    }
 
-    let tree = parse(tokens).unwrap();
+    // Syntax errors in every branch of every conditional, and nothing more
+    if all_branches {
+        let tree = parse_variability(&preprocessed, standard);
+        for (condition, error) in &tree.errors {
+            let (line, column) = error.span.start.line_column(&preprocessed);
+            match condition {
+                PresenceCondition::Always => {
+                    eprintln!("{}:{}:{}: error: {}", filename, line, column, error)
+                }
+                _ => eprintln!(
+                    "{}:{}:{}: error: {} (when {})",
+                    filename, line, column, error, condition
+                ),
+            }
+        }
+        process::exit(if tree.errors.is_empty() { 0 } else { 1 });
+    }
 
-    let u_ast = build_untyped_ast(tree);
+    let chars = preprocessed.chars();
+    let tokens = Lexer::with_standard(chars, standard);
+
+    let (tree, errors) = parse_recovering_with(tokens, standard);
+    for error in &errors {
+        let (line, column) = error.span.start.line_column(&preprocessed);
+        eprintln!("{}:{}:{}: error: {}", filename, line, column, error);
+        // Every derivation of ambiguous input, for whoever is changing the grammar
+        if let (true, Found::Ambiguity(report)) = (grammar_debug, &error.found) {
+            eprintln!("{}", report);
+        }
+    }
+    // The tree is printed even with syntax errors, which it shows as nodes
+    if let Some(format) = tree_format {
+        print!("{}", dump(&tree, format, &preprocessed));
+        process::exit(if errors.is_empty() { 0 } else { 1 });
+    }
+    if !errors.is_empty() {
+        process::exit(1);
+    }
+
+    let (u_ast, errors) = build_untyped_ast_with(tree, standard);
+    for error in &errors {
+        let (line, column) = error.span.start.line_column(&preprocessed);
+        let severity = error.error_type.severity();
+        eprintln!("{}:{}:{}: {}: {}", filename, line, column, severity, error);
+    }
+    if errors
+        .iter()
+        .any(|error| error.error_type.severity() == Severity::Error)
+    {
+        process::exit(1);
+    }
     println!("Got tree {:?}", u_ast);
 
     Ok(())
@@ -0,0 +1,169 @@
+use crate::lex::errors::{Location, Span};
+use crate::parse::analysis::rule_text;
+use crate::parse::dump::to_s_expression;
+use crate::parse::forest::{Forest, ForestNode};
+use crate::parse::types::{NonTerminalType, ParseNode, RuleType};
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
+
+/// Input which the grammar derives in more than one way, shown by the smallest
+/// subtree where the derivations differ
+#[derive(Clone, Debug, PartialEq)]
+pub struct AmbiguityReport {
+    /// The nonterminal at the root of the subtree
+    pub symbol: NonTerminalType,
+    pub span: Span,
+    pub derivations: Vec<Derivation>,
+}
+
+/// One way of deriving an ambiguous subtree
+#[derive(Clone, Debug, PartialEq)]
+pub struct Derivation {
+    pub tree: Rc<ParseNode>,
+    /// The rules used for the parts of the subtree that the other derivations
+    /// do not share, outermost first, with how deep in the subtree each is
+    pub rules: Vec<(usize, String)>,
+}
+
+impl AmbiguityReport {
+    /// Reports the smallest node below `root` with more than one derivation,
+    /// if there is one
+    pub(super) fn new(
+        forest: &Forest,
+        rules: &[(NonTerminalType, &[RuleType])],
+        root: usize,
+    ) -> Option<AmbiguityReport> {
+        let node = smallest_ambiguous(forest, root)?;
+        let (symbol, alternatives) = match &forest.nodes[node] {
+            ForestNode::NonTerminal {
+                symbol,
+                alternatives,
+            } => (*symbol, alternatives),
+            ForestNode::Terminal(_) => unreachable!(),
+        };
+
+        // The nodes below that every derivation has in common
+        let walks: Vec<Vec<(usize, usize)>> = (0..alternatives.len())
+            .map(|alternative| walk(forest, node, alternative, &HashSet::new()))
+            .collect();
+        let mut shared: HashSet<usize> = walks[0].iter().map(|&(node, _)| node).collect();
+        for walked in &walks[1..] {
+            let walked: HashSet<usize> = walked.iter().map(|&(node, _)| node).collect();
+            shared.retain(|node| walked.contains(node));
+        }
+        shared.remove(&node);
+
+        let derivations = (0..alternatives.len())
+            .map(|alternative| Derivation {
+                tree: forest.derivation(node, alternative),
+                rules: walk(forest, node, alternative, &shared)
+                    .into_iter()
+                    .filter_map(|(below, depth)| {
+                        let rule = match &forest.nodes[below] {
+                            ForestNode::NonTerminal { alternatives, .. } if below == node => {
+                                alternatives[alternative].rule
+                            }
+                            ForestNode::NonTerminal { alternatives, .. } => alternatives[0].rule,
+                            ForestNode::Terminal(_) => None,
+                        };
+                        rule.map(|rule| (depth, rule_text(rules, rule)))
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Some(AmbiguityReport {
+            symbol,
+            span: span(forest, node),
+            derivations,
+        })
+    }
+}
+
+/// The ambiguous node below `root` covering the fewest tokens
+fn smallest_ambiguous(forest: &Forest, root: usize) -> Option<usize> {
+    let mut seen = vec![false; forest.nodes.len()];
+    let mut stack = vec![root];
+    let mut smallest: Option<(usize, usize)> = None;
+    while let Some(node) = stack.pop() {
+        if std::mem::replace(&mut seen[node], true) {
+            continue;
+        }
+        if let ForestNode::NonTerminal { alternatives, .. } = &forest.nodes[node] {
+            if let (true, Some((start, end))) = (alternatives.len() > 1, forest.positions(node)) {
+                match smallest {
+                    Some((length, _)) if length <= end - start => {}
+                    _ => smallest = Some((end - start, node)),
+                }
+            }
+            stack.extend(alternatives.iter().flat_map(|a| a.children.iter()));
+        }
+    }
+    smallest.map(|(_, node)| node)
+}
+
+/// The nonterminal nodes of a derivation of `root` in preorder with their
+/// depths, not going into the nodes in `skipped`
+fn walk(
+    forest: &Forest,
+    root: usize,
+    alternative: usize,
+    skipped: &HashSet<usize>,
+) -> Vec<(usize, usize)> {
+    let mut walked = Vec::new();
+    let mut stack = vec![(root, 0)];
+    while let Some((node, depth)) = stack.pop() {
+        let alternatives = match &forest.nodes[node] {
+            ForestNode::NonTerminal { alternatives, .. } => alternatives,
+            ForestNode::Terminal(_) => continue,
+        };
+        if skipped.contains(&node) {
+            continue;
+        }
+        walked.push((node, depth));
+        let children = match node == root {
+            true => &alternatives[alternative].children,
+            false => &alternatives[0].children,
+        };
+        stack.extend(children.iter().rev().map(|&child| (child, depth + 1)));
+    }
+    walked
+}
+
+/// Where the tokens of a node are, or where it would be if it has none
+fn span(forest: &Forest, node: usize) -> Span {
+    let (start, end) = forest.positions(node).unwrap_or((0, 0));
+    let location = |position: usize| match position {
+        0 => Location { character: 0 },
+        _ => forest.token(position - 1).end,
+    };
+    match start < end {
+        true => Span {
+            start: forest.token(start).location,
+            end: forest.token(end - 1).end,
+        },
+        false => Span {
+            start: location(start),
+            end: location(start),
+        },
+    }
+}
+
+impl Display for AmbiguityReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} is derived in {} ways:",
+            self.symbol,
+            self.derivations.len()
+        )?;
+        for (index, derivation) in self.derivations.iter().enumerate() {
+            write!(f, "\n{}. {}", index + 1, to_s_expression(&derivation.tree))?;
+            for (depth, rule) in &derivation.rules {
+                write!(f, "\n   {:width$}{}", "", rule, width = depth * 2)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,461 @@
+use crate::lex::types::LexItem;
+use crate::parse::errors::{token_name, Expected};
+use crate::parse::precedence::{Precedence, Resolution};
+use crate::parse::types::{NonTerminalType, RuleType};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{self, Display, Formatter};
+use std::mem::Discriminant;
+
+type Rules<'a> = [(NonTerminalType, &'a [RuleType])];
+
+/// Facts about a grammar which the parse tables are built from, and mistakes
+/// which make parts of it useless.
+#[derive(Debug)]
+pub(super) struct Analysis {
+    /// Nonterminals which derive the empty string
+    pub(super) nullable: HashSet<NonTerminalType>,
+    /// The terminals each nonterminal can start with
+    pub(super) first: HashMap<NonTerminalType, HashSet<Discriminant<LexItem>>>,
+    /// The terminals which can follow each nonterminal. Every nonterminal can
+    /// be followed by the end of the input so that each can be parsed on its own.
+    pub(super) follow: HashMap<NonTerminalType, HashSet<Expected>>,
+    /// Nonterminals which are used but have no rules
+    pub(super) undefined: Vec<NonTerminalType>,
+    /// Nonterminals with rules that cannot be reached from `Start`
+    pub(super) unreachable: Vec<NonTerminalType>,
+    /// Nonterminals with rules that never derive a string of terminals
+    pub(super) unproductive: Vec<NonTerminalType>,
+}
+
+/// Two actions a deterministic parser could not choose between
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum Conflict {
+    /// Two rules of the same nonterminal can start with the same lookahead
+    Ll {
+        rules: (usize, usize),
+        lookaheads: Vec<Expected>,
+    },
+    /// An LR(1) parser can reduce by more than one rule, or reduce and shift,
+    /// before the same lookahead
+    Lr {
+        lookahead: Expected,
+        reductions: Vec<usize>,
+        shift: bool,
+    },
+}
+
+fn sorted(set: impl IntoIterator<Item = NonTerminalType>) -> Vec<NonTerminalType> {
+    let mut sorted: Vec<NonTerminalType> = set.into_iter().collect();
+    sorted.sort_by_key(|non_terminal| format!("{:?}", non_terminal));
+    sorted.dedup();
+    sorted
+}
+
+impl Analysis {
+    pub(super) fn new(rules: &Rules) -> Analysis {
+        let defined: HashSet<NonTerminalType> = rules.iter().map(|(lhs, _)| *lhs).collect();
+        let used = rules
+            .iter()
+            .flat_map(|(_, rhs)| rhs.iter())
+            .filter_map(|symbol| match symbol {
+                RuleType::NonTerminal(symbol) => Some(*symbol),
+                RuleType::Terminal(_) => None,
+            });
+
+        let mut reachable: HashSet<NonTerminalType> = HashSet::new();
+        let mut queue = vec![NonTerminalType::Start];
+        while let Some(non_terminal) = queue.pop() {
+            if !reachable.insert(non_terminal) {
+                continue;
+            }
+            for (_, rhs) in rules.iter().filter(|(lhs, _)| *lhs == non_terminal) {
+                for symbol in rhs.iter() {
+                    if let RuleType::NonTerminal(symbol) = symbol {
+                        queue.push(*symbol);
+                    }
+                }
+            }
+        }
+
+        let mut productive: HashSet<NonTerminalType> = HashSet::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (lhs, rhs) in rules {
+                let derives = rhs.iter().all(|symbol| match symbol {
+                    RuleType::Terminal(_) => true,
+                    RuleType::NonTerminal(symbol) => productive.contains(symbol),
+                });
+                if derives && productive.insert(*lhs) {
+                    changed = true;
+                }
+            }
+        }
+
+        let mut analysis = Analysis {
+            nullable: HashSet::new(),
+            first: HashMap::new(),
+            follow: HashMap::new(),
+            undefined: sorted(used.filter(|symbol| !defined.contains(symbol))),
+            unreachable: sorted(defined.iter().cloned().filter(|n| !reachable.contains(n))),
+            unproductive: sorted(defined.iter().cloned().filter(|n| !productive.contains(n))),
+        };
+        analysis.compute_first(rules);
+        analysis.compute_follow(rules);
+        analysis
+    }
+
+    fn compute_first(&mut self, rules: &Rules) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (lhs, rhs) in rules {
+                if !self.nullable.contains(lhs) && self.is_nullable(rhs) {
+                    self.nullable.insert(*lhs);
+                    changed = true;
+                }
+                let first = self.first_of(rhs);
+                let entry = self.first.entry(*lhs).or_default();
+                let before = entry.len();
+                entry.extend(first);
+                changed |= entry.len() != before;
+            }
+        }
+    }
+
+    fn compute_follow(&mut self, rules: &Rules) {
+        for (lhs, _) in rules {
+            self.follow
+                .entry(*lhs)
+                .or_default()
+                .insert(Expected::EndOfFile);
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (lhs, rhs) in rules {
+                for (index, symbol) in rhs.iter().enumerate() {
+                    let symbol = match symbol {
+                        RuleType::NonTerminal(symbol) => *symbol,
+                        RuleType::Terminal(_) => continue,
+                    };
+                    let rest = &rhs[index + 1..];
+                    let mut follow: HashSet<Expected> = self
+                        .first_of(rest)
+                        .into_iter()
+                        .map(Expected::Token)
+                        .collect();
+                    if self.is_nullable(rest) {
+                        follow.extend(self.follow[lhs].iter().cloned());
+                    }
+                    let entry = self.follow.entry(symbol).or_default();
+                    let before = entry.len();
+                    entry.extend(follow);
+                    changed |= entry.len() != before;
+                }
+            }
+        }
+    }
+
+    pub(super) fn is_nullable(&self, symbols: &[RuleType]) -> bool {
+        symbols.iter().all(|symbol| match symbol {
+            RuleType::Terminal(_) => false,
+            RuleType::NonTerminal(symbol) => self.nullable.contains(symbol),
+        })
+    }
+
+    /// The terminals a sequence of symbols can start with
+    pub(super) fn first_of(&self, symbols: &[RuleType]) -> HashSet<Discriminant<LexItem>> {
+        let mut first = HashSet::new();
+        for symbol in symbols {
+            match symbol {
+                RuleType::Terminal(item) => {
+                    first.insert(*item);
+                    break;
+                }
+                RuleType::NonTerminal(symbol) => {
+                    if let Some(set) = self.first.get(symbol) {
+                        first.extend(set.iter().cloned());
+                    }
+                    if !self.nullable.contains(symbol) {
+                        break;
+                    }
+                }
+            }
+        }
+        first
+    }
+
+    /// The lookaheads which select `rule` in a top-down parser
+    fn predict(&self, rules: &Rules, rule: usize) -> HashSet<Expected> {
+        let (lhs, rhs) = rules[rule];
+        let mut predict: HashSet<Expected> = self
+            .first_of(rhs)
+            .into_iter()
+            .map(Expected::Token)
+            .collect();
+        if self.is_nullable(rhs) {
+            predict.extend(self.follow[&lhs].iter().cloned());
+        }
+        predict
+    }
+
+    /// Pairs of rules a top-down parser with one token of lookahead could not
+    /// choose between
+    pub(super) fn ll1_conflicts(&self, rules: &Rules) -> Vec<Conflict> {
+        let predict: Vec<HashSet<Expected>> = (0..rules.len())
+            .map(|rule| self.predict(rules, rule))
+            .collect();
+        let mut conflicts = Vec::new();
+        for first in 0..rules.len() {
+            for second in first + 1..rules.len() {
+                if rules[first].0 != rules[second].0 {
+                    continue;
+                }
+                let mut lookaheads: Vec<Expected> = predict[first]
+                    .intersection(&predict[second])
+                    .cloned()
+                    .collect();
+                if !lookaheads.is_empty() {
+                    lookaheads.sort_by_key(Expected::to_string);
+                    conflicts.push(Conflict::Ll {
+                        rules: (first, second),
+                        lookaheads,
+                    });
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// The conflicts of a canonical LR(1) parser for `Start` which
+    /// `precedence` does not settle
+    pub(super) fn lr1_conflicts(&self, rules: &Rules, precedence: &Precedence) -> Vec<Conflict> {
+        let mut terminals: Vec<Expected> = vec![Expected::EndOfFile];
+        for (_, rhs) in rules {
+            for symbol in rhs.iter() {
+                if let RuleType::Terminal(item) = symbol {
+                    if !terminals.contains(&Expected::Token(*item)) {
+                        terminals.push(Expected::Token(*item));
+                    }
+                }
+            }
+        }
+        let index = |terminal: Expected| terminals.iter().position(|t| *t == terminal).unwrap();
+        let first: HashMap<NonTerminalType, Lookaheads> = self
+            .first
+            .iter()
+            .map(|(non_terminal, first)| {
+                let mut set = Lookaheads::default();
+                first
+                    .iter()
+                    .for_each(|item| set.insert(index(Expected::Token(*item))));
+                (*non_terminal, set)
+            })
+            .collect();
+        let first_of = |symbols: &[RuleType], after: Lookaheads| {
+            let mut set = Lookaheads::default();
+            for symbol in symbols {
+                match symbol {
+                    RuleType::Terminal(item) => {
+                        set.insert(index(Expected::Token(*item)));
+                        return set;
+                    }
+                    RuleType::NonTerminal(symbol) => {
+                        set.extend(first.get(symbol).cloned().unwrap_or_default());
+                        if !self.nullable.contains(symbol) {
+                            return set;
+                        }
+                    }
+                }
+            }
+            set.extend(after);
+            set
+        };
+        let closure = |kernel: &[(Item, Lookaheads)]| {
+            let mut items: Vec<(Item, Lookaheads)> = kernel.to_vec();
+            let mut positions: HashMap<Item, usize> = items
+                .iter()
+                .enumerate()
+                .map(|(position, (item, _))| (*item, position))
+                .collect();
+            let mut queue: VecDeque<usize> = (0..items.len()).collect();
+            while let Some(position) = queue.pop_front() {
+                let ((rule, dot), lookaheads) = items[position];
+                let rhs = rules[rule].1;
+                let next = match rhs.get(dot) {
+                    Some(RuleType::NonTerminal(next)) => *next,
+                    _ => continue,
+                };
+                let lookaheads = first_of(&rhs[dot + 1..], lookaheads);
+                for (rule, _) in rules.iter().enumerate().filter(|(_, r)| r.0 == next) {
+                    match positions.get(&(rule, 0)) {
+                        Some(&existing) => {
+                            if items[existing].1.extend(lookaheads) {
+                                queue.push_back(existing);
+                            }
+                        }
+                        None => {
+                            positions.insert((rule, 0), items.len());
+                            queue.push_back(items.len());
+                            items.push(((rule, 0), lookaheads));
+                        }
+                    }
+                }
+            }
+            items
+        };
+
+        let mut start = Lookaheads::default();
+        start.insert(index(Expected::EndOfFile));
+        let kernel: Vec<(Item, Lookaheads)> = rules
+            .iter()
+            .enumerate()
+            .filter(|(_, (lhs, _))| *lhs == NonTerminalType::Start)
+            .map(|(rule, _)| ((rule, 0), start))
+            .collect();
+
+        let mut seen: HashSet<Vec<(Item, Lookaheads)>> = HashSet::new();
+        let mut queue = vec![kernel];
+        let mut conflicts: Vec<Conflict> = Vec::new();
+        while let Some(kernel) = queue.pop() {
+            if !seen.insert(kernel.clone()) {
+                continue;
+            }
+            let items = closure(&kernel);
+
+            let mut transitions: HashMap<RuleType, Vec<(Item, Lookaheads)>> = HashMap::new();
+            let mut reductions: Vec<(usize, Lookaheads)> = Vec::new();
+            for &((rule, dot), lookaheads) in &items {
+                match rules[rule].1.get(dot) {
+                    Some(symbol) => transitions
+                        .entry(*symbol)
+                        .or_default()
+                        .push(((rule, dot + 1), lookaheads)),
+                    None => reductions.push((rule, lookaheads)),
+                }
+            }
+
+            for (terminal, &lookahead) in terminals.iter().enumerate() {
+                let mut shift = match lookahead {
+                    Expected::Token(item) => transitions.contains_key(&RuleType::Terminal(item)),
+                    Expected::EndOfFile => false,
+                };
+                let mut reducing: Vec<usize> = Vec::new();
+                for (rule, lookaheads) in &reductions {
+                    if !lookaheads.contains(terminal) {
+                        continue;
+                    }
+                    let resolution = match shift {
+                        true => precedence.resolve(rules[*rule].1, lookahead),
+                        false => Resolution::Both,
+                    };
+                    if let Resolution::Reduce | Resolution::Neither = resolution {
+                        shift = false;
+                        if let Expected::Token(item) = lookahead {
+                            transitions.remove(&RuleType::Terminal(item));
+                        }
+                    }
+                    if let Resolution::Both | Resolution::Reduce = resolution {
+                        reducing.push(*rule);
+                    }
+                }
+                let conflict = Conflict::Lr {
+                    lookahead,
+                    reductions: reducing.clone(),
+                    shift,
+                };
+                if (reducing.len() > 1 || (shift && !reducing.is_empty()))
+                    && !conflicts.contains(&conflict)
+                {
+                    conflicts.push(conflict);
+                }
+            }
+
+            for (_, mut kernel) in transitions {
+                kernel.sort_unstable_by_key(|(item, _)| *item);
+                queue.push(kernel);
+            }
+        }
+        conflicts.sort_by_key(|conflict| conflict.to_string());
+        conflicts
+    }
+}
+
+/// A rule of a grammar by index, with how much of it has been matched
+type Item = (usize, usize);
+
+/// A set of terminals, by their index in `Analysis::lr1_conflicts`
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+struct Lookaheads([u64; 4]);
+
+impl Lookaheads {
+    fn insert(&mut self, terminal: usize) {
+        self.0[terminal / 64] |= 1 << (terminal % 64);
+    }
+
+    fn contains(&self, terminal: usize) -> bool {
+        self.0[terminal / 64] & (1 << (terminal % 64)) != 0
+    }
+
+    /// Adds every terminal of `other`, returning whether any was new
+    fn extend(&mut self, other: Lookaheads) -> bool {
+        let before = *self;
+        for (word, other) in self.0.iter_mut().zip(other.0.iter()) {
+            *word |= other;
+        }
+        before != *self
+    }
+}
+
+/// Writes a rule such as `Return -> 'return' Expression ';'`
+pub(super) fn rule_text(rules: &Rules, rule: usize) -> String {
+    let (lhs, rhs) = rules[rule];
+    let mut text = format!("{:?} ->", lhs);
+    if rhs.is_empty() {
+        text += " ε";
+    }
+    for symbol in rhs.iter() {
+        match symbol {
+            RuleType::Terminal(item) => text += &format!(" {}", token_name(*item)),
+            RuleType::NonTerminal(symbol) => text += &format!(" {:?}", symbol),
+        }
+    }
+    text
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let rules = &*crate::parse::rules::RULES;
+        match self {
+            Conflict::Ll {
+                rules: (first, second),
+                lookaheads,
+            } => {
+                let names: Vec<String> = lookaheads.iter().map(Expected::to_string).collect();
+                write!(
+                    f,
+                    "LL(1): `{}` and `{}` both start with {}",
+                    rule_text(rules, *first),
+                    rule_text(rules, *second),
+                    names.join(", ")
+                )
+            }
+            Conflict::Lr {
+                lookahead,
+                reductions,
+                shift,
+            } => {
+                let names: Vec<String> = reductions
+                    .iter()
+                    .map(|rule| format!("reduce `{}`", rule_text(rules, *rule)))
+                    .collect();
+                write!(f, "LR(1): before {}, ", lookahead)?;
+                if *shift {
+                    write!(f, "shift or ")?;
+                }
+                write!(f, "{}", names.join(" or "))
+            }
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use crate::lex::errors::{LexSuccess, Span};
+use crate::lex::types::LexItem;
+use crate::parse::types::{NonTerminalType, ParseNode, ParseNodeType};
+use std::fmt::{self, Debug, Formatter};
+use std::rc::Rc;
+
+/// A node of a parse tree together with where it is in the tree. Parse nodes
+/// are shared between trees, such as after an incremental reparse, so they
+/// cannot point to their parents. Syntax nodes are made on the way down
+/// instead, and are cheap to clone.
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<SyntaxData>);
+
+struct SyntaxData {
+    node: Rc<ParseNode>,
+    parent: Option<SyntaxNode>,
+    /// Where the node is among the children of its parent
+    index: usize,
+}
+
+impl SyntaxNode {
+    /// The root of a tree, or of a subtree looked at on its own
+    pub fn new_root(node: Rc<ParseNode>) -> SyntaxNode {
+        SyntaxNode(Rc::new(SyntaxData {
+            node,
+            parent: None,
+            index: 0,
+        }))
+    }
+
+    pub fn node(&self) -> &Rc<ParseNode> {
+        &self.0.node
+    }
+
+    pub fn node_type(&self) -> &ParseNodeType {
+        &self.0.node.node_type
+    }
+
+    /// The token of a terminal
+    pub fn token(&self) -> Option<&LexSuccess> {
+        match self.node_type() {
+            ParseNodeType::Terminal(token) => Some(token),
+            _ => None,
+        }
+    }
+
+    /// Whether this holds the tokens of a syntax error
+    pub fn is_error(&self) -> bool {
+        *self.node_type() == ParseNodeType::Error
+    }
+
+    pub fn parent(&self) -> Option<&SyntaxNode> {
+        self.0.parent.as_ref()
+    }
+
+    /// Where this is among the children of its parent
+    pub fn index(&self) -> usize {
+        self.0.index
+    }
+
+    /// The parent, its parent and so on up to the root
+    pub fn ancestors(&self) -> impl Iterator<Item = SyntaxNode> {
+        std::iter::successors(self.parent().cloned(), |node| node.parent().cloned())
+    }
+
+    /// The innermost node of type `T` this is inside of
+    pub fn ancestor<T: AstNode>(&self) -> Option<T> {
+        self.ancestors().find_map(T::cast)
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = SyntaxNode> + '_ {
+        (0..self.0.node.children.len()).map(move |index| self.child(index))
+    }
+
+    pub fn child(&self, index: usize) -> SyntaxNode {
+        SyntaxNode(Rc::new(SyntaxData {
+            node: self.0.node.children[index].clone(),
+            parent: Some(self.clone()),
+            index,
+        }))
+    }
+
+    /// From the start of the first token below this to the end of the last,
+    /// or `None` if there are none
+    pub fn range(&self) -> Option<Span> {
+        Some(Span {
+            start: edge_token(self.node(), false)?.location,
+            end: edge_token(self.node(), true)?.end,
+        })
+    }
+
+    /// The characters of the source this was parsed from that `range` covers
+    pub fn text(&self, source: &str) -> String {
+        match self.range() {
+            Some(span) => source
+                .chars()
+                .skip(span.start.character)
+                .take(span.end.character - span.start.character)
+                .collect(),
+            None => String::new(),
+        }
+    }
+
+    /// The `index`th child of type `T`
+    fn nth<T: AstNode>(&self, index: usize) -> Option<T> {
+        self.children().filter_map(T::cast).nth(index)
+    }
+
+    /// The children of type `T` of this and of each child of the same type
+    /// as this, in order, which are the items of a recursive list
+    fn flatten<T: AstNode>(&self) -> Vec<T> {
+        let mut items = Vec::new();
+        let mut stack = vec![self.clone()];
+        while let Some(node) = stack.pop() {
+            if node.node_type() == self.node_type() {
+                stack.extend(node.children().collect::<Vec<_>>().into_iter().rev());
+            } else if let Some(item) = T::cast(node) {
+                items.push(item);
+            }
+        }
+        items
+    }
+
+    /// The first child which is a token matching `matches`
+    fn token_child(&self, matches: impl Fn(&LexItem) -> bool) -> Option<SyntaxNode> {
+        self.children()
+            .find(|child| child.token().is_some_and(|token| matches(&token.item)))
+    }
+}
+
+/// The first token below `node`, or the last if `last`. Only the path to it
+/// and the subtrees without tokens on the way are walked.
+fn edge_token(node: &ParseNode, last: bool) -> Option<&LexSuccess> {
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        if let ParseNodeType::Terminal(token) = &node.node_type {
+            return Some(token);
+        }
+        let children = node.children.iter().map(|child| &**child);
+        match last {
+            true => stack.extend(children),
+            false => stack.extend(children.rev()),
+        }
+    }
+    None
+}
+
+/// Syntax nodes are the same if they are the same parse node in the same
+/// place
+impl PartialEq for SyntaxNode {
+    fn eq(&self, other: &SyntaxNode) -> bool {
+        Rc::ptr_eq(self.node(), other.node())
+            && self.index() == other.index()
+            && self.parent() == other.parent()
+    }
+}
+
+impl Debug for SyntaxNode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.node_type() {
+            ParseNodeType::NonTerminal(symbol) => write!(f, "{:?}", symbol)?,
+            ParseNodeType::Error => write!(f, "Error")?,
+            ParseNodeType::Terminal(token) => write!(f, "{:?}", token.item)?,
+        }
+        match self.range() {
+            Some(span) => write!(f, "@{}..{}", span.start.character, span.end.character),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A syntax node of one `NonTerminalType`, with accessors for its parts
+pub trait AstNode: Sized {
+    const KIND: NonTerminalType;
+
+    /// The node as this type, if it is a node of `KIND`
+    fn cast(node: SyntaxNode) -> Option<Self>;
+
+    fn syntax(&self) -> &SyntaxNode;
+
+    /// A subtree as this type, looked at on its own
+    fn from_root(node: Rc<ParseNode>) -> Option<Self> {
+        Self::cast(SyntaxNode::new_root(node))
+    }
+
+    fn node(&self) -> &Rc<ParseNode> {
+        self.syntax().node()
+    }
+
+    fn range(&self) -> Option<Span> {
+        self.syntax().range()
+    }
+}
+
+/// Defines a wrapper for each nonterminal
+macro_rules! wrappers {
+    ($($name:ident)*) => {
+        /// The nonterminal of every wrapper
+        pub const KINDS: &[NonTerminalType] = &[$(NonTerminalType::$name),*];
+
+        $(
+            #[derive(Clone, Debug, PartialEq)]
+            pub struct $name(SyntaxNode);
+
+            impl AstNode for $name {
+                const KIND: NonTerminalType = NonTerminalType::$name;
+
+                fn cast(node: SyntaxNode) -> Option<Self> {
+                    match node.node_type() {
+                        ParseNodeType::NonTerminal(NonTerminalType::$name) => Some($name(node)),
+                        _ => None,
+                    }
+                }
+
+                fn syntax(&self) -> &SyntaxNode {
+                    &self.0
+                }
+            }
+        )*
+    };
+}
+
+// Every nonterminal of the grammar but those of repetitions
+include!(concat!(env!("OUT_DIR"), "/wrappers.rs"));
+
+/// Defines accessors for the parts of nonterminals. Each accessor is one of:
+///
+/// - `name: Kind[n]` for the `n`th child of its kind, which every rule has
+/// - `name: Option<Kind>[n]` for one which only some rules have
+/// - `name: Vec<Kind>` for the items of a list, which is either a repetition
+///   or recursive
+/// - `name: token Pattern` for the first token matching `Pattern`
+macro_rules! accessors {
+    ($($name:ident { $($accessors:tt)* })*) => {
+        $(
+            impl $name {
+                accessors!(@accessors $name $($accessors)*);
+            }
+        )*
+    };
+    (@accessors $name:ident) => {};
+    (@accessors $name:ident $accessor:ident: Option<$kind:ident>[$index:literal], $($rest:tt)*) => {
+        pub fn $accessor(&self) -> Option<$kind> {
+            self.0.nth($index)
+        }
+        accessors!(@accessors $name $($rest)*);
+    };
+    (@accessors $name:ident $accessor:ident: Vec<$kind:ident>, $($rest:tt)*) => {
+        pub fn $accessor(&self) -> Vec<$kind> {
+            self.0.flatten()
+        }
+        accessors!(@accessors $name $($rest)*);
+    };
+    (@accessors $name:ident $accessor:ident: $kind:ident[$index:literal], $($rest:tt)*) => {
+        pub fn $accessor(&self) -> $kind {
+            self.0.nth($index).expect(concat!(
+                "Every ",
+                stringify!($name),
+                " has a ",
+                stringify!($kind)
+            ))
+        }
+        accessors!(@accessors $name $($rest)*);
+    };
+    (@accessors $name:ident $accessor:ident: token $($pattern:pat)|+, $($rest:tt)*) => {
+        pub fn $accessor(&self) -> Option<SyntaxNode> {
+            self.0.token_child(|item| matches!(item, $($pattern)|+))
+        }
+        accessors!(@accessors $name $($rest)*);
+    };
+}
+
+// An accessor for each label in the rules of the grammar
+include!(concat!(env!("OUT_DIR"), "/accessors.rs"));
+
+impl StructOrUnion {
+    pub fn is_union(&self) -> bool {
+        self.0.token_child(|item| *item == LexItem::Union).is_some()
+    }
+}
+
+impl Args {
+    /// Whether the parameters end in `...`
+    pub fn is_variadic(&self) -> bool {
+        let mut args = self.clone();
+        loop {
+            if args
+                .0
+                .token_child(|item| *item == LexItem::Ellipsis)
+                .is_some()
+            {
+                return true;
+            }
+            match args.0.nth::<Args>(0) {
+                Some(rest) => args = rest,
+                None => return false,
+            }
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use crate::lex::errors::Location;
+use crate::lex::types::{LexItem, NumberType};
+use crate::parse::errors::token_name;
+use crate::parse::types::{ParseNode, ParseNodeType};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::mem::discriminant;
+use std::str::FromStr;
+
+/// How a parse tree is printed, as chosen with `--dump-tree=`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TreeFormat {
+    /// A Graphviz graph, with nonterminals as boxes and tokens as leaves
+    Dot,
+    /// The whole tree on one line, as in `(TopStatement ";")`
+    SExpression,
+    /// One node per line, indented by depth, with the span of each node
+    Indented,
+}
+
+impl FromStr for TreeFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "dot" => Ok(TreeFormat::Dot),
+            "sexp" => Ok(TreeFormat::SExpression),
+            "indented" => Ok(TreeFormat::Indented),
+            _ => Err(format!("unknown tree format '{}'", name)),
+        }
+    }
+}
+
+/// Prints a parse tree of `source` in `format`, ending in a newline
+pub fn dump(tree: &ParseNode, format: TreeFormat, source: &str) -> String {
+    match format {
+        TreeFormat::Dot => to_dot(tree),
+        TreeFormat::SExpression => to_s_expression(tree) + "\n",
+        TreeFormat::Indented => to_indented(tree, source),
+    }
+}
+
+enum Visit<'a> {
+    Enter(&'a ParseNode, usize),
+    Leave(&'a ParseNode),
+}
+
+/// Visits every node before and after its children. Right recursive rules
+/// nest as deeply as the input is long, so this does not recurse.
+fn walk<'a>(root: &'a ParseNode, mut visit: impl FnMut(Visit<'a>)) {
+    let mut stack = vec![(root, 0, false)];
+    while let Some((node, depth, entered)) = stack.pop() {
+        if entered {
+            visit(Visit::Leave(node));
+            continue;
+        }
+        visit(Visit::Enter(node, depth));
+        stack.push((node, depth, true));
+        for child in node.children.iter().rev() {
+            stack.push((child, depth + 1, false));
+        }
+    }
+}
+
+/// The name of a nonterminal, `Error` for a syntax error, or the spelling of
+/// a token
+fn label(node: &ParseNode) -> String {
+    match &node.node_type {
+        ParseNodeType::NonTerminal(symbol) => format!("{:?}", symbol),
+        ParseNodeType::Error => "Error".to_string(),
+        ParseNodeType::Terminal(token) => spelling(&token.item),
+    }
+}
+
+/// How a token could be written in the source
+fn spelling(item: &LexItem) -> String {
+    match item {
+        LexItem::Identifier(name) | LexItem::TypedefName(name) => name.clone(),
+        LexItem::NumericLiteral(number) => match number {
+            NumberType::Float(value) => format!("{:?}f", value),
+            NumberType::Double(value) => format!("{:?}", value),
+            NumberType::SignedChar(value) => value.to_string(),
+            NumberType::UnsignedChar(value) => value.to_string(),
+            NumberType::SignedShort(value) => value.to_string(),
+            NumberType::UnsignedShort(value) => value.to_string(),
+            NumberType::SignedInt(value) => value.to_string(),
+            NumberType::UnsignedInt(value) => format!("{}u", value),
+            NumberType::SignedLong(value) => format!("{}l", value),
+            NumberType::UnsignedLong(value) => format!("{}ul", value),
+            NumberType::SignedLongLong(value) => format!("{}ll", value),
+            NumberType::UnsignedLongLong(value) => format!("{}ull", value),
+        },
+        LexItem::StringLiteral(bytes) => format!("{:?}", String::from_utf8_lossy(bytes)),
+        item => token_name(discriminant(item))
+            .trim_matches('\'')
+            .to_string(),
+    }
+}
+
+/// A double-quoted string, escaped for both DOT and S-expressions
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Prints a Graphviz digraph of a parse tree, with children in order
+pub fn to_dot(tree: &ParseNode) -> String {
+    let mut dot = String::from("digraph parse {\n    ordering=out;\n    node [shape=box];\n");
+    let mut parents: Vec<usize> = Vec::new();
+    let mut count = 0;
+    walk(tree, |visit| match visit {
+        Visit::Enter(node, _) => {
+            let id = count;
+            count += 1;
+            let attributes = match node.node_type {
+                ParseNodeType::Terminal(_) => ", shape=plaintext",
+                ParseNodeType::Error => ", color=red",
+                ParseNodeType::NonTerminal(_) => "",
+            };
+            let _ = writeln!(
+                dot,
+                "    n{} [label={}{}];",
+                id,
+                quoted(&label(node)),
+                attributes
+            );
+            if let Some(parent) = parents.last() {
+                let _ = writeln!(dot, "    n{} -> n{};", parent, id);
+            }
+            parents.push(id);
+        }
+        Visit::Leave(_) => {
+            parents.pop();
+        }
+    });
+    dot.push_str("}\n");
+    dot
+}
+
+/// Prints a parse tree on one line, with a list for each nonterminal and an
+/// atom for each token. Tokens which are not words are quoted.
+pub fn to_s_expression(tree: &ParseNode) -> String {
+    let mut expression = String::new();
+    walk(tree, |visit| {
+        let node = match visit {
+            Visit::Enter(node, _) => node,
+            Visit::Leave(node) => {
+                if !matches!(node.node_type, ParseNodeType::Terminal(_)) {
+                    expression.push(')');
+                }
+                return;
+            }
+        };
+        if !expression.is_empty() && !expression.ends_with('(') {
+            expression.push(' ');
+        }
+        let label = label(node);
+        match node.node_type {
+            ParseNodeType::Terminal(_) => {
+                let word = label
+                    .chars()
+                    .all(|ch| ch.is_alphanumeric() || ch == '_' || ch == '.');
+                if word {
+                    expression.push_str(&label);
+                } else {
+                    expression.push_str(&quoted(&label));
+                }
+            }
+            _ => {
+                expression.push('(');
+                expression.push_str(&label);
+            }
+        }
+    });
+    expression
+}
+
+/// Prints a parse tree with one node per line, indented by depth, each with
+/// the line and column where it starts and where it ends, which is just past
+/// its last character. Empty nodes have no span.
+pub fn to_indented(tree: &ParseNode, source: &str) -> String {
+    // The first and last token of every node with any
+    let mut spans: HashMap<*const ParseNode, (Location, Location)> = HashMap::new();
+    let mut open: Vec<Option<Location>> = Vec::new();
+    let mut last = None;
+    walk(tree, |visit| match visit {
+        Visit::Enter(node, _) => match &node.node_type {
+            ParseNodeType::Terminal(token) => {
+                spans.insert(node, (token.location, token.end));
+                // Nodes without tokens so far are the innermost ones
+                let empty = open.iter_mut().rev().take_while(|start| start.is_none());
+                for start in empty {
+                    *start = Some(token.location);
+                }
+                last = Some(token.end);
+            }
+            _ => open.push(None),
+        },
+        Visit::Leave(node) => {
+            if let ParseNodeType::Terminal(_) = node.node_type {
+                return;
+            }
+            if let (Some(Some(start)), Some(end)) = (open.pop(), last) {
+                spans.insert(node, (start, end));
+            }
+        }
+    });
+
+    let lines: Vec<usize> = std::iter::once(0)
+        .chain(
+            source
+                .chars()
+                .enumerate()
+                .filter(|&(_, ch)| ch == '\n')
+                .map(|(index, _)| index + 1),
+        )
+        .collect();
+    let position = |location: Location| {
+        let line = lines.partition_point(|&start| start <= location.character);
+        let column = location.character - lines[line - 1] + 1;
+        format!("{}:{}", line, column)
+    };
+
+    let mut indented = String::new();
+    walk(tree, |visit| {
+        if let Visit::Enter(node, depth) = visit {
+            let _ = write!(indented, "{}{}", "  ".repeat(depth), label(node));
+            if let Some(&(start, end)) = spans.get(&(node as *const ParseNode)) {
+                let _ = write!(indented, " {}-{}", position(start), position(end));
+            }
+            indented.push('\n');
+        }
+    });
+    indented
+}
@@ -0,0 +1,131 @@
+use crate::lex::constants::{C99_TOKENS, GNU_TOKENS, LITERAL_TOKENS};
+use crate::lex::errors::{LexError, LexSuccess, Span};
+use crate::lex::types::{LexItem, NumberType};
+use crate::parse::ambiguity::AmbiguityReport;
+use crate::parse::types::NonTerminalType;
+use std::fmt::{self, Display, Formatter};
+use std::mem::{discriminant, Discriminant};
+
+/// What the parser found where it could not continue
+#[derive(Clone, Debug, PartialEq)]
+pub enum Found {
+    Token(LexSuccess),
+    Invalid(LexError),
+    EndOfFile,
+    /// Input with more than one parse, which is a mistake in the grammar
+    Ambiguity(Box<AmbiguityReport>),
+}
+
+/// A token the parser would have accepted
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Expected {
+    Token(Discriminant<LexItem>),
+    EndOfFile,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub found: Found,
+    pub span: Span,
+    /// Deduplicated and sorted by name
+    pub expected: Vec<Expected>,
+    /// The innermost construct with a description that was being parsed
+    pub context: Option<NonTerminalType>,
+}
+
+impl ParseError {
+    pub(super) fn new(
+        found: Found,
+        span: Span,
+        expected: impl IntoIterator<Item = Expected>,
+        context: Option<NonTerminalType>,
+    ) -> ParseError {
+        let mut deduplicated: Vec<Expected> = Vec::new();
+        for item in expected {
+            if !deduplicated.contains(&item) {
+                deduplicated.push(item);
+            }
+        }
+        deduplicated.sort_by_key(|item| item.to_string());
+
+        ParseError {
+            found,
+            span,
+            expected: deduplicated,
+            context,
+        }
+    }
+}
+
+/// A readable name for a kind of token, such as `';'` or `identifier`
+pub fn token_name(token: Discriminant<LexItem>) -> String {
+    if token == discriminant(&LexItem::Identifier(String::new())) {
+        "identifier".to_string()
+    } else if token == discriminant(&LexItem::TypedefName(String::new())) {
+        "type name".to_string()
+    } else if token == discriminant(&LexItem::NumericLiteral(NumberType::default())) {
+        "numeric literal".to_string()
+    } else if token == discriminant(&LexItem::StringLiteral(Vec::new())) {
+        "string literal".to_string()
+    } else {
+        LITERAL_TOKENS
+            .iter()
+            .chain(C99_TOKENS)
+            .chain(GNU_TOKENS)
+            .find(|(_, item)| discriminant(item) == token)
+            .map(|(key, _)| format!("'{}'", key.trim_end_matches('\x00')))
+            .unwrap_or_else(|| format!("{:?}", token))
+    }
+}
+
+impl Display for Expected {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::Token(token) => f.write_str(&token_name(*token)),
+            Expected::EndOfFile => f.write_str("end of file"),
+        }
+    }
+}
+
+impl Display for Found {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Found::Token(LexSuccess {
+                item: LexItem::Identifier(name),
+                ..
+            }) => write!(f, "identifier `{}`", name),
+            Found::Token(LexSuccess {
+                item: LexItem::TypedefName(name),
+                ..
+            }) => write!(f, "type name `{}`", name),
+            Found::Token(token) => f.write_str(&token_name(discriminant(&token.item))),
+            Found::Invalid(error) => write!(f, "invalid token ({})", error.error_type),
+            Found::EndOfFile => f.write_str("end of file"),
+            Found::Ambiguity(report) => match report.symbol.description() {
+                Some(description) => write!(f, "ambiguous {}", description),
+                None => write!(f, "ambiguous {:?}", report.symbol),
+            },
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Found::Ambiguity(report) = &self.found {
+            let ways = report.derivations.len();
+            return write!(f, "{} with {} derivations", self.found, ways);
+        }
+        write!(f, "unexpected {}", self.found)?;
+        if let Some(description) = self.context.and_then(NonTerminalType::description) {
+            write!(f, " in {}", description)?;
+        }
+        match self.expected.as_slice() {
+            [] => Ok(()),
+            [expected] => write!(f, ", expected {}", expected),
+            expected => {
+                let names: Vec<String> = expected.iter().map(Expected::to_string).collect();
+                write!(f, ", expected one of {}", names.join(", "))
+            }
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use crate::lex::errors::LexSuccess;
+use crate::parse::types::{NonTerminalType, ParseNode, ParseNodeType};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// One way of deriving a nonterminal over its span
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct Alternative {
+    /// The rule of the grammar used, `None` for tokens skipped by error recovery
+    pub(super) rule: Option<usize>,
+    pub(super) children: Vec<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub(super) enum ForestNode {
+    Terminal(LexSuccess),
+    NonTerminal {
+        symbol: NonTerminalType,
+        alternatives: Vec<Alternative>,
+    },
+}
+
+/// A shared packed parse forest.
+///
+/// There is a single node for each nonterminal and span of tokens, which holds
+/// every derivation found for it, so the derivations of an ambiguous input
+/// share all of their unambiguous parts.
+#[derive(Debug, Default)]
+pub(super) struct Forest {
+    pub(super) nodes: Vec<ForestNode>,
+    /// The terminal node of each token, by position
+    pub(super) tokens: Vec<usize>,
+    spans: HashMap<(NonTerminalType, usize, usize), usize>,
+}
+
+impl Forest {
+    /// Adds the next token of the input
+    pub(super) fn terminal(&mut self, token: LexSuccess) -> usize {
+        self.nodes.push(ForestNode::Terminal(token));
+        self.tokens.push(self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    /// The node for `symbol` from position `start` up to `end`
+    pub(super) fn non_terminal(
+        &mut self,
+        symbol: NonTerminalType,
+        start: usize,
+        end: usize,
+    ) -> usize {
+        let nodes = &mut self.nodes;
+        *self.spans.entry((symbol, start, end)).or_insert_with(|| {
+            nodes.push(ForestNode::NonTerminal {
+                symbol,
+                alternatives: Vec::new(),
+            });
+            nodes.len() - 1
+        })
+    }
+
+    pub(super) fn add_alternative(&mut self, node: usize, alternative: Alternative) {
+        if let ForestNode::NonTerminal { alternatives, .. } = &mut self.nodes[node] {
+            if !alternatives.contains(&alternative) {
+                alternatives.push(alternative);
+            }
+        }
+    }
+
+    /// Drops the derivations of `node` by rules that `preferred` rejects, as
+    /// long as one by a rule it accepts is left
+    pub(super) fn prefer(&mut self, node: usize, preferred: impl Fn(usize) -> bool) {
+        if let ForestNode::NonTerminal { alternatives, .. } = &mut self.nodes[node] {
+            let is_preferred = |alternative: &Alternative| alternative.rule.is_some_and(&preferred);
+            if alternatives.iter().any(is_preferred) {
+                alternatives.retain(is_preferred);
+            }
+        }
+    }
+
+    /// A node for `symbol` covering the tokens from `start` up to `end`, which
+    /// could not be parsed
+    pub(super) fn error(&mut self, symbol: NonTerminalType, start: usize, end: usize) -> usize {
+        self.nodes.push(ForestNode::NonTerminal {
+            symbol,
+            alternatives: vec![Alternative {
+                rule: None,
+                children: self.tokens[start..end].to_vec(),
+            }],
+        });
+        self.nodes.len() - 1
+    }
+
+    /// The positions of the tokens a nonterminal node covers, from its first
+    /// up to the one after its last
+    pub(super) fn positions(&self, node: usize) -> Option<(usize, usize)> {
+        self.spans
+            .iter()
+            .find(|(_, &found)| found == node)
+            .map(|(&(_, start, end), _)| (start, end))
+    }
+
+    pub(super) fn token(&self, position: usize) -> &LexSuccess {
+        match &self.nodes[self.tokens[position]] {
+            ForestNode::Terminal(token) => token,
+            ForestNode::NonTerminal { .. } => unreachable!(),
+        }
+    }
+
+    /// Whether any node reachable from `root` has more than one derivation
+    pub(super) fn is_ambiguous(&self, root: usize) -> bool {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            if std::mem::replace(&mut seen[node], true) {
+                continue;
+            }
+            if let ForestNode::NonTerminal { alternatives, .. } = &self.nodes[node] {
+                if alternatives.len() > 1 {
+                    return true;
+                }
+                stack.extend(alternatives.iter().flat_map(|a| a.children.iter()));
+            }
+        }
+        false
+    }
+
+    /// Builds the parse tree of `root`, taking the first derivation of every node
+    pub(super) fn tree(&self, root: usize) -> Rc<ParseNode> {
+        self.derivation(root, 0)
+    }
+
+    /// Builds the parse tree of `root` by its derivation `alternative`, taking
+    /// the first derivation of every node below it
+    pub(super) fn derivation(&self, root: usize, alternative: usize) -> Rc<ParseNode> {
+        let mut built: HashMap<usize, Rc<ParseNode>> = HashMap::new();
+        // Children are built before their parents without recursing, as right
+        // recursive rules nest as deeply as the input is long
+        let mut stack = vec![(root, false)];
+        while let Some((node, expanded)) = stack.pop() {
+            if built.contains_key(&node) {
+                continue;
+            }
+            let (symbol, alternative) = match &self.nodes[node] {
+                ForestNode::Terminal(token) => {
+                    built.insert(node, Rc::new(token.clone().into()));
+                    continue;
+                }
+                ForestNode::NonTerminal {
+                    symbol,
+                    alternatives,
+                } => match node == root {
+                    true => (*symbol, &alternatives[alternative]),
+                    false => (*symbol, &alternatives[0]),
+                },
+            };
+            if !expanded {
+                stack.push((node, true));
+                stack.extend(alternative.children.iter().map(|&child| (child, false)));
+                continue;
+            }
+
+            // The items of a repetition belong to the node it is in
+            let children = alternative.children.iter().flat_map(|child| {
+                let built = &built[child];
+                match built.node_type {
+                    ParseNodeType::NonTerminal(symbol) if symbol.is_hidden() => {
+                        built.children.clone()
+                    }
+                    _ => vec![built.clone()],
+                }
+            });
+            let children = match alternative.rule {
+                Some(_) => children.collect(),
+                None => vec![Rc::new(ParseNode {
+                    node_type: ParseNodeType::Error,
+                    children: children.collect(),
+                })],
+            };
+            built.insert(
+                node,
+                Rc::new(ParseNode {
+                    node_type: ParseNodeType::NonTerminal(symbol),
+                    children,
+                }),
+            );
+        }
+        built.remove(&root).unwrap()
+    }
+}
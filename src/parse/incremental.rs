@@ -0,0 +1,383 @@
+use crate::lex::errors::{LexError, LexResult, LexSuccess, Location, Span};
+use crate::lex::Lexer;
+use crate::parse::ambiguity::{AmbiguityReport, Derivation};
+use crate::parse::errors::{Found, ParseError};
+use crate::parse::parser::{parse_after, translation_unit};
+use crate::parse::symbols::TypedefTable;
+use crate::parse::types::{NonTerminalType, ParseNode, ParseNodeType};
+use crate::standard::Standard;
+use std::rc::Rc;
+
+/// Replaces the characters of `span` in a source with `replacement`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Edit {
+    pub span: Span,
+    pub replacement: String,
+}
+
+impl Edit {
+    /// The source after the edit
+    pub fn apply(&self, source: &str) -> String {
+        let mut chars = source.chars();
+        let mut edited: String = chars.by_ref().take(self.span.start.character).collect();
+        edited.push_str(&self.replacement);
+        edited.extend(chars.skip(self.span.end.character - self.span.start.character));
+        edited
+    }
+
+    /// How many characters longer the source gets
+    fn growth(&self) -> isize {
+        self.replacement.chars().count() as isize
+            - (self.span.end.character - self.span.start.character) as isize
+    }
+}
+
+/// A translation unit parsed with everything needed to reparse it after an
+/// edit
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parsed {
+    pub standard: Standard,
+    /// The tokens of the source as they were lexed, before the parser found
+    /// the typedef names among them
+    pub tokens: Vec<LexResult>,
+    pub tree: Rc<ParseNode>,
+    pub errors: Vec<ParseError>,
+}
+
+impl Parsed {
+    /// Lexes and parses a whole source
+    pub fn new(source: &str, standard: Standard) -> Parsed {
+        let tokens: Vec<LexResult> = Lexer::with_standard(source.chars(), standard).collect();
+        let (tree, errors, _) = parse_after(tokens.iter().cloned(), standard, TypedefTable::new());
+        Parsed {
+            standard,
+            tokens,
+            tree,
+            errors,
+        }
+    }
+
+    /// Parses `source`, which is the previous source after `edit`, with the
+    /// same result as `Parsed::new`.
+    ///
+    /// Only the top-level declarations the edit touches are relexed and
+    /// reparsed. The others keep their subtrees, which are copied with new
+    /// locations if they come after the edit and it changed the length of the
+    /// source. The reparsed region grows while it does not end where an
+    /// unchanged declaration starts, such as when a `}` was deleted, and up to
+    /// the end if it declares different typedef names, which changes how the
+    /// rest is parsed.
+    pub fn reparse(&self, source: &str, edit: &Edit) -> Parsed {
+        let statements = top_statements(&self.tree);
+        let ranges = token_ranges(&statements, &self.tokens);
+        let count = statements.len();
+        let growth = edit.growth();
+        let (start, end) = (edit.span.start.character, edit.span.end.character);
+
+        // Where each declaration starts and ends, if it has any tokens
+        let span = |index: usize| {
+            let (first, last) = ranges[index];
+            let tokens = &self.tokens[first..last];
+            let last = tokens.iter().rev().find_map(|token| token.as_ref().ok())?;
+            Some((location(&tokens[0]), last.end.character))
+        };
+        let touched = |index: &usize| match span(*index) {
+            Some((first, last)) => first <= end && last >= start,
+            None => true,
+        };
+        let (mut first, mut last) = match (0..count).find(touched) {
+            Some(first) => (first, (0..count).rev().find(touched).unwrap() + 1),
+            None => {
+                let next = (0..count)
+                    .find(|&index| span(index).is_none_or(|(first, _)| first > end))
+                    .unwrap_or(count);
+                (next, next)
+            }
+        };
+        // Recovery from a syntax error looks at the token after it to see
+        // where the erroneous declaration ends
+        while first > 0 && is_error(&statements[first - 1]) {
+            first -= 1;
+        }
+
+        let previous_end = match first {
+            0 => 0,
+            _ => span(first - 1).unwrap().1,
+        };
+        let first_token = match first {
+            0 => 0,
+            _ => ranges[first - 1].1,
+        };
+        let token_start = |index: usize| match index {
+            _ if index == count => self.tokens.len(),
+            _ => ranges[index].0,
+        };
+
+        // Relex until a token starts where an unchanged declaration did, or
+        // up to the end
+        let mut region = Vec::new();
+        let lexer = Lexer::with_standard(source.chars().skip(previous_end), self.standard);
+        let mut tokens = lexer.map(|token| shift(&token, previous_end as isize));
+        let moved_start = |index: usize| span(index).map(|(first, _)| moved(first, growth));
+        loop {
+            let token = match tokens.next() {
+                Some(token) => token,
+                None => {
+                    last = count;
+                    break;
+                }
+            };
+            let position = location(&token);
+            while last < count && moved_start(last).is_none_or(|first| first < position) {
+                last += 1;
+            }
+            if last < count && moved_start(last) == Some(position) {
+                break;
+            }
+            region.push(token);
+        }
+
+        let declare = |table: TypedefTable, index: usize| {
+            self.declare(table, &statements[index], ranges[index])
+        };
+        let before = (0..first).fold(TypedefTable::new(), declare);
+        loop {
+            // An erroneous declaration may report its error at the token
+            // after it, which has to be reparsed with it
+            let mut next = last;
+            while next > first && next < count && is_error(&statements[next - 1]) {
+                next += 1;
+            }
+            let added = &self.tokens[token_start(last)..token_start(next)];
+            region.extend(added.iter().map(|token| shift(token, growth)));
+            last = next;
+
+            let (tree, errors, after) =
+                parse_after(region.iter().cloned(), self.standard, before.clone());
+            let reparsed = top_statements(&tree);
+            let clean = !errors.iter().any(|error| error.found == Found::EndOfFile)
+                && !reparsed.last().is_some_and(|statement| is_error(statement));
+            let same_typedefs = clean && {
+                let old = (first..last).fold(before.clone(), declare);
+                old.file_scope_typedefs() == after.file_scope_typedefs()
+            };
+
+            if last == count || same_typedefs {
+                let old_end = match last {
+                    _ if last == count => usize::MAX,
+                    _ => span(last).map_or(usize::MAX, |(first, _)| first),
+                };
+                let statements: Vec<Rc<ParseNode>> = statements[..first]
+                    .iter()
+                    .cloned()
+                    .chain(reparsed)
+                    .chain(
+                        statements[last..]
+                            .iter()
+                            .map(|node| shift_node(node, growth)),
+                    )
+                    .collect();
+                let tokens = self.tokens[..first_token]
+                    .iter()
+                    .cloned()
+                    .chain(region)
+                    .chain(
+                        self.tokens[token_start(last)..]
+                            .iter()
+                            .map(|token| shift(token, growth)),
+                    )
+                    .collect();
+                let errors = self
+                    .errors
+                    .iter()
+                    .filter(|error| error.span.start.character < previous_end)
+                    .cloned()
+                    .chain(errors)
+                    .chain(
+                        self.errors
+                            .iter()
+                            .filter(|error| error.span.start.character >= old_end)
+                            .map(|error| shift_error(error, growth)),
+                    )
+                    .collect();
+                return Parsed {
+                    standard: self.standard,
+                    tokens,
+                    tree: translation_unit(statements),
+                    errors,
+                };
+            }
+
+            // Unchanged declarations may now be inside the region, such as
+            // after a deleted `}`, so it grows faster the more it needed
+            let next = match clean {
+                true => count,
+                false => count.min(last + (last - first).max(1)),
+            };
+            let added = &self.tokens[token_start(last)..token_start(next)];
+            region.extend(added.iter().map(|token| shift(token, growth)));
+            last = next;
+        }
+    }
+
+    /// Adds the names a top-level declaration declared to `table`. Those of a
+    /// syntax error cannot be read from the tree, so its tokens are parsed
+    /// again.
+    fn declare(
+        &self,
+        mut table: TypedefTable,
+        statement: &ParseNode,
+        (first, last): (usize, usize),
+    ) -> TypedefTable {
+        if is_error(statement) {
+            let tokens = self.tokens[first..last].iter().cloned();
+            parse_after(tokens, self.standard, table).2
+        } else {
+            table.replay(statement);
+            table
+        }
+    }
+}
+
+/// The top-level declarations of a translation unit
+pub(super) fn top_statements(tree: &Rc<ParseNode>) -> Vec<Rc<ParseNode>> {
+    let mut statements = Vec::new();
+    let mut rest = &tree.children[0];
+    while let [statement, next] = &rest.children[..] {
+        statements.push(statement.clone());
+        rest = next;
+    }
+    statements
+}
+
+/// Whether a top-level declaration is a syntax error, maybe after attributes
+fn is_error(statement: &ParseNode) -> bool {
+    match statement.children.last() {
+        Some(child) => match child.node_type {
+            ParseNodeType::Error => true,
+            ParseNodeType::NonTerminal(NonTerminalType::TopStatement) => is_error(child),
+            _ => false,
+        },
+        None => false,
+    }
+}
+
+/// The range of `tokens` each declaration was parsed from. Invalid tokens
+/// between declarations belong to none of them.
+fn token_ranges(statements: &[Rc<ParseNode>], tokens: &[LexResult]) -> Vec<(usize, usize)> {
+    let mut position = 0;
+    statements
+        .iter()
+        .map(|statement| {
+            while tokens.get(position).is_some_and(Result::is_err) {
+                position += 1;
+            }
+            let first = position;
+            let mut remaining = terminals(statement);
+            while remaining > 0 {
+                if tokens[position].is_ok() {
+                    remaining -= 1;
+                }
+                position += 1;
+            }
+            (first, position)
+        })
+        .collect()
+}
+
+fn terminals(node: &ParseNode) -> usize {
+    match node.node_type {
+        ParseNodeType::Terminal(_) => 1,
+        _ => node.children.iter().map(|child| terminals(child)).sum(),
+    }
+}
+
+/// Where a token or an invalid token starts
+fn location(token: &LexResult) -> usize {
+    match token {
+        Ok(token) => token.location.character,
+        Err(error) => error.location.character,
+    }
+}
+
+fn moved(character: usize, growth: isize) -> usize {
+    (character as isize + growth) as usize
+}
+
+fn shift_location(location: Location, growth: isize) -> Location {
+    Location {
+        character: moved(location.character, growth),
+    }
+}
+
+fn shift_span(span: Span, growth: isize) -> Span {
+    Span {
+        start: shift_location(span.start, growth),
+        end: shift_location(span.end, growth),
+    }
+}
+
+fn shift_token(token: &LexSuccess, growth: isize) -> LexSuccess {
+    LexSuccess {
+        item: token.item.clone(),
+        location: shift_location(token.location, growth),
+        end: shift_location(token.end, growth),
+    }
+}
+
+fn shift_lex_error(error: &LexError, growth: isize) -> LexError {
+    LexError {
+        error_type: error.error_type.clone(),
+        location: shift_location(error.location, growth),
+    }
+}
+
+pub(super) fn shift(token: &LexResult, growth: isize) -> LexResult {
+    match token {
+        Ok(token) => Ok(shift_token(token, growth)),
+        Err(error) => Err(shift_lex_error(error, growth)),
+    }
+}
+
+/// A subtree moved by `growth` characters, which is the same subtree if it
+/// did not move
+fn shift_node(node: &Rc<ParseNode>, growth: isize) -> Rc<ParseNode> {
+    if growth == 0 {
+        return node.clone();
+    }
+    Rc::new(ParseNode {
+        node_type: match &node.node_type {
+            ParseNodeType::Terminal(token) => ParseNodeType::Terminal(shift_token(token, growth)),
+            node_type => node_type.clone(),
+        },
+        children: node
+            .children
+            .iter()
+            .map(|child| shift_node(child, growth))
+            .collect(),
+    })
+}
+
+fn shift_error(error: &ParseError, growth: isize) -> ParseError {
+    ParseError {
+        found: match &error.found {
+            Found::Token(token) => Found::Token(shift_token(token, growth)),
+            Found::Invalid(error) => Found::Invalid(shift_lex_error(error, growth)),
+            Found::EndOfFile => Found::EndOfFile,
+            Found::Ambiguity(report) => Found::Ambiguity(Box::new(AmbiguityReport {
+                span: shift_span(report.span, growth),
+                derivations: report
+                    .derivations
+                    .iter()
+                    .map(|derivation| Derivation {
+                        tree: shift_node(&derivation.tree, growth),
+                        ..derivation.clone()
+                    })
+                    .collect(),
+                ..**report
+            })),
+        },
+        span: shift_span(error.span, growth),
+        ..error.clone()
+    }
+}
@@ -1,5 +1,21 @@
+pub mod ambiguity;
+#[allow(dead_code)]
+mod analysis;
+#[allow(dead_code)]
+pub mod cst;
+pub mod dump;
+pub mod errors;
+mod forest;
+#[allow(dead_code)]
+pub mod incremental;
 pub mod parser;
+#[allow(dead_code)]
+mod precedence;
 pub mod rules;
+mod symbols;
+mod table;
 #[cfg(test)]
 mod tests;
 pub mod types;
+#[allow(dead_code)]
+pub mod variability;
@@ -1,294 +1,866 @@
-#![allow(unused_variables)]
-use super::types::{ParseNode, ParseNodeType};
-use crate::lex::errors::{LexResult, LexSuccess};
+use super::types::ParseNode;
+use crate::lex::errors::{LexResult, LexSuccess, Location, Span};
 use crate::lex::types::LexItem;
-use crate::parse::types::NonTerminalType;
-use crate::parse::types::RuleType;
+use crate::parse::ambiguity::AmbiguityReport;
+use crate::parse::errors::{Expected, Found, ParseError};
+use crate::parse::forest::{Alternative, Forest, ForestNode};
+use crate::parse::symbols::{TypedefTable, DECLARING};
+use crate::parse::table::{table, Table};
+use crate::parse::types::{NonTerminalType, ParseNodeType, RuleType};
+use crate::standard::Standard;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::mem::discriminant;
-use std::mem::Discriminant;
 use std::rc::Rc;
 
-use lazy_static::lazy_static;
+/// Nonterminals a syntax error can be confined to
+const RECOVERY_POINTS: &[NonTerminalType] =
+    &[NonTerminalType::Statement, NonTerminalType::TopStatement];
 
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
-use std::iter;
+/// Rules whose derivation wins where another rule of the same nonterminal
+/// derives the same tokens. A parameter which could either be named by a
+/// typedef name or have it as its type has it as its type (C11 6.7.6.3p11),
+/// so `void f(int (T))` takes a function rather than an int named `T`.
+const PREFERRED: &[(NonTerminalType, &[RuleType])] = &[(
+    NonTerminalType::TypeWithMaybeIdentifier,
+    &[RuleType::NonTerminal(NonTerminalType::TypeName)],
+)];
 
-#[derive(Clone, Debug)]
-struct RuleState<'a> {
-    pub rule: &'a [RuleType],
-    pub parent: Option<Rc<RuleState<'a>>>,
-    pub self_node: Rc<ParseNode>,
+/// A node of the graph-structured stack. Every path from a node down to the
+/// bottom is one parse stack, and stacks share their common parts.
+#[derive(Debug)]
+struct GssNode {
+    state: usize,
+    /// How many tokens were read before this node
+    position: usize,
+    links: RefCell<Vec<Link>>,
 }
 
-trait UnwrapOrClone {
-    type Item;
-    fn unwrap_or_clone(self) -> Self::Item;
+/// An edge to the node below, labelled with the forest node of the symbol in between
+#[derive(Clone, Debug)]
+struct Link {
+    to: Rc<GssNode>,
+    node: usize,
 }
 
-impl<T: Clone> UnwrapOrClone for Rc<T> {
-    type Item = T;
+/// Identifies a link by the node it starts from and its index there
+type LinkId = (*const GssNode, usize);
+
+impl GssNode {
+    fn new(state: usize, position: usize) -> Rc<GssNode> {
+        Rc::new(GssNode {
+            state,
+            position,
+            links: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Every path of `length` links down from this node, which has to use
+    /// `required` if it is given. Returns where each path ends and the forest
+    /// nodes along it, bottom first.
+    fn paths(
+        self: &Rc<Self>,
+        length: usize,
+        required: Option<LinkId>,
+    ) -> Vec<(Rc<GssNode>, Vec<usize>)> {
+        fn walk(
+            node: &Rc<GssNode>,
+            length: usize,
+            required: Option<LinkId>,
+            children: &mut Vec<usize>,
+            paths: &mut Vec<(Rc<GssNode>, Vec<usize>)>,
+        ) {
+            if length == 0 {
+                if required.is_none() {
+                    paths.push((node.clone(), children.iter().rev().cloned().collect()));
+                }
+                return;
+            }
+            for (index, link) in node.links.borrow().iter().enumerate() {
+                let required = required.filter(|&id| id != (Rc::as_ptr(node), index));
+                children.push(link.node);
+                walk(&link.to, length - 1, required, children, paths);
+                children.pop();
+            }
+        }
 
-    fn unwrap_or_clone(self) -> T {
-        Rc::try_unwrap(self).unwrap_or_else(|rc| (*rc).clone())
+        let mut paths = Vec::new();
+        walk(self, length, required, &mut Vec::new(), &mut paths);
+        paths
+    }
+
+    /// The node `length` links down along any path
+    fn below(self: &Rc<Self>, length: usize) -> Rc<GssNode> {
+        (0..length).fold(self.clone(), |node, _| {
+            let below = node.links.borrow()[0].to.clone();
+            below
+        })
     }
 }
 
-fn find_non_terminal<'a>(
-    non_terminal: NonTerminalType,
-    intermediate: Vec<(NonTerminalType, &'a [RuleType])>,
-    rules: &[(NonTerminalType, &'a [RuleType])],
-) -> Vec<Vec<(NonTerminalType, &'a [RuleType])>> {
-    let last = *intermediate.last().unwrap();
-    match last.1.get(0) {
-        Some(RuleType::NonTerminal(current_rule)) => {
-            if *current_rule == non_terminal {
-                return vec![intermediate];
+/// The tops of every parse stack after the same number of tokens
+struct Frontier {
+    table: &'static Table,
+    position: usize,
+    nodes: Vec<Rc<GssNode>>,
+    by_state: HashMap<usize, usize>,
+}
+
+impl Frontier {
+    fn new(table: &'static Table, node: Rc<GssNode>) -> Frontier {
+        Frontier {
+            table,
+            position: node.position,
+            by_state: vec![(node.state, 0)].into_iter().collect(),
+            nodes: vec![node],
+        }
+    }
+
+    /// Does every reduction possible before one of `lookaheads`, or before any
+    /// token if there are none. The forest nodes of the declarations that were
+    /// reduced are added to `declarations`.
+    fn reduce(
+        &mut self,
+        lookaheads: Option<&[Expected]>,
+        forest: &mut Forest,
+        declarations: &mut Vec<usize>,
+    ) {
+        let mut queue: VecDeque<(Rc<GssNode>, usize, Option<LinkId>)> = VecDeque::new();
+        for node in &self.nodes {
+            for rule in self.table.reductions(node.state, lookaheads) {
+                queue.push_back((node.clone(), rule, None));
             }
-            for (_, int) in intermediate.iter().rev().skip(1) {
-                if int[0] == RuleType::NonTerminal(*current_rule) {
-                    return Vec::new();
+        }
+
+        while let Some((node, rule, required)) = queue.pop_front() {
+            let (lhs, rhs) = self.table.rules[rule];
+            for (below, children) in node.paths(rhs.len(), required) {
+                let target = self.table.states[below.state].gotos[&lhs];
+                let reduced = forest.non_terminal(lhs, below.position, self.position);
+                forest.add_alternative(
+                    reduced,
+                    Alternative {
+                        rule: Some(rule),
+                        children,
+                    },
+                );
+                if let Some((_, preferred)) = PREFERRED.iter().find(|(symbol, _)| *symbol == lhs) {
+                    let rules = self.table.rules;
+                    forest.prefer(reduced, |rule| rules[rule].1 == *preferred);
+                }
+                if DECLARING.contains(&lhs) && !declarations.contains(&reduced) {
+                    declarations.push(reduced);
+                }
+
+                if let Some(&index) = self.by_state.get(&target) {
+                    let existing = self.nodes[index].clone();
+                    let mut links = existing.links.borrow_mut();
+                    // Links between the same nodes are labelled with the same forest node
+                    if links.iter().any(|link| Rc::ptr_eq(&link.to, &below)) {
+                        continue;
+                    }
+                    links.push(Link {
+                        to: below,
+                        node: reduced,
+                    });
+                    // Paths from any node of the frontier can lead through the new link
+                    let link = (Rc::as_ptr(&existing), links.len() - 1);
+                    drop(links);
+                    for node in &self.nodes {
+                        for rule in self.table.reductions(node.state, lookaheads) {
+                            if !self.table.rules[rule].1.is_empty() {
+                                queue.push_back((node.clone(), rule, Some(link)));
+                            }
+                        }
+                    }
+                } else {
+                    let created = GssNode::new(target, self.position);
+                    created.links.borrow_mut().push(Link {
+                        to: below,
+                        node: reduced,
+                    });
+                    self.by_state.insert(target, self.nodes.len());
+                    self.nodes.push(created.clone());
+                    for rule in self.table.reductions(target, lookaheads) {
+                        queue.push_back((created.clone(), rule, None));
+                    }
                 }
             }
-            rules
-                .iter()
-                .filter(|(key, _)| *key == *current_rule)
-                .flat_map(|(key, value)| {
-                    let mut clone = intermediate.clone();
-                    clone.push((*key, value));
-                    find_non_terminal(non_terminal, clone, rules).into_iter()
-                })
-                .collect()
         }
+    }
+
+    /// Shifts `token` onto every stack that accepts it, or returns `None` if
+    /// no stack does
+    fn shift(&self, token: &LexSuccess, forest: &mut Forest) -> Option<Frontier> {
+        let item = discriminant(&token.item);
+        let mut next: Option<Frontier> = None;
+        let mut terminal = None;
+        for node in &self.nodes {
+            let target = match self.table.states[node.state].shifts.get(&item) {
+                Some(&target) => target,
+                None => continue,
+            };
+            let terminal = *terminal.get_or_insert_with(|| forest.terminal(token.clone()));
+            let link = Link {
+                to: node.clone(),
+                node: terminal,
+            };
+            match &mut next {
+                Some(next) => next.push(target, link),
+                None => {
+                    let created = GssNode::new(target, self.position + 1);
+                    created.links.borrow_mut().push(link);
+                    next = Some(Frontier::new(self.table, created));
+                }
+            }
+        }
+        next
+    }
+
+    fn push(&mut self, state: usize, link: Link) {
+        match self.by_state.get(&state) {
+            Some(&index) => self.nodes[index].links.borrow_mut().push(link),
+            None => {
+                let created = GssNode::new(state, self.position);
+                created.links.borrow_mut().push(link);
+                self.by_state.insert(state, self.nodes.len());
+                self.nodes.push(created);
+            }
+        }
+    }
+
+    /// The forest node of a complete parse of `non_terminal` starting at `bottom`
+    fn accepted(&self, non_terminal: NonTerminalType, bottom: &Rc<GssNode>) -> Option<usize> {
+        let state = self.table.accepting(non_terminal)?;
+        let node = &self.nodes[*self.by_state.get(&state)?];
+        let links = node.links.borrow();
+        links
+            .iter()
+            .find(|link| Rc::ptr_eq(&link.to, bottom))
+            .map(|link| link.node)
+    }
+
+    /// The terminals some stack parsing `goal` from `bottom` can continue with.
+    /// Only meaningful once every reduction has been done.
+    fn expected(&self, goal: NonTerminalType, bottom: &Rc<GssNode>) -> Vec<Expected> {
+        let mut expected: Vec<Expected> = self
+            .nodes
+            .iter()
+            .flat_map(|node| self.table.states[node.state].shifts.keys())
+            .map(|&item| Expected::Token(item))
+            .collect();
+        // The file may only end between top-level declarations, or after a
+        // whole fragment
+        let between = goal == NonTerminalType::TopStatement && self.position == bottom.position;
+        if between || self.accepted(goal, bottom).is_some() {
+            expected.push(Expected::EndOfFile);
+        }
+        expected
+    }
 
-        _ => Vec::new(),
+    /// The outermost of the innermost constructs with a description that each
+    /// stack is in the middle of
+    fn context(&self) -> Option<NonTerminalType> {
+        let mut contexts = HashMap::new();
+        self.nodes
+            .iter()
+            .filter_map(|node| context(self.table, node, &mut contexts))
+            .min_by_key(|(start, _)| *start)
+            .map(|(_, context)| context)
     }
 }
 
-fn left_recursions_map(
-) -> HashMap<NonTerminalType, Vec<Vec<(NonTerminalType, &'static [RuleType])>>> {
-    let mut map: HashMap<NonTerminalType, Vec<Vec<(NonTerminalType, &'static [RuleType])>>> =
-        HashMap::new();
-    let rules = *super::rules::RULES;
-
-    for rule in rules {
-        let key = rule.0;
-        match map.entry(key) {
-            Entry::Occupied(mut o) => {
-                o.get_mut()
-                    .extend_from_slice(&find_non_terminal(key, vec![*rule], rules));
+/// The innermost construct with a description that `node` is in the middle of,
+/// and the position it starts at
+fn context(
+    table: &Table,
+    node: &Rc<GssNode>,
+    contexts: &mut HashMap<*const GssNode, Option<(usize, NonTerminalType)>>,
+) -> Option<(usize, NonTerminalType)> {
+    if let Some(context) = contexts.get(&Rc::as_ptr(node)) {
+        return *context;
+    }
+    contexts.insert(Rc::as_ptr(node), None);
+
+    let found = table.states[node.state]
+        .items
+        .iter()
+        .filter(|&&(rule, dot)| dot > 0 && dot < table.rules[rule].1.len())
+        .filter_map(|&(rule, dot)| {
+            let start = node.below(dot);
+            let lhs = table.rules[rule].0;
+            let top_level = table.states[start.state]
+                .gotos
+                .contains_key(&NonTerminalType::TopStatement);
+            match lhs.description() {
+                Some(_) => Some((start.position, lhs)),
+                // Nothing outside of a top-level declaration has a description
+                None if top_level => None,
+                None => context(table, &start, contexts),
             }
-            Entry::Vacant(v) => {
-                v.insert(find_non_terminal(key, vec![*rule], rules));
+        })
+        .min_by_key(|(start, _)| *start);
+    contexts.insert(Rc::as_ptr(node), found);
+    found
+}
+
+/// The lookaheads to reduce before when `item` is next. Identifiers may turn out
+/// to be typedef names once the declarations before them are reduced.
+fn lookaheads(item: &LexItem) -> Vec<Expected> {
+    match item {
+        LexItem::Identifier(_) => vec![
+            Expected::Token(discriminant(item)),
+            Expected::Token(discriminant(&LexItem::TypedefName(String::new()))),
+        ],
+        _ => vec![Expected::Token(discriminant(item))],
+    }
+}
+
+/// Finds where an error in the middle of `frontier` can be confined to: a node
+/// waiting for one of the `RECOVERY_POINTS` which has started parsing it. The
+/// innermost one is the latest, or the earliest if `outermost` is set.
+fn recovery_point(
+    frontier: &Frontier,
+    forest: &Forest,
+    outermost: bool,
+) -> Option<(Rc<GssNode>, NonTerminalType)> {
+    // A node whose next symbol is already the recovery point has finished it
+    let is_point = |node: &Rc<GssNode>, next: Option<NonTerminalType>| {
+        RECOVERY_POINTS
+            .iter()
+            .find(|point| {
+                frontier.table.states[node.state].gotos.contains_key(point) && next != Some(**point)
+            })
+            .map(|point| (node.clone(), *point))
+    };
+
+    let mut points: Vec<(Rc<GssNode>, NonTerminalType)> = Vec::new();
+    let mut seen: HashSet<*const GssNode> = HashSet::new();
+    let mut queue: VecDeque<Rc<GssNode>> = VecDeque::new();
+    for node in &frontier.nodes {
+        points.extend(is_point(node, None));
+        seen.insert(Rc::as_ptr(node));
+        queue.push_back(node.clone());
+    }
+    while let Some(node) = queue.pop_front() {
+        for link in node.links.borrow().iter() {
+            let next = match forest.nodes[link.node] {
+                ForestNode::NonTerminal { symbol, .. } => Some(symbol),
+                ForestNode::Terminal(_) => None,
+            };
+            points.extend(is_point(&link.to, next));
+            if seen.insert(Rc::as_ptr(&link.to)) {
+                queue.push_back(link.to.clone());
             }
-        };
+        }
+    }
+
+    // Statements are nested in top-level declarations, so they are further in
+    let depth = |(node, point): &(Rc<GssNode>, NonTerminalType)| {
+        (node.position, *point == NonTerminalType::Statement)
+    };
+    if outermost {
+        points
+            .into_iter()
+            .min_by_key(|point| (depth(point).0, !depth(point).1))
+    } else {
+        points.into_iter().max_by_key(depth)
     }
-    map
 }
 
-lazy_static! {
-    static ref LEFT_RECURSIONS: HashMap<NonTerminalType, Vec<Vec<(NonTerminalType, &'static [RuleType])>>> =
-        left_recursions_map();
+/// Whether `item` starts a new statement or declaration and can end an error
+/// confined to `point`
+fn starts_construct(item: &LexItem, point: NonTerminalType) -> bool {
+    use crate::lex::types::LexItem::*;
+    match item {
+        Typedef | Struct | Union | Enum | Int | Long | Float | Short | Char | Double | Unsigned
+        | Signed | Void | Bool | Register | Volatile | Const | Static | Extern | Auto | Inline
+        | Restrict | Noreturn | ThreadLocal | Atomic | Complex | Alignas | StaticAssert
+        | Attribute | Extension | Typeof | BuiltinVaList | Int128 => true,
+        If | For | While | Do | Return | Switch | Case | Default | Break | Continue | Goto => {
+            point == NonTerminalType::Statement
+        }
+        _ => false,
+    }
 }
 
-impl<'a> RuleState<'a> {
-    pub fn new_start(rules: &[(NonTerminalType, &'a [RuleType])]) -> RuleState<'a> {
-        let rule = {
-            let rules = search_rule(rules, NonTerminalType::Start);
-            if rules.len() > 2 {
-                panic!("There is more than one Start rule, which is not allowed.")
-            } else {
-                *rules.first().unwrap()
+/// Replaces the construct that `frontier` failed in with an error node. Tokens
+/// are skipped until a `;` or a `}` closing the construct, or until the start
+/// of the next statement or declaration. Returns the frontier to continue with
+/// and the token it should see next, if that was already read.
+fn recover<T: Iterator<Item = LexResult>>(
+    frontier: &Frontier,
+    token: Option<LexSuccess>,
+    tokens: &mut T,
+    errors: &mut Vec<ParseError>,
+    forest: &mut Forest,
+    table: &mut TypedefTable,
+) -> Option<(Frontier, Option<LexSuccess>)> {
+    let (point_node, point) = recovery_point(frontier, forest, token.is_none())?;
+    let start = point_node.position;
+
+    let partial: Vec<&LexItem> = (start..frontier.position)
+        .map(|position| &forest.token(position).item)
+        .collect();
+    let count = |item: LexItem| partial.iter().filter(|&&partial| *partial == item).count();
+    let mut braces = count(LexItem::LeftCurlyBrace).saturating_sub(count(LexItem::RightCurlyBrace));
+    let mut parens = count(LexItem::LeftParen).saturating_sub(count(LexItem::RightParen));
+    // Only a for-loop header has semicolons inside parentheses
+    let in_for = partial.first() == Some(&&LexItem::For);
+    let mut skipped = partial.len();
+
+    let mut next = token;
+    let lookahead = loop {
+        let token = match next.take().map(Ok).or_else(|| tokens.next()) {
+            Some(Ok(token)) => token,
+            Some(Err(error)) => {
+                let span = Span {
+                    start: error.location,
+                    end: error.location,
+                };
+                errors.push(ParseError::new(Found::Invalid(error), span, None, None));
+                continue;
             }
+            None => break None,
         };
-        RuleState {
-            rule,
-            parent: None,
-            self_node: Rc::new(ParseNode {
-                node_type: ParseNodeType::NonTerminal(NonTerminalType::Start),
-                children: Vec::new(),
-            }),
+        let token = table.classify(token);
+        let first = skipped == 0;
+        let nested = braces > 0 || parens > 0;
+        let for_header = parens > 0 && in_for;
+
+        let stop = match token.item {
+            LexItem::RightCurlyBrace if braces == 0 && !first => break Some(token),
+            LexItem::RightCurlyBrace if braces == 1 && parens == 0 => true,
+            LexItem::Semicolon if braces == 0 && !for_header => true,
+            ref item if !nested && !first && starts_construct(item, point) => break Some(token),
+            LexItem::LeftCurlyBrace => {
+                braces += 1;
+                false
+            }
+            LexItem::RightCurlyBrace => {
+                braces = braces.saturating_sub(1);
+                false
+            }
+            LexItem::LeftParen => {
+                parens += 1;
+                false
+            }
+            LexItem::RightParen => {
+                parens = parens.saturating_sub(1);
+                false
+            }
+            _ => false,
+        };
+        table.observe(&token);
+        forest.terminal(token);
+        skipped += 1;
+        if stop {
+            break None;
         }
+    };
+
+    let end = start + skipped;
+    let error = forest.error(point, start, end);
+    let target = frontier.table.states[point_node.state].gotos[&point];
+    let node = GssNode::new(target, end);
+    node.links.borrow_mut().push(Link {
+        to: point_node,
+        node: error,
+    });
+    Some((Frontier::new(frontier.table, node), lookahead))
+}
+
+/// Builds the error for the point where none of the stacks in `frontier` can
+/// continue, once every reduction has been done
+fn unexpected(
+    frontier: &Frontier,
+    goal: NonTerminalType,
+    bottom: &Rc<GssNode>,
+    found: Found,
+    span: Span,
+) -> ParseError {
+    let expected = frontier.expected(goal, bottom);
+    ParseError::new(found, span, expected, frontier.context())
+}
+
+/// The tree of a complete parse, taking the first derivation of any ambiguous
+/// input, and the error reporting every derivation of it
+fn tree(forest: &Forest, table: &Table, root: usize) -> (Rc<ParseNode>, Option<ParseError>) {
+    let error = match forest.is_ambiguous(root) {
+        true => AmbiguityReport::new(forest, table.rules, root).map(|report| {
+            let (span, context) = (report.span, Some(report.symbol));
+            ParseError::new(Found::Ambiguity(Box::new(report)), span, None, context)
+        }),
+        false => None,
+    };
+    (forest.tree(root), error)
+}
+
+/// Parses a whole translation unit, failing with the first syntax error
+#[allow(dead_code)]
+pub fn parse<T: Iterator<Item = LexResult>>(tokens: T) -> Result<Rc<ParseNode>, ParseError> {
+    parse_with(tokens, Standard::default())
+}
+
+/// Parses a whole translation unit in the grammar of `standard`, failing with
+/// the first syntax error
+#[allow(dead_code)]
+pub fn parse_with<T: Iterator<Item = LexResult>>(
+    tokens: T,
+    standard: Standard,
+) -> Result<Rc<ParseNode>, ParseError> {
+    let (tree, mut errors) = parse_recovering_with(tokens, standard);
+    if errors.is_empty() {
+        Ok(tree)
+    } else {
+        Err(errors.swap_remove(0))
     }
-    fn match_token(
-        self,
-        token: &LexSuccess,
-        rules: &[(NonTerminalType, &'a [RuleType])],
-    ) -> Result<RuleState<'a>, Option<Discriminant<LexItem>>> {
-        let index = self.self_node.children.len();
-        if self.rule.len() == index {
-            return Err(None);
+}
+
+/// Parses a whole translation unit, reporting every syntax error. Each statement
+/// or top-level declaration containing an error becomes a node with a single
+/// `ParseNodeType::Error` child holding its tokens.
+///
+/// This is a generalised LR parser: it follows every action of its `Table`, so
+/// local ambiguities are parsed side by side on a graph-structured stack and
+/// their derivations are shared in a `Forest`.
+#[allow(dead_code)]
+pub fn parse_recovering<T: Iterator<Item = LexResult>>(
+    tokens: T,
+) -> (Rc<ParseNode>, Vec<ParseError>) {
+    parse_recovering_with(tokens, Standard::default())
+}
+
+/// Parses a whole translation unit in the grammar of `standard`, reporting
+/// every syntax error like `parse_recovering`
+pub fn parse_recovering_with<T: Iterator<Item = LexResult>>(
+    tokens: T,
+    standard: Standard,
+) -> (Rc<ParseNode>, Vec<ParseError>) {
+    let (tree, errors, _) = parse_after(tokens, standard, TypedefTable::new());
+    (tree, errors)
+}
+
+/// Parses top-level declarations which follow others that declared the
+/// typedef names in `table`, like `parse_recovering_with`. Also returns the
+/// typedef names known at the end.
+pub(super) fn parse_after<T: Iterator<Item = LexResult>>(
+    tokens: T,
+    standard: Standard,
+    typedefs: TypedefTable,
+) -> (Rc<ParseNode>, Vec<ParseError>, TypedefTable) {
+    let mut top_level = TopLevel::after(tokens, standard, typedefs);
+    let mut statements = Vec::new();
+    let mut errors = Vec::new();
+    for parsed in &mut top_level {
+        match parsed {
+            Ok(statement) => statements.push(statement),
+            Err(error) => errors.push(error),
         }
-        let next_rule = &self.rule[index];
-        if let RuleType::Terminal(item) = next_rule {
-            if discriminant(&token.item) == *item {
-                let mut state = self;
-                let node = Rc::make_mut(&mut state.self_node);
-                node.children.push(Rc::new(ParseNode {
-                    node_type: ParseNodeType::Terminal(token.clone()),
-                    children: Vec::new(),
-                }));
-                Ok(state)
-            } else {
-                Err(Some(*item))
+    }
+    (translation_unit(statements), errors, top_level.table)
+}
+
+/// A translation unit of the given top-level declarations
+pub(super) fn translation_unit(statements: Vec<Rc<ParseNode>>) -> Rc<ParseNode> {
+    let non_terminal = |symbol, children| {
+        Rc::new(ParseNode {
+            node_type: ParseNodeType::NonTerminal(symbol),
+            children,
+        })
+    };
+    let empty = non_terminal(NonTerminalType::TopStatements, Vec::new());
+    let list = statements.into_iter().rev().fold(empty, |rest, statement| {
+        non_terminal(NonTerminalType::TopStatements, vec![statement, rest])
+    });
+    non_terminal(NonTerminalType::Start, vec![list])
+}
+
+/// Parses all of `tokens` as a single `non_terminal`, such as an expression or a
+/// statement on its own, failing with the first syntax error. Names are only
+/// typedef names if the fragment declares them.
+#[allow(dead_code)]
+pub fn parse_as<T: Iterator<Item = LexResult>>(
+    non_terminal: NonTerminalType,
+    tokens: T,
+) -> Result<Rc<ParseNode>, ParseError> {
+    parse_as_with(non_terminal, tokens, Standard::default())
+}
+
+/// Parses all of `tokens` as a single `non_terminal` in the grammar of
+/// `standard`, like `parse_as`
+pub fn parse_as_with<T: Iterator<Item = LexResult>>(
+    non_terminal: NonTerminalType,
+    tokens: T,
+    standard: Standard,
+) -> Result<Rc<ParseNode>, ParseError> {
+    parse_by(table(standard), non_terminal, tokens)
+}
+
+/// Parses all of `tokens` as a single `non_terminal` by the tables `grammar`,
+/// like `parse_as`
+pub(super) fn parse_by<T: Iterator<Item = LexResult>>(
+    grammar: &'static Table,
+    non_terminal: NonTerminalType,
+    tokens: T,
+) -> Result<Rc<ParseNode>, ParseError> {
+    let mut typedefs = TypedefTable::new();
+    let bottom = GssNode::new(grammar.entries[&non_terminal], 0);
+    let mut frontier = Frontier::new(grammar, bottom.clone());
+    let mut forest = Forest::default();
+    let mut end = Location { character: 0 };
+
+    for token in tokens {
+        let token = match token {
+            Ok(token) => token,
+            Err(error) => {
+                frontier.reduce(None, &mut forest, &mut Vec::new());
+                let span = Span {
+                    start: error.location,
+                    end: error.location,
+                };
+                let found = Found::Invalid(error);
+                return Err(unexpected(&frontier, non_terminal, &bottom, found, span));
             }
-        } else {
-            unreachable!();
+        };
+
+        let mut declarations = Vec::new();
+        frontier.reduce(
+            Some(&lookaheads(&token.item)),
+            &mut forest,
+            &mut declarations,
+        );
+        for declaration in declarations {
+            typedefs.declare(&forest.tree(declaration));
         }
-    }
-    fn move_forward(
-        self,
-        rules: &[(NonTerminalType, &'a [RuleType])],
-    ) -> impl IntoIterator<Item = RuleState<'a>> {
-        if let Some(next_rule) = self.rule.get(self.self_node.children.len()) {
-            match next_rule {
-                RuleType::Terminal(item) => vec![self],
-                RuleType::NonTerminal(needed) if !self.looped_token(*needed) => {
-                    let matched_rules = search_rule(rules, *needed);
-
-                    let self_rc = Rc::new(self);
-
-                    matched_rules
-                        .iter()
-                        .map(|rule| RuleState {
-                            rule,
-                            parent: Some(self_rc.clone()),
-                            self_node: Rc::new(ParseNode {
-                                node_type: ParseNodeType::NonTerminal(*needed),
-                                children: Vec::new(),
-                            }),
-                        })
-                        .map(|rule_state| rule_state.move_forward(rules))
-                        .flatten()
-                        .collect()
-                }
-                _ => Vec::new(),
+        let token = typedefs.classify(token);
+        match frontier.shift(&token, &mut forest) {
+            Some(next) => {
+                typedefs.observe(&token);
+                end = token.end;
+                frontier = next;
             }
-        } else {
-            let RuleState {
-                rule,
-                parent,
-                self_node,
-            } = self;
-
-            if let Some(parent) = parent {
-                let non_terminal = match parent.rule[parent.self_node.children.len()] {
-                    RuleType::Terminal(_) => unreachable!(),
-                    RuleType::NonTerminal(ref non_terminal) => non_terminal,
-                };
-                let recursions = LEFT_RECURSIONS.get(non_terminal).unwrap();
-                recursions
-                    .iter()
-                    .map(|recursion| {
-                        recursion.iter().fold(
-                            (*parent).clone(),
-                            |node, &(non_terminal, rule): &(NonTerminalType, &[RuleType])| {
-                                RuleState {
-                                    rule,
-                                    parent: Some(Rc::new(node)),
-                                    self_node: Rc::new(ParseNode {
-                                        node_type: ParseNodeType::NonTerminal(non_terminal),
-                                        children: vec![],
-                                    }),
-                                }
-                            },
-                        )
-                    })
-                    .chain(iter::once((*parent).clone()))
-                    .flat_map(|mut node| {
-                        let new_node = Rc::make_mut(&mut node.self_node);
-                        new_node.children.push(self_node.clone());
-                        node.move_forward(rules).into_iter()
-                    })
-                    .collect()
-            } else {
-                vec![RuleState {
-                    rule,
-                    parent: None,
-                    self_node,
-                }]
+            None => {
+                frontier.reduce(None, &mut forest, &mut Vec::new());
+                let span = token.span();
+                let found = Found::Token(token);
+                return Err(unexpected(&frontier, non_terminal, &bottom, found, span));
             }
         }
     }
-    fn looped_token(&self, token: NonTerminalType) -> bool {
-        if !self.self_node.children.is_empty() {
-            false
-        } else if let Some(ref parent) = self.parent {
-            parent.rule[parent.self_node.children.len()] == RuleType::NonTerminal(token)
-                || parent.as_ref().looped_token(token)
-        } else {
-            false
+
+    frontier.reduce(Some(&[Expected::EndOfFile]), &mut forest, &mut Vec::new());
+    match frontier.accepted(non_terminal, &bottom) {
+        Some(root) => match tree(&forest, grammar, root) {
+            (tree, None) => Ok(tree),
+            (_, Some(error)) => Err(error),
+        },
+        None => {
+            frontier.reduce(None, &mut forest, &mut Vec::new());
+            let span = Span { start: end, end };
+            let found = Found::EndOfFile;
+            Err(unexpected(&frontier, non_terminal, &bottom, found, span))
         }
     }
 }
 
-fn search_rule<'a>(
-    rules: &[(NonTerminalType, &'a [RuleType])],
-    request: NonTerminalType,
-) -> Vec<&'a [RuleType]> {
-    rules
-        .iter()
-        .filter(|rule| rule.0 == request)
-        .map(|rule| rule.1)
-        .collect()
+/// Parses the top-level declarations of a translation unit in the grammar of
+/// `standard` while they are read. See `TopLevel`.
+#[allow(dead_code)]
+pub fn parse_top_level<T: Iterator<Item = LexResult>>(
+    tokens: T,
+    standard: Standard,
+) -> TopLevel<T> {
+    TopLevel::after(tokens, standard, TypedefTable::new())
 }
 
-pub fn parse<T: Iterator<Item = LexResult>>(
-    mut tokens: T,
-) -> Result<Rc<ParseNode>, Vec<Option<Discriminant<LexItem>>>> {
-    let rules = &*super::rules::RULES;
+/// An iterator over the `TopStatement` subtrees of a translation unit, each
+/// yielded as soon as the token after it shows that it is finished. Syntax
+/// errors are yielded before the declaration they are in, which recovers
+/// like in `parse_recovering`, and collecting the declarations gives the same
+/// tree.
+///
+/// Only the declaration being parsed is kept, so a large file takes as much
+/// memory as its largest declaration. Typedef names are declared as each
+/// declaration goes past.
+pub struct TopLevel<T: Iterator<Item = LexResult>> {
+    tokens: T,
+    grammar: &'static Table,
+    table: TypedefTable,
+    /// The token or invalid token after the last declaration, if it was read
+    lookahead: Option<LexResult>,
+    /// Where the last token shifted ends
+    end: Location,
+    errors: Vec<ParseError>,
+    /// A declaration waiting for the errors in it to be yielded
+    finished: Option<Rc<ParseNode>>,
+    done: bool,
+}
 
-    let mut states: Vec<RuleState> = vec![RuleState::new_start(rules)];
+impl<T: Iterator<Item = LexResult>> TopLevel<T> {
+    fn after(tokens: T, standard: Standard, typedefs: TypedefTable) -> TopLevel<T> {
+        TopLevel {
+            tokens,
+            grammar: table(standard),
+            table: typedefs,
+            lookahead: None,
+            end: Location { character: 0 },
+            errors: Vec::new(),
+            finished: None,
+            done: false,
+        }
+    }
 
-    loop {
-        states = states
-            .into_iter()
-            .flat_map(|state| state.move_forward(rules))
-            .collect();
+    /// Whether `name` is a typedef name after the declarations so far
+    #[allow(dead_code)]
+    pub fn is_typedef_name(&self, name: &str) -> bool {
+        self.table.is_typedef_name(name)
+    }
 
-        let token = {
-            if let Some(token) = tokens.next() {
-                token.ok().unwrap()
-            } else {
-                break;
+    fn declare(&mut self, forest: &Forest, declarations: Vec<usize>) {
+        for declaration in declarations {
+            self.table.declare(&forest.tree(declaration));
+        }
+    }
+
+    /// Parses the next declaration, or returns `None` at the end of the file
+    fn statement(&mut self) -> Option<Rc<ParseNode>> {
+        let goal = NonTerminalType::TopStatement;
+        let bottom = GssNode::new(self.grammar.entries[&goal], 0);
+        let mut frontier = Frontier::new(self.grammar, bottom.clone());
+        let mut forest = Forest::default();
+
+        let root = loop {
+            let token = match self.lookahead.take().or_else(|| self.tokens.next()) {
+                Some(Ok(token)) => token,
+                Some(Err(error)) => {
+                    let span = Span {
+                        start: error.location,
+                        end: error.location,
+                    };
+                    let mut declarations = Vec::new();
+                    frontier.reduce(None, &mut forest, &mut declarations);
+                    if let Some(root) = frontier.accepted(goal, &bottom) {
+                        self.declare(&forest, declarations);
+                        self.lookahead = Some(Err(error));
+                        break Some(root);
+                    }
+                    let error = unexpected(&frontier, goal, &bottom, Found::Invalid(error), span);
+                    self.errors.push(error);
+                    continue;
+                }
+                None => break None,
+            };
+
+            let mut declarations = Vec::new();
+            frontier.reduce(
+                Some(&lookaheads(&token.item)),
+                &mut forest,
+                &mut declarations,
+            );
+            self.declare(&forest, declarations);
+            let token = self.table.classify(token);
+
+            match frontier.shift(&token, &mut forest) {
+                Some(next) => {
+                    self.table.observe(&token);
+                    self.end = token.end;
+                    frontier = next;
+                }
+                None => {
+                    // Declarations which the token cannot follow are finished
+                    // all the same
+                    let mut declarations = Vec::new();
+                    frontier.reduce(None, &mut forest, &mut declarations);
+                    self.declare(&forest, declarations);
+                    if let Some(root) = frontier.accepted(goal, &bottom) {
+                        self.lookahead = Some(Ok(token));
+                        break Some(root);
+                    }
+                    self.errors.push(unexpected(
+                        &frontier,
+                        goal,
+                        &bottom,
+                        Found::Token(token.clone()),
+                        token.span(),
+                    ));
+                    let (next, token) = recover(
+                        &frontier,
+                        Some(token),
+                        &mut self.tokens,
+                        &mut self.errors,
+                        &mut forest,
+                        &mut self.table,
+                    )
+                    .expect("Every token is inside a top-level statement");
+                    frontier = next;
+                    self.lookahead = token.map(Ok);
+                }
             }
         };
 
-        let state_results: (Vec<_>, Vec<_>) = states
-            .into_iter()
-            .map(|state| state.match_token(&token, rules))
-            .partition(Result::is_ok);
-        states = state_results.0.into_iter().map(Result::unwrap).collect();
-
-        if states.is_empty() {
-            let expected: Vec<Option<Discriminant<LexItem>>> = state_results
-                .1
-                .into_iter()
-                .map(Result::unwrap_err)
-                .collect();
-            return Err(expected);
-        }
-    }
+        let root = match root {
+            Some(root) => root,
+            None if frontier.position == 0 => return None,
+            None => {
+                // The last declaration is only finished by the end of the file
+                let end_of_file = [Expected::EndOfFile];
+                let mut declarations = Vec::new();
+                frontier.reduce(Some(&end_of_file), &mut forest, &mut declarations);
+                self.declare(&forest, declarations);
+                match frontier.accepted(goal, &bottom) {
+                    Some(root) => root,
+                    None => {
+                        frontier.reduce(None, &mut forest, &mut Vec::new());
+                        let span = Span {
+                            start: self.end,
+                            end: self.end,
+                        };
+                        let error = unexpected(&frontier, goal, &bottom, Found::EndOfFile, span);
+                        self.errors.push(error);
+                        let (next, _) = recover(
+                            &frontier,
+                            None,
+                            &mut self.tokens,
+                            &mut self.errors,
+                            &mut forest,
+                            &mut self.table,
+                        )
+                        .expect("Every token is inside a top-level statement");
+                        frontier = next;
+                        frontier.reduce(Some(&end_of_file), &mut forest, &mut Vec::new());
+                        frontier
+                            .accepted(goal, &bottom)
+                            .expect("Recovery finishes the declaration")
+                    }
+                }
+            }
+        };
 
-    states = states
-        .into_iter()
-        .flat_map(|state| state.move_forward(rules))
-        .collect();
+        let (tree, error) = tree(&forest, self.grammar, root);
+        self.errors.extend(error);
+        Some(tree)
+    }
+}
 
-    states = states
-        .into_iter()
-        .filter(|state| state.self_node.children.len() == state.rule.len())
-        .collect();
+impl<T: Iterator<Item = LexResult>> Iterator for TopLevel<T> {
+    type Item = Result<Rc<ParseNode>, ParseError>;
 
-    if states.len() > 1 {
-        dbg!(states);
-        unimplemented!("Ambiguous parsing")
-    } else if let Some(state) = states.into_iter().next() {
-        if state.parent.is_some() {
-            unimplemented!()
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if !self.errors.is_empty() {
+                return Some(Err(self.errors.remove(0)));
+            }
+            if let Some(statement) = self.finished.take() {
+                return Some(Ok(statement));
+            }
+            if self.done {
+                return None;
+            }
+            match self.statement() {
+                Some(statement) => self.finished = Some(statement),
+                None => self.done = true,
+            }
         }
-        Ok(state.self_node)
-    } else {
-        unimplemented!()
     }
 }
@@ -0,0 +1,73 @@
+use crate::lex::types::LexItem;
+use crate::parse::errors::Expected;
+use crate::parse::types::RuleType;
+use std::collections::HashMap;
+use std::mem::Discriminant;
+
+/// How operators of the same precedence group
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Associativity {
+    /// `a - b - c` is `(a - b) - c`
+    Left,
+    /// `a = b = c` is `a = (b = c)`
+    Right,
+    /// `a < b < c` is an error
+    NonAssociative,
+}
+
+/// What is left of a conflict between shifting a token and reducing a rule
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum Resolution {
+    /// Neither has a precedence, so the generalised parser follows both
+    Both,
+    Shift,
+    Reduce,
+    Neither,
+}
+
+/// The precedence of operator tokens, which settles the shift/reduce conflicts
+/// of ambiguous rules like `E -> E "+" E` the way yacc does
+#[derive(Debug, Default)]
+pub struct Precedence {
+    /// The level of each token, higher binding tighter
+    tokens: HashMap<Discriminant<LexItem>, (usize, Associativity)>,
+}
+
+impl Precedence {
+    /// Precedence from levels of tokens, loosest first
+    pub fn new(levels: Vec<(Associativity, Vec<RuleType>)>) -> Precedence {
+        let mut tokens = HashMap::new();
+        for (level, (associativity, items)) in levels.into_iter().enumerate() {
+            for item in items {
+                if let RuleType::Terminal(item) = item {
+                    tokens.insert(item, (level, associativity));
+                }
+            }
+        }
+        Precedence { tokens }
+    }
+
+    /// The precedence of a rule is that of its last token which has one
+    fn of_rule(&self, rhs: &[RuleType]) -> Option<(usize, Associativity)> {
+        rhs.iter().rev().find_map(|symbol| match symbol {
+            RuleType::Terminal(item) => self.tokens.get(item).cloned(),
+            RuleType::NonTerminal(_) => None,
+        })
+    }
+
+    /// Which of shifting `lookahead` and reducing by a rule with `rhs` to do
+    pub(super) fn resolve(&self, rhs: &[RuleType], lookahead: Expected) -> Resolution {
+        let token = match lookahead {
+            Expected::Token(item) => self.tokens.get(&item),
+            _ => None,
+        };
+        match (self.of_rule(rhs), token) {
+            (Some((rule, _)), Some((token, _))) if rule > *token => Resolution::Reduce,
+            (Some((rule, _)), Some((token, _))) if rule < *token => Resolution::Shift,
+            (Some(_), Some((_, Associativity::Left))) => Resolution::Reduce,
+            (Some(_), Some((_, Associativity::Right))) => Resolution::Shift,
+            (Some(_), Some((_, Associativity::NonAssociative))) => Resolution::Neither,
+            _ => Resolution::Both,
+        }
+    }
+}
@@ -0,0 +1,47 @@
+int printf(char *format, int value);
+
+int every_statement(int n) {
+    int total = 0;
+    ;
+    typedef int counter;
+    counter i;
+
+    for (i = 0; i < n; i++) total += i;
+    for (int j = 0; j < n; j++) {
+        if (j == 3) continue;
+        if (j == 5) break;
+    }
+    for (;;) break;
+    for (i = 0;; i++) {
+        if (i > n) break;
+    }
+
+    while (total > 100) total /= 2;
+    do {
+        total--;
+    } while (total > 50);
+
+    switch (n) {
+    case 0:
+        return 0;
+    case 1:
+    case 2 + 1:
+        total++;
+        break;
+    default:
+        break;
+    }
+
+    if (total < 0)
+        goto fail;
+    else
+        printf("%d\n", total);
+    return total;
+
+fail:
+    return -1;
+}
+
+void nothing(void) {
+    return;
+}
@@ -0,0 +1,267 @@
+use crate::lex::errors::LexSuccess;
+use crate::lex::types::LexItem;
+use crate::parse::types::{NonTerminalType, ParseNode, ParseNodeType};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// Tracks which identifiers are typedef names in each scope.
+///
+/// C cannot be parsed without knowing this: `T * x;` declares a pointer if `T`
+/// names a type and multiplies otherwise. The parser reports each declaration
+/// it finishes and asks the table to reclassify every identifier it reads,
+/// which is the classic "lexer hack".
+#[derive(Clone, Debug)]
+pub struct TypedefTable {
+    /// Every name declared in a scope, mapped to whether it is a typedef name.
+    /// Ordinary declarations are kept because they hide outer typedefs.
+    scopes: Vec<HashMap<String, bool>>,
+    /// Parameters of the last function declarator, which belong to the scope
+    /// of the function body if one follows
+    parameters: Vec<String>,
+    /// Whether the last tokens were `struct`, `union` or `enum` and maybe a tag
+    tag_head: bool,
+    /// Whether the last token was `for`
+    for_head: bool,
+    /// How many scopes were open before each `for` loop being read, whose
+    /// header opens a scope that only ends after the loop body
+    loops: Vec<usize>,
+    /// How many scopes were open before each open brace which opened one. The
+    /// braces of a struct, union or enum do not: enumerators belong to the
+    /// enclosing scope.
+    braces: Vec<Option<usize>>,
+}
+
+impl TypedefTable {
+    pub fn new() -> TypedefTable {
+        TypedefTable {
+            scopes: vec![HashMap::new()],
+            parameters: Vec::new(),
+            tag_head: false,
+            for_head: false,
+            loops: Vec::new(),
+            braces: Vec::new(),
+        }
+    }
+
+    pub fn is_typedef_name(&self, name: &str) -> bool {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+            .unwrap_or(false)
+    }
+
+    pub fn declare_typedef(&mut self, name: String) {
+        self.scopes.last_mut().unwrap().insert(name, true);
+    }
+
+    pub fn declare_ordinary(&mut self, name: String) {
+        self.scopes.last_mut().unwrap().insert(name, false);
+    }
+
+    /// Turns an identifier into a `TypedefName` if it names a type here
+    pub(super) fn classify(&self, token: LexSuccess) -> LexSuccess {
+        match token.item {
+            LexItem::Identifier(name) if self.is_typedef_name(&name) => LexSuccess {
+                item: LexItem::TypedefName(name),
+                ..token
+            },
+            _ => token,
+        }
+    }
+
+    /// Opens and closes scopes as the parser reads braces and `for` headers
+    pub(super) fn observe(&mut self, token: &LexSuccess) {
+        let for_head = std::mem::replace(&mut self.for_head, token.item == LexItem::For);
+        let tag_head = self.tag_head;
+        self.tag_head = match token.item {
+            LexItem::Struct | LexItem::Union | LexItem::Enum => true,
+            LexItem::Identifier(_) | LexItem::TypedefName(_) => tag_head,
+            _ => false,
+        };
+        match token.item {
+            LexItem::LeftCurlyBrace if tag_head => self.braces.push(None),
+            LexItem::LeftCurlyBrace => {
+                let parameters = std::mem::take(&mut self.parameters);
+                self.braces.push(Some(self.scopes.len()));
+                self.scopes
+                    .push(parameters.into_iter().map(|name| (name, false)).collect());
+            }
+            LexItem::LeftParen if for_head => {
+                self.loops.push(self.scopes.len());
+                self.scopes.push(HashMap::new());
+            }
+            LexItem::RightCurlyBrace => self.close_brace(),
+            LexItem::Semicolon => self.parameters.clear(),
+            _ => {}
+        }
+    }
+
+    /// Closes the scope of a brace, and of any loop in it which never ended
+    /// because of a syntax error
+    fn close_brace(&mut self) {
+        if let Some(Some(depth)) = self.braces.pop() {
+            self.scopes.truncate(depth);
+            self.loops.retain(|&outside| outside < depth);
+        }
+    }
+
+    /// Records the names declared by a node the parser just finished
+    pub(super) fn declare(&mut self, node: &ParseNode) {
+        match node.node_type {
+            ParseNodeType::NonTerminal(NonTerminalType::Typedef) => {
+                if let Some(declarators) = node.children.get(2) {
+                    for name in declared_names(declarators) {
+                        self.declare_typedef(name);
+                    }
+                }
+            }
+            ParseNodeType::NonTerminal(NonTerminalType::InitDeclarator) => {
+                if let Some(name) = node.children.first().and_then(declared_name) {
+                    self.declare_ordinary(name);
+                }
+            }
+            ParseNodeType::NonTerminal(NonTerminalType::ForLoop) => {
+                if let Some(depth) = self.loops.pop() {
+                    self.scopes.truncate(depth);
+                }
+            }
+            ParseNodeType::NonTerminal(NonTerminalType::Enumerator) => {
+                if let Some(name) = node.children.first().and_then(identifier) {
+                    self.declare_ordinary(name);
+                }
+            }
+            // Only the parameter list right after the declared name belongs to
+            // the function: in `void (*f(int a))(int b)` it is `a`, not `b`
+            ParseNodeType::NonTerminal(NonTerminalType::DirectDeclarator)
+            | ParseNodeType::NonTerminal(NonTerminalType::FunctionDeclarator) => {
+                let declarator = node.children.iter().find(|child| {
+                    child.node_type == ParseNodeType::NonTerminal(NonTerminalType::DirectDeclarator)
+                });
+                let named = declarator
+                    .and_then(|declarator| declarator.children.first())
+                    .and_then(identifier)
+                    .is_some();
+                let is_function = node.children.iter().any(|child| match &child.node_type {
+                    ParseNodeType::Terminal(token) => token.item == LexItem::LeftParen,
+                    _ => false,
+                });
+                if named && is_function {
+                    self.parameters = node
+                        .children
+                        .iter()
+                        .filter(|child| {
+                            child.node_type == ParseNodeType::NonTerminal(NonTerminalType::Args)
+                        })
+                        .flat_map(parameter_names)
+                        .collect();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Reads a finished parse tree as the parser would have: every token in
+    /// order, and each declaration once its last token has been read
+    pub(super) fn replay(&mut self, node: &ParseNode) {
+        match &node.node_type {
+            ParseNodeType::Terminal(token) => self.observe(token),
+            ParseNodeType::NonTerminal(symbol) => {
+                for child in &node.children {
+                    self.replay(child);
+                }
+                if DECLARING.contains(symbol) {
+                    self.declare(node);
+                }
+            }
+            ParseNodeType::Error => {
+                for child in &node.children {
+                    self.replay(child);
+                }
+            }
+        }
+    }
+
+    /// The typedef names of file scope, which is all that is left between
+    /// top-level declarations
+    pub(super) fn file_scope_typedefs(&self) -> HashSet<&str> {
+        self.scopes[0]
+            .iter()
+            .filter(|(_, &is_typedef)| is_typedef)
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+}
+
+/// The nonterminals `TypedefTable::declare` is interested in
+pub(super) const DECLARING: &[NonTerminalType] = &[
+    NonTerminalType::Typedef,
+    NonTerminalType::InitDeclarator,
+    NonTerminalType::Enumerator,
+    NonTerminalType::ForLoop,
+    NonTerminalType::DirectDeclarator,
+    NonTerminalType::FunctionDeclarator,
+];
+
+fn identifier(node: &Rc<ParseNode>) -> Option<String> {
+    match &node.node_type {
+        ParseNodeType::Terminal(LexSuccess {
+            item: LexItem::Identifier(name),
+            ..
+        })
+        | ParseNodeType::Terminal(LexSuccess {
+            item: LexItem::TypedefName(name),
+            ..
+        }) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+/// The identifiers declared by a list of `Declarators`
+fn declared_names(node: &Rc<ParseNode>) -> Vec<String> {
+    let mut names = match node.children.first() {
+        Some(first) if node.children.len() == 3 => declared_names(first),
+        _ => Vec::new(),
+    };
+    names.extend(node.children.last().and_then(declared_name));
+    names
+}
+
+/// The identifier declared by a `TypeWithIdentifier`, `TypeWithMaybeIdentifier`
+/// or any declarator
+fn declared_name(node: &Rc<ParseNode>) -> Option<String> {
+    match node.node_type {
+        ParseNodeType::NonTerminal(NonTerminalType::TypeWithIdentifier) => {
+            node.children.last().and_then(declared_name)
+        }
+        // A declarator may be followed by GNU attributes and asm labels
+        ParseNodeType::NonTerminal(NonTerminalType::Declarator) => {
+            node.children.iter().find_map(declared_name)
+        }
+        // A parameter may start with attributes
+        ParseNodeType::NonTerminal(NonTerminalType::TypeWithMaybeIdentifier) => {
+            node.children.last().and_then(declared_name)
+        }
+        ParseNodeType::NonTerminal(NonTerminalType::DirectDeclarator) => node
+            .children
+            .iter()
+            .find_map(|child| identifier(child).or_else(|| declared_name(child))),
+        _ => None,
+    }
+}
+
+/// The names of the parameters in `Args`, in order. Unnamed parameters and
+/// `...` have none.
+fn parameter_names(node: &Rc<ParseNode>) -> Vec<String> {
+    let mut names: Vec<String> = node
+        .children
+        .first()
+        .and_then(declared_name)
+        .into_iter()
+        .collect();
+    if let Some(rest) = node.children.get(2) {
+        names.extend(parameter_names(rest));
+    }
+    names
+}
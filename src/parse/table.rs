@@ -0,0 +1,204 @@
+use crate::lex::types::LexItem;
+use crate::parse::analysis::Analysis;
+use crate::parse::errors::Expected;
+use crate::parse::precedence::{Precedence, Resolution};
+use crate::parse::rules::{C89_RULES, GNU_RULES, PRECEDENCE, RULES};
+use crate::parse::types::{NonTerminalType, RuleType};
+use crate::standard::Standard;
+use lazy_static::lazy_static;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::mem::Discriminant;
+
+/// A rule of the grammar by index, with how much of it has been matched
+type Item = (usize, usize);
+
+/// One state of the LR(0) automaton
+#[derive(Debug)]
+pub(super) struct State {
+    /// Every item of the state, including the ones added by the closure
+    pub(super) items: Vec<Item>,
+    pub(super) shifts: HashMap<Discriminant<LexItem>, usize>,
+    pub(super) gotos: HashMap<NonTerminalType, usize>,
+    /// Rules which may be reduced before each lookahead
+    pub(super) reductions: HashMap<Expected, Vec<usize>>,
+}
+
+/// SLR(1) parse tables for a grammar. Conflicts that precedence does not settle
+/// are kept, the generalised parser follows every action.
+#[derive(Debug)]
+pub(super) struct Table {
+    pub(super) rules: &'static [(NonTerminalType, &'static [RuleType])],
+    pub(super) states: Vec<State>,
+    /// The state to start in for each nonterminal which can be parsed on its own
+    pub(super) entries: HashMap<NonTerminalType, usize>,
+}
+
+lazy_static! {
+    static ref TABLE: Table = Table::new(&RULES, &PRECEDENCE);
+    static ref GNU_TABLE: Table = Table::new(&GNU_RULES, &PRECEDENCE);
+    static ref C89_TABLE: Table = Table::new(&C89_RULES, &PRECEDENCE);
+}
+
+/// The tables for the grammar of `standard`
+pub(super) fn table(standard: Standard) -> &'static Table {
+    match standard {
+        Standard::C11 => &TABLE,
+        Standard::Gnu11 => &GNU_TABLE,
+        Standard::C89 => &C89_TABLE,
+    }
+}
+
+impl Table {
+    pub(super) fn new(
+        rules: &'static [(NonTerminalType, &'static [RuleType])],
+        precedence: &Precedence,
+    ) -> Table {
+        let analysis = Analysis::new(rules);
+        let mut by_lhs: HashMap<NonTerminalType, Vec<usize>> = HashMap::new();
+        for (index, (lhs, _)) in rules.iter().enumerate() {
+            by_lhs.entry(*lhs).or_default().push(index);
+        }
+
+        let closure = |kernel: &[Item]| {
+            let mut items = kernel.to_vec();
+            let mut seen: HashSet<Item> = items.iter().cloned().collect();
+            let mut index = 0;
+            while index < items.len() {
+                let (rule, dot) = items[index];
+                index += 1;
+                if let Some(RuleType::NonTerminal(next)) = rules[rule].1.get(dot) {
+                    for &rule in by_lhs.get(next).into_iter().flatten() {
+                        if seen.insert((rule, 0)) {
+                            items.push((rule, 0));
+                        }
+                    }
+                }
+            }
+            items
+        };
+
+        let mut kernels: HashMap<Vec<Item>, usize> = HashMap::new();
+        let mut states: Vec<State> = Vec::new();
+        let mut queue: VecDeque<(usize, Vec<Item>)> = VecDeque::new();
+        let add_state = |kernel: Vec<Item>,
+                         kernels: &mut HashMap<Vec<Item>, usize>,
+                         states: &mut Vec<State>,
+                         queue: &mut VecDeque<(usize, Vec<Item>)>| {
+            if let Some(&state) = kernels.get(&kernel) {
+                return state;
+            }
+            let state = states.len();
+            states.push(State {
+                items: Vec::new(),
+                shifts: HashMap::new(),
+                gotos: HashMap::new(),
+                reductions: HashMap::new(),
+            });
+            kernels.insert(kernel.clone(), state);
+            queue.push_back((state, kernel));
+            state
+        };
+
+        let mut entries = HashMap::new();
+        for (lhs, rules) in &by_lhs {
+            let kernel = rules.iter().map(|&rule| (rule, 0)).collect();
+            let state = add_state(kernel, &mut kernels, &mut states, &mut queue);
+            entries.insert(*lhs, state);
+        }
+
+        while let Some((state, kernel)) = queue.pop_front() {
+            let items = closure(&kernel);
+
+            let mut transitions: HashMap<RuleType, Vec<Item>> = HashMap::new();
+            let mut finished = Vec::new();
+            for &(rule, dot) in &items {
+                match rules[rule].1.get(dot) {
+                    Some(symbol) => transitions
+                        .entry(*symbol)
+                        .or_default()
+                        .push((rule, dot + 1)),
+                    None => finished.push(rule),
+                }
+            }
+            for rule in finished {
+                let (lhs, rhs) = rules[rule];
+                for &lookahead in &analysis.follow[&lhs] {
+                    let shift = match lookahead {
+                        Expected::Token(item) => Some(RuleType::Terminal(item)),
+                        Expected::EndOfFile => None,
+                    }
+                    .filter(|shift| transitions.contains_key(shift));
+                    let resolution = match shift {
+                        Some(shift) => match precedence.resolve(rhs, lookahead) {
+                            resolution @ (Resolution::Reduce | Resolution::Neither) => {
+                                transitions.remove(&shift);
+                                resolution
+                            }
+                            resolution => resolution,
+                        },
+                        None => Resolution::Reduce,
+                    };
+                    if let Resolution::Both | Resolution::Reduce = resolution {
+                        states[state]
+                            .reductions
+                            .entry(lookahead)
+                            .or_default()
+                            .push(rule);
+                    }
+                }
+            }
+            for (symbol, mut kernel) in transitions {
+                kernel.sort_unstable();
+                kernel.dedup();
+                let target = add_state(kernel, &mut kernels, &mut states, &mut queue);
+                match symbol {
+                    RuleType::Terminal(item) => states[state].shifts.insert(item, target),
+                    RuleType::NonTerminal(symbol) => states[state].gotos.insert(symbol, target),
+                };
+            }
+            states[state].items = items;
+        }
+
+        // A finished parse of a nonterminal which does not recur on its left ends
+        // in a state of its own
+        let accept = states.len();
+        states.push(State {
+            items: Vec::new(),
+            shifts: HashMap::new(),
+            gotos: HashMap::new(),
+            reductions: HashMap::new(),
+        });
+        for (lhs, entry) in &entries {
+            states[*entry].gotos.entry(*lhs).or_insert(accept);
+        }
+
+        Table {
+            rules,
+            states,
+            entries,
+        }
+    }
+
+    /// The rules to reduce in `state` before any of `lookaheads`, or before
+    /// any token at all if there are no lookaheads
+    pub(super) fn reductions(&self, state: usize, lookaheads: Option<&[Expected]>) -> Vec<usize> {
+        let reductions = &self.states[state].reductions;
+        let mut rules: Vec<usize> = match lookaheads {
+            Some(lookaheads) => lookaheads
+                .iter()
+                .flat_map(|lookahead| reductions.get(lookahead).into_iter().flatten())
+                .cloned()
+                .collect(),
+            None => reductions.values().flatten().cloned().collect(),
+        };
+        rules.sort_unstable();
+        rules.dedup();
+        rules
+    }
+
+    /// The state that a parse of `non_terminal` from its entry state ends in
+    pub(super) fn accepting(&self, non_terminal: NonTerminalType) -> Option<usize> {
+        let entry = self.entries.get(&non_terminal)?;
+        self.states[*entry].gotos.get(&non_terminal).cloned()
+    }
+}
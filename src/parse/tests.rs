@@ -1,10 +1,29 @@
-use crate::lex::errors::LexResult;
+use crate::cpp::conditional::PresenceCondition;
+use crate::cpp::preprocessor::Preprocessor;
+use crate::lex::errors::{LexResult, Location, Span};
+use crate::lex::types::LexItem;
 use crate::lex::Lexer;
-use crate::parse::parser::parse;
+use crate::parse::analysis::{Analysis, Conflict};
+use crate::parse::cst::{self, AstNode, SyntaxNode, KINDS};
+use crate::parse::dump::{dump, to_dot, to_indented, to_s_expression, TreeFormat};
+use crate::parse::errors::{token_name, Expected, Found};
+use crate::parse::incremental::{Edit, Parsed};
+use crate::parse::parser::{
+    parse, parse_as, parse_by, parse_recovering, parse_top_level, parse_with, translation_unit,
+};
+use crate::parse::precedence::{Associativity, Precedence, Resolution};
+use crate::parse::rules::{C89_RULES, GNU_RULES, PRECEDENCE, RULES};
+use crate::parse::table::Table;
 use crate::parse::types::NonTerminalType;
 use crate::parse::types::ParseNode;
 use crate::parse::types::ParseNodeType;
+use crate::parse::types::RuleType;
+use crate::parse::variability::{parse_variability, Variant};
+use crate::standard::Standard;
+use crate::untyped_ast::build_untyped_ast;
+use std::cell::Cell;
 use std::io::Write;
+use std::mem::discriminant;
 use std::process::Command;
 use std::process::Stdio;
 use std::rc::Rc;
@@ -43,6 +62,12 @@ fn test_parse_typedef() {
     let _parse = parse(Lexer::new(text.chars())).unwrap();
 }
 
+#[test]
+fn test_parse_struct() {
+    let text = "struct point { int x; int y : 3; } origin;\nstruct { } empty;";
+    let _parse = parse(Lexer::new(text.chars())).unwrap();
+}
+
 #[test]
 fn test_parse_gcd() {
     let text = include_str!("gcd.c");
@@ -55,11 +80,245 @@ fn test_parse_pointers() {
     let _parse = parse(Lexer::new(text.chars())).unwrap();
 }
 
+#[test]
+fn test_parse_declarators() {
+    let text = "int a[10];\n\
+                int (*cb)(int, void *);\n\
+                int main(int argc, char *argv[]);\n\
+                void (*signal(int, void (*)(int)))(int);\n\
+                int *(*table[4][2])(char **, int (*)[3]);\n\
+                void (*handler(int sig))(int) {\n\
+                    return (void (*)(int))(sizeof(int *[4]) + sizeof(char (*)(void)));\n\
+                }\n";
+    let _parse = parse(Lexer::new(text.chars())).unwrap();
+}
+
+#[test]
+fn test_parse_initializers() {
+    let text = "int a = 1;\n\
+                int b[3] = { 1, 2, 3, };\n\
+                int c[2][2] = { { 1, 2 }, { 3 } };\n\
+                int d[10] = { [0] = 1, [2] = 2, 7 };\n\
+                struct point { int x; int y; } e = { .y = 2, .x = 1 };\n\
+                struct line { int from; int to[2]; } f = { .to[1] = 1, .from = 2 };\n\
+                int main() {\n\
+                    int *p = (int[2]){ [1] = 1 };\n\
+                    return (int[]){ 1, 2, }[1] + (struct { int x; int y; }){ .x = 1 }.x;\n\
+                }\n";
+    let _parse = parse(Lexer::new(text.chars())).unwrap();
+}
+
+#[test]
+fn test_parse_declarator_lists() {
+    let text = "int a, *b = 0, c[3];\n\
+                typedef struct node node, *link;\n\
+                struct node;\n\
+                struct node *head;\n\
+                struct node { int value; link next; } first, *last;\n\
+                union value { int i; float f; struct { char a, b; }; };\n\
+                struct flags { unsigned ready : 1, : 3, mode : 2 + 2; };\n\
+                int main() {\n\
+                    node n, *p = &n;\n\
+                    for (int i = 0, j = i; i < j; i++) {}\n\
+                }\n";
+    let _parse = parse(Lexer::new(text.chars())).unwrap();
+}
+
+#[test]
+fn test_parse_c11_keywords() {
+    let text = "_Static_assert(sizeof(int) == 4, \"int is 32 bits\");\n\
+                struct s { _Alignas(16) char c; _Static_assert(1, \"member\"); };\n\
+                _Alignas(double) char buffer[8];\n\
+                _Alignas(2 * 8) int aligned;\n\
+                int main() {\n\
+                    _Static_assert(_Alignof(struct s) == 16, \"block\");\n\
+                    return _Generic(1 + 1, int: 1, double: 2, char *: 3, default: 0);\n\
+                }\n";
+    let _parse = parse(Lexer::new(text.chars())).unwrap();
+}
+
+#[test]
+fn test_parse_variadic() {
+    let text = "int printf(const char *format, ...);\nint (*log)(int, char *, ...);";
+    let _parse = parse(Lexer::new(text.chars())).unwrap();
+}
+
+fn parse_gnu(text: &str) -> Result<Rc<ParseNode>, crate::parse::errors::ParseError> {
+    parse_with(
+        Lexer::with_standard(text.chars(), Standard::Gnu11),
+        Standard::Gnu11,
+    )
+}
+
+#[test]
+fn test_parse_gnu_extensions() {
+    let text = "typedef __builtin_va_list va_list;\n\
+                __extension__ typedef unsigned __int128 u128;\n\
+                extern int printf(const char *__restrict format, ...)\n\
+                    __attribute__((__nonnull__(1), format(printf, 1, 2)));\n\
+                extern int scanf(const char *__restrict, ...) __asm__(\"\" \"__isoc99_scanf\");\n\
+                __attribute__((noreturn)) void exit(int);\n\
+                struct packed { char c; int i __attribute__((aligned(8))); } __attribute__((packed));\n\
+                static __inline __typeof__(int *) first(int *__restrict p, int n __attribute__((unused))) {\n\
+                    __typeof__(p) q = p;\n\
+                    __extension__ long long total = ({ int x = *q; x + 1; });\n\
+                    return __extension__ ({ q; });\n\
+                }\n\
+                int d[10] = { [0] = 1, [2 ... 5] = 2, 7 };\n\
+                int __attribute__((const)) twice(int) __attribute__(());\n";
+    let _parse = parse_gnu(text).unwrap();
+}
+
+#[test]
+fn test_gnu_extensions_need_gnu_mode() {
+    let _parse = parse(Lexer::new("int d[10] = { [2 ... 5] = 2 };".chars())).unwrap_err();
+    let _parse = parse(Lexer::new("int main() { return ({ 1; }); }".chars())).unwrap_err();
+    let _parse = parse(Lexer::new("__int128 x;".chars())).unwrap_err();
+}
+
+fn parse_c89(text: &str) -> Result<Rc<ParseNode>, crate::parse::errors::ParseError> {
+    parse_with(
+        Lexer::with_standard(text.chars(), Standard::C89),
+        Standard::C89,
+    )
+}
+
+#[test]
+fn test_parse_c89_names_of_later_keywords() {
+    let text = "int restrict = 1;\n\
+                int inline = 1;\n\
+                static __inline int f(char *__restrict p) { return restrict + inline; }\n";
+    let _parse = parse_c89(text).unwrap();
+    let _parse = parse(Lexer::new("int restrict = 1;".chars())).unwrap_err();
+}
+
+#[test]
+fn test_parse_c89_old_style_definitions() {
+    let text = "static count;\n\
+                int f(a, b)\n\
+                    int a;\n\
+                    char *b, c;\n\
+                {\n\
+                    register i;\n\
+                    return a + b[i];\n\
+                }\n\
+                *g(x) { return 0; }\n\
+                main() { return f(1, \"x\"); }\n";
+    let tree = parse_c89(text).unwrap();
+    let root = cst::Start::cast(SyntaxNode::new_root(tree)).unwrap();
+    let function = |start: &str| {
+        root.top_statements()
+            .statements()
+            .into_iter()
+            .filter_map(|statement| statement.function())
+            .find(|function| function.syntax().text(text).starts_with(start))
+            .unwrap()
+    };
+    let f = function("int f");
+    assert!(f.declarator().is_none());
+    assert!(f.old_style_declarator().is_some());
+    assert_eq!(f.parameter_declarations().len(), 2);
+    let g = function("*g");
+    assert!(g.specifiers().is_none());
+    assert!(g.old_style_declarator().unwrap().pointer().is_some());
+    let main = function("main");
+    assert!(main.declarator().unwrap().args().is_none());
+
+    assert_eq!("c89".parse(), Ok(Standard::C89));
+    assert_eq!(Standard::C89.to_string(), "c89");
+    for text in ["static count;", "int f(a) int a; { }", "main() { }"] {
+        let _parse = parse(Lexer::new(text.chars())).unwrap_err();
+    }
+}
+
+#[test]
+fn test_parse_standard_attributes() {
+    let text = "[[nodiscard, gnu::warn_unused_result]] int f(void);\n\
+                [[deprecated(\"use g\")]] [[maybe_unused]] static int old [[gnu::unused]], g;\n\
+                int h(int, [[maybe_unused]] int b) [[gnu::const]];\n\
+                [[noreturn]] void stop(void) { for (;;); }\n\
+                struct s { [[deprecated]] int a; int b [[gnu::aligned(8)]]; };\n\
+                enum e { A [[deprecated]], B [[maybe_unused]] [[]] = 2 };\n\
+                [[]];\n\
+                int main(void) {\n\
+                    [[maybe_unused]] int x = 1;\n\
+                    switch (x) {\n\
+                    case 1:\n\
+                        x++;\n\
+                        [[fallthrough]];\n\
+                    case 2:\n\
+                        [[gnu::hot]] x--;\n\
+                    }\n\
+                    return 0;\n\
+                }\n";
+    let _parse = parse(Lexer::new(text.chars())).unwrap();
+}
+
+#[test]
+fn test_parse_attributes_after_specifiers() {
+    let text = "int [[gnu::aligned(8)]] x;\n\
+                typedef int T;\n\
+                const T [[gnu::unused]] t;\n\
+                int * [[gnu::unused]] const p, ** [[gnu::unused]] [[]] q;\n\
+                struct [[gnu::packed]] s { char c; int i; };\n\
+                union [[gnu::packed]] { int i; } u;\n";
+    let _parse = parse(Lexer::new(text.chars())).unwrap();
+}
+
+#[test]
+fn test_attribute_before_parameter_declares_it() {
+    let text = "typedef int T;\n\
+                void f([[maybe_unused]] int T) { T * 2; }";
+    let _parse = parse(Lexer::new(text.chars())).unwrap();
+}
+
+#[test]
+#[ignore]
+fn test_parse_stdio() {
+    let mut gcc = Command::new("gcc")
+        .args(["-E", "-P", "-std=gnu11", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    gcc.stdin
+        .as_mut()
+        .unwrap()
+        .write_all(b"#include <stdio.h>\n")
+        .unwrap();
+    let output = gcc.wait_with_output().unwrap().stdout;
+    let text = String::from_utf8(output).unwrap();
+
+    let (_, errors) = build_untyped_ast(parse_gnu(&text).unwrap());
+    assert_eq!(errors, vec![]);
+}
+
+#[test]
+fn test_declarator_list_declares_each_name() {
+    // Each declarator shadows `a` and `b` as soon as it is complete
+    let text = "typedef int a;\ntypedef int b;\n\
+                int main() {\n    int a = 1, b = a * 2;\n    b * 2;\n}\n";
+    assert_eq!(
+        parse_statements(text),
+        vec![NonTerminalType::Declaration, NonTerminalType::Expression]
+    );
+}
+
+#[test]
+fn test_enumerator_in_struct_is_in_enclosing_scope() {
+    let text = "typedef int A;\n\
+                int main() {\n    struct s { enum { A } e; } x;\n    A * 2;\n}\n";
+    assert_eq!(
+        parse_statements(text),
+        vec![NonTerminalType::Declaration, NonTerminalType::Expression]
+    );
+}
+
 #[test]
 #[ignore]
 fn test_n_body() {
     let mut gcc = Command::new("gcc")
-        .args(&["-E", "-"])
+        .args(["-E", "-"])
         .stdin(Stdio::piped())
         .spawn()
         .unwrap();
@@ -86,3 +345,1077 @@ fn test_parse_0_invalid() {
     let text = "0";
     let _parse = parse(Lexer::new(text.chars())).unwrap_err();
 }
+
+#[test]
+fn test_parse_error_found_token() {
+    let text = "int main() {\n    return 0\n}\n";
+    let error = parse(Lexer::new(text.chars())).unwrap_err();
+    match &error.found {
+        Found::Token(token) => assert_eq!(token.item, LexItem::RightCurlyBrace),
+        found => panic!("Expected a token, found {:?}", found),
+    }
+    assert_eq!(error.span.start.line_column(text), (3, 1));
+    assert_eq!(error.span.end.character, error.span.start.character + 1);
+    assert_eq!(error.context, Some(NonTerminalType::Return));
+    assert!(error
+        .expected
+        .contains(&Expected::Token(discriminant(&LexItem::Semicolon))));
+}
+
+#[test]
+fn test_parse_error_message() {
+    let text = "int f() { for (int i = 0; i < 3 i++) ; }";
+    let error = parse(Lexer::new(text.chars())).unwrap_err();
+    assert_eq!(error.context, Some(NonTerminalType::ForLoop));
+    let message = error.to_string();
+    assert!(
+        message.starts_with("unexpected identifier `i` in for-loop header, expected one of "),
+        "{}",
+        message
+    );
+    assert!(message.contains("';'"), "{}", message);
+
+    let names: Vec<String> = error.expected.iter().map(Expected::to_string).collect();
+    let mut deduplicated = names.clone();
+    deduplicated.sort();
+    deduplicated.dedup();
+    assert_eq!(names, deduplicated);
+}
+
+#[test]
+fn test_parse_error_end_of_file() {
+    let text = "int main() {";
+    let error = parse(Lexer::new(text.chars())).unwrap_err();
+    assert_eq!(error.found, Found::EndOfFile);
+    assert_eq!(error.span.start.character, text.len());
+    assert!(error
+        .expected
+        .contains(&Expected::Token(discriminant(&LexItem::RightCurlyBrace))));
+}
+
+fn error_nodes(node: &Rc<ParseNode>) -> Vec<(NonTerminalType, usize)> {
+    node.children
+        .iter()
+        .flat_map(|child| match child.node_type {
+            ParseNodeType::NonTerminal(non_terminal)
+                if child.children.len() == 1
+                    && child.children[0].node_type == ParseNodeType::Error =>
+            {
+                vec![(non_terminal, child.children[0].children.len())]
+            }
+            _ => error_nodes(child),
+        })
+        .collect()
+}
+
+#[test]
+fn test_recover_missing_semicolons() {
+    let text = "int a = 1\nint b;\nint f() {\n    a = 2\n    b = 3;\n    return a\n}\nint c;\n";
+    let (tree, errors) = parse_recovering(Lexer::new(text.chars()));
+    let lines: Vec<usize> = errors
+        .iter()
+        .map(|error| error.span.start.line_column(text).0)
+        .collect();
+    assert_eq!(lines, vec![2, 5, 7]);
+    assert_eq!(
+        error_nodes(&tree),
+        vec![
+            (NonTerminalType::TopStatement, 4),
+            (NonTerminalType::Statement, 7),
+            (NonTerminalType::Statement, 2),
+        ]
+    );
+}
+
+#[test]
+fn test_recover_skips_to_semicolon() {
+    let text = "int f() {\n    g(1 2 3);\n    int x;\n    x = (;\n}\n";
+    let (tree, errors) = parse_recovering(Lexer::new(text.chars()));
+    assert_eq!(errors.len(), 2);
+    assert_eq!(
+        error_nodes(&tree),
+        vec![
+            (NonTerminalType::Statement, 7),
+            (NonTerminalType::Statement, 4),
+        ]
+    );
+}
+
+#[test]
+fn test_recover_stray_brace() {
+    let text = "int a;\n}\nint b;\n";
+    let (tree, errors) = parse_recovering(Lexer::new(text.chars()));
+    assert_eq!(errors.len(), 1);
+    assert_eq!(error_nodes(&tree), vec![(NonTerminalType::TopStatement, 1)]);
+}
+
+#[test]
+fn test_recover_end_of_file() {
+    let text = "int a;\nint f() {\n    return 0;\n";
+    let (tree, errors) = parse_recovering(Lexer::new(text.chars()));
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].found, Found::EndOfFile);
+    assert_eq!(error_nodes(&tree), vec![(NonTerminalType::TopStatement, 8)]);
+}
+
+#[test]
+fn test_recover_for_header() {
+    let text = "int f() {\n    for (i = 0; i < 3 i++) x = 1;\n    return 0;\n}\n";
+    let (tree, errors) = parse_recovering(Lexer::new(text.chars()));
+    assert_eq!(errors.len(), 1);
+    assert_eq!(error_nodes(&tree), vec![(NonTerminalType::Statement, 16)]);
+}
+
+/// What every statement consists of, in source order
+fn statement_kinds(node: &Rc<ParseNode>) -> Vec<NonTerminalType> {
+    let mut kinds = Vec::new();
+    let mut queue = vec![node.clone()];
+    while let Some(node) = queue.pop() {
+        if node.node_type == ParseNodeType::NonTerminal(NonTerminalType::Statement) {
+            if let ParseNodeType::NonTerminal(kind) = node.children[0].node_type {
+                kinds.push(kind);
+            }
+        }
+        queue.extend(node.children.iter().rev().cloned());
+    }
+    kinds
+}
+
+fn parse_statements(text: &str) -> Vec<NonTerminalType> {
+    statement_kinds(&parse(Lexer::new(text.chars())).unwrap())
+}
+
+#[test]
+fn test_typedef_pointer_declaration() {
+    let text = "typedef int T;\nint f() {\n    T * x;\n}\n";
+    assert_eq!(parse_statements(text), vec![NonTerminalType::Declaration]);
+}
+
+#[test]
+fn test_typedef_multiplication() {
+    let text = "int f(int a, int b) {\n    a * b;\n}\n";
+    assert_eq!(parse_statements(text), vec![NonTerminalType::Expression]);
+}
+
+#[test]
+fn test_typedef_cast() {
+    let cast = "typedef int T;\nint f(int x) {\n    (T)(x);\n}\n";
+    let call = "int f(int T, int x) {\n    (T)(x);\n}\n";
+    let find = |text: &str, wanted: NonTerminalType| {
+        let mut queue = vec![parse(Lexer::new(text.chars())).unwrap()];
+        let mut found = false;
+        while let Some(node) = queue.pop() {
+            found |=
+                node.node_type == ParseNodeType::NonTerminal(wanted) && node.children.len() == 4;
+            queue.extend(node.children.iter().cloned());
+        }
+        found
+    };
+    assert!(find(cast, NonTerminalType::Expression2_5));
+    assert!(!find(call, NonTerminalType::Expression2_5));
+    assert!(find(call, NonTerminalType::Expression1));
+}
+
+#[test]
+fn test_typedef_shadowed_in_block() {
+    let text =
+        "typedef int T;\nint f() {\n    {\n        int T;\n        T * 2;\n    }\n    T * y;\n}\n";
+    assert_eq!(
+        parse_statements(text),
+        vec![
+            NonTerminalType::Block,
+            NonTerminalType::Declaration,
+            NonTerminalType::Expression,
+            NonTerminalType::Declaration,
+        ]
+    );
+}
+
+#[test]
+fn test_typedef_shadowed_by_parameter() {
+    let text = "typedef int T;\nint f(int T) {\n    T * 2;\n}\nT g() {\n    T * y;\n}\n";
+    assert_eq!(
+        parse_statements(text),
+        vec![NonTerminalType::Expression, NonTerminalType::Declaration]
+    );
+}
+
+#[test]
+fn test_typedef_shadowed_by_nested_parameter() {
+    // Only `a` belongs to the body of `f`, `b` is a parameter of its result
+    let text = "typedef int a;\ntypedef int b;\n\
+                void (*f(int a))(int b) {\n    a * 2;\n    b * c;\n}\n";
+    assert_eq!(
+        parse_statements(text),
+        vec![NonTerminalType::Expression, NonTerminalType::Declaration]
+    );
+}
+
+#[test]
+fn test_typedef_shadowed_in_for_loop() {
+    let text = "typedef int T;\nint f() {\n    for (int T = 0; T < 3; T++)\n        T * 2;\n    T * y;\n}\n";
+    assert_eq!(
+        parse_statements(text),
+        vec![
+            NonTerminalType::ForLoop,
+            NonTerminalType::Expression,
+            NonTerminalType::Declaration,
+        ]
+    );
+}
+
+#[test]
+fn test_typedef_shadowed_by_enumerator() {
+    let text = "typedef int a;\nint main() {\n    enum { a, b = a + 1 } e;\n    a * 2;\n}\n";
+    assert_eq!(
+        parse_statements(text),
+        vec![NonTerminalType::Declaration, NonTerminalType::Expression]
+    );
+}
+
+#[test]
+fn test_parse_every_statement() {
+    use crate::parse::types::NonTerminalType::*;
+    let kinds = parse_statements(include_str!("statements.c"));
+    for kind in &[
+        Declaration,
+        Expression,
+        ForLoop,
+        WhileLoop,
+        DoWhileLoop,
+        If,
+        Block,
+        Return,
+        Typedef,
+        Switch,
+        Label,
+        Case,
+        Goto,
+        Break,
+        Continue,
+    ] {
+        assert!(kinds.contains(kind), "no {:?} statement was parsed", kind);
+    }
+}
+
+/// A translation unit of `functions` functions with long expressions and deeply
+/// nested blocks
+fn generated_source(functions: usize) -> String {
+    let mut text = String::from("typedef int T;\n");
+    for function in 0..functions {
+        text += &format!("int f{}(int a, int b) {{\n    T * x;\n", function);
+        text += "    a = b";
+        for term in 0..100 {
+            text += &format!(" + (a * {} - b) / {}", term, term + 1);
+        }
+        text += ";\n";
+        for depth in 0..20 {
+            text += &format!("    while (a < {}) {{\n", depth);
+        }
+        text += "    a = a + 1;\n";
+        text += &"    }\n".repeat(20);
+        text += "    for (a = 0; a < b; a = a + 1) if (a) b = f(a, b); else return a;\n";
+        text += "    return a;\n}\n";
+    }
+    text
+}
+
+#[test]
+#[ignore]
+fn bench_parse_generated() {
+    let mut per_token = Vec::new();
+    for functions in [25, 50, 100, 200] {
+        let text = generated_source(functions);
+        let tokens: Vec<LexResult> = Lexer::new(text.chars()).collect();
+        let count = tokens.len();
+
+        let start = std::time::Instant::now();
+        parse(tokens.into_iter()).unwrap();
+        let elapsed = start.elapsed();
+
+        println!(
+            "{} functions, {} tokens: {:?} ({:?} per token)",
+            functions,
+            count,
+            elapsed,
+            elapsed / count as u32
+        );
+        per_token.push(elapsed.as_secs_f64() / count as f64);
+    }
+    // Close to linear: eight times the input may not cost more than three times
+    // as much per token
+    assert!(per_token[3] < per_token[0] * 3.0, "{:?}", per_token);
+}
+
+#[test]
+fn test_grammar_analysis() {
+    let rules = &*RULES;
+    let analysis = Analysis::new(rules);
+    assert_eq!(analysis.undefined, vec![]);
+    assert_eq!(analysis.unreachable, vec![]);
+    assert_eq!(analysis.unproductive, vec![]);
+
+    // Conflicts are expected, as the parser is generalised, but should be
+    // known. An LL(1) parser is not even close, a canonical LR(1) one only
+    // needs to look further ahead around typedef names, `_Atomic` and the
+    // `[[` of attributes, which could also start an array.
+    let ll1: Vec<String> = analysis
+        .ll1_conflicts(rules)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    // Left recursion, and a label needing the `:` after its name
+    for conflict in [
+        "LL(1): `Declarators -> Declarator` and `Declarators -> Declarators ',' Declarator` both start with '(', '*', identifier, type name",
+        "LL(1): `Statement -> Expression ';'` and `Statement -> Label` both start with identifier",
+    ] {
+        assert!(ll1.iter().any(|found| found == conflict), "{}", conflict);
+    }
+    // The end of the members of a struct is never where a member could start
+    assert!(
+        !ll1.iter().any(|found| found.contains("`Members ->")),
+        "{:?}",
+        ll1
+    );
+    let mut conflicts: Vec<String> = analysis
+        .lr1_conflicts(rules, &PRECEDENCE)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    conflicts.sort();
+    assert_eq!(
+        conflicts,
+        [
+            "LR(1): before '(', reduce `DirectDeclarator -> type name` or reduce `TypedefSpecifiers -> ε`",
+            "LR(1): before '(', shift or reduce `TypeQualifier -> '_Atomic'`",
+            "LR(1): before ')', reduce `DirectDeclarator -> type name` or reduce `TypedefSpecifiers -> ε`",
+            "LR(1): before '[', shift or reduce `AttributeSpecifiers -> AttributeSpecifiersRepetition1`",
+            "LR(1): before '[', shift or reduce `DirectDeclarator -> type name` or reduce `TypedefSpecifiers -> ε`",
+            "LR(1): before '[', shift or reduce `NumberType -> ε`",
+            "LR(1): before '[', shift or reduce `PointerQualifiers -> ε`",
+            "LR(1): before '[', shift or reduce `TypedefSpecifiers -> ε`",
+        ]
+    );
+}
+
+#[test]
+fn test_gnu_grammar_analysis() {
+    let rules = &*GNU_RULES;
+    let analysis = Analysis::new(rules);
+    assert_eq!(analysis.undefined, vec![]);
+    assert_eq!(analysis.unreachable, vec![]);
+    assert_eq!(analysis.unproductive, vec![]);
+}
+
+#[test]
+fn test_c89_grammar_analysis() {
+    let rules = &*C89_RULES;
+    let analysis = Analysis::new(rules);
+    assert_eq!(analysis.undefined, vec![]);
+    assert_eq!(analysis.unreachable, vec![]);
+    assert_eq!(analysis.unproductive, vec![]);
+}
+
+#[test]
+fn test_grammar_analysis_mistakes() {
+    use crate::parse::types::NonTerminalType::*;
+    use crate::parse::types::RuleType::NonTerminal;
+    let semicolon = RuleType::from(";");
+    let rules: &[(NonTerminalType, &[RuleType])] = &[
+        (Start, &[NonTerminal(TopStatements)]),
+        (TopStatements, &[NonTerminal(Members)]),
+        (TopStatements, &[]),
+        (Block, &[NonTerminal(Block), semicolon]),
+        (Statement, &[semicolon]),
+    ];
+    let analysis = Analysis::new(rules);
+    assert_eq!(analysis.undefined, vec![Members]);
+    assert_eq!(analysis.unreachable, vec![Block, Statement]);
+    assert_eq!(analysis.unproductive, vec![Block]);
+    assert!(analysis.nullable.contains(&Start));
+    assert_eq!(
+        analysis.follow[&Block],
+        vec![
+            Expected::Token(discriminant(&LexItem::Semicolon)),
+            Expected::EndOfFile
+        ]
+        .into_iter()
+        .collect()
+    );
+}
+
+#[test]
+fn test_grammar_conflicts() {
+    use crate::parse::types::NonTerminalType::*;
+    use crate::parse::types::RuleType::NonTerminal;
+    // Expression -> Expression '+' Expression | Literal
+    let plus = RuleType::from("+");
+    let rules: &[(NonTerminalType, &[RuleType])] = &[
+        (Start, &[NonTerminal(Expression)]),
+        (
+            Expression,
+            &[NonTerminal(Expression), plus, NonTerminal(Expression)],
+        ),
+        (Expression, &[NonTerminal(Literal)]),
+        (Literal, &[RuleType::from(";")]),
+    ];
+    let analysis = Analysis::new(rules);
+    assert_eq!(analysis.ll1_conflicts(rules).len(), 1);
+    let conflicts = analysis.lr1_conflicts(rules, &Precedence::default());
+    let lookahead = Expected::Token(discriminant(&LexItem::Plus));
+    assert_eq!(
+        conflicts,
+        vec![Conflict::Lr {
+            lookahead,
+            reductions: vec![1],
+            shift: true,
+        }]
+    );
+
+    // Declaring the precedence of '+' settles it
+    for (associativity, resolution) in [
+        (Associativity::Left, Resolution::Reduce),
+        (Associativity::Right, Resolution::Shift),
+        (Associativity::NonAssociative, Resolution::Neither),
+    ] {
+        let precedence = Precedence::new(vec![(associativity, vec![plus])]);
+        assert_eq!(precedence.resolve(rules[1].1, lookahead), resolution);
+        assert_eq!(analysis.lr1_conflicts(rules, &precedence), vec![]);
+    }
+}
+
+/// An expression with each operation in parentheses
+fn grouped(node: &ParseNode) -> String {
+    let children: Vec<String> = node.children.iter().map(|child| grouped(child)).collect();
+    match &node.node_type {
+        ParseNodeType::Terminal(token) => match &token.item {
+            LexItem::Identifier(name) => name.clone(),
+            item => token_name(discriminant(item))
+                .trim_matches('\'')
+                .to_string(),
+        },
+        ParseNodeType::NonTerminal(NonTerminalType::Expression)
+        | ParseNodeType::NonTerminal(NonTerminalType::ExpressionWithoutComma)
+            if children.len() > 1 =>
+        {
+            format!("({})", children.join(" "))
+        }
+        _ => children.concat(),
+    }
+}
+
+#[test]
+fn test_operator_precedence() {
+    let group = |expression: &str| {
+        let text = format!("int f() {{ {}; }}", expression);
+        let mut stack = vec![parse(Lexer::new(text.chars())).unwrap()];
+        while let Some(node) = stack.pop() {
+            if node.node_type == ParseNodeType::NonTerminal(NonTerminalType::Expression) {
+                return grouped(&node);
+            }
+            stack.extend(node.children.iter().cloned());
+        }
+        unreachable!()
+    };
+    assert_eq!(group("a + b * c"), "(a + (b * c))");
+    assert_eq!(group("a * b + c"), "((a * b) + c)");
+    assert_eq!(group("a - b - c"), "((a - b) - c)");
+    assert_eq!(
+        group("a || b && c | d ^ e & f == g < h << i + j * k"),
+        "(a || (b && (c | (d ^ (e & (f == (g < (h << (i + (j * k))))))))))"
+    );
+    assert_eq!(
+        group("a * b / c % d + e - f << g >> h"),
+        "(((((((a * b) / c) % d) + e) - f) << g) >> h)"
+    );
+    // Assignment and the conditional operator group to the right
+    assert_eq!(group("a = b += c"), "(a = (b += c))");
+    assert_eq!(group("a ? b : c ? d : e"), "(a ? b : (c ? d : e))");
+    assert_eq!(group("a ? b ? c : d : e"), "(a ? (b ? c : d) : e)");
+    assert_eq!(
+        group("a = b || c ? d = e : f"),
+        "(a = ((b || c) ? (d = e) : f))"
+    );
+    assert_eq!(group("a ? b : c = d"), "((a ? b : c) = d)");
+    assert_eq!(group("a = b, c, d"), "(((a = b) , c) , d)");
+}
+
+/// Replaces the first `old` in `source` with `new`
+fn edit(source: &str, old: &str, new: &str) -> Edit {
+    let start = source.find(old).unwrap();
+    let start = source[..start].chars().count();
+    Edit {
+        span: Span {
+            start: Location { character: start },
+            end: Location {
+                character: start + old.chars().count(),
+            },
+        },
+        replacement: new.to_string(),
+    }
+}
+
+#[test]
+fn test_cst_accessors() {
+    let source =
+        "int f(int n, ...) {\n    for (int i = 0; i < n; i++) if (i) break; else continue;\n}\n";
+    let tree = parse(Lexer::new(source.chars())).unwrap();
+    let root = cst::Start::from_root(tree).unwrap();
+    let statements = root.top_statements().statements();
+    assert_eq!(statements.len(), 1);
+
+    let function = statements[0].function().unwrap();
+    let args = function.declarator().unwrap().args().unwrap();
+    assert_eq!(args.parameters().len(), 1);
+    assert!(args.is_variadic());
+    let body = function.body().contents().statements();
+    let for_loop = body[0].for_loop().unwrap();
+    let declaration = for_loop.initial().declaration().unwrap();
+    let declarators = declaration.declarators().unwrap().declarators();
+    assert_eq!(declarators[0].syntax().text(source), "i = 0");
+    let condition = for_loop.condition().expression().unwrap();
+    assert_eq!(condition.syntax().text(source), "i < n");
+    let step = for_loop.step().expression().unwrap();
+    assert_eq!(step.syntax().text(source), "i++");
+
+    let if_statement = for_loop.body().if_statement().unwrap();
+    assert!(if_statement.then_branch().break_statement().is_some());
+    let otherwise = if_statement.else_branch().unwrap();
+    assert!(otherwise.continue_statement().is_some());
+    let span = otherwise.range().unwrap();
+    let start = source.find("continue").unwrap();
+    assert_eq!(
+        (span.start.character, span.end.character),
+        (start, start + 9)
+    );
+
+    // Nodes know where they are
+    let found: cst::ForLoop = otherwise.syntax().ancestor().unwrap();
+    assert_eq!(found, for_loop);
+    assert!(otherwise
+        .syntax()
+        .ancestor::<cst::FunctionDeclaration>()
+        .is_some());
+    let parent = if_statement.syntax().parent().unwrap();
+    assert_eq!(
+        parent.child(if_statement.syntax().index()),
+        *if_statement.syntax()
+    );
+    assert_eq!(
+        SyntaxNode::new_root(Rc::new(ParseNode {
+            node_type: ParseNodeType::NonTerminal(NonTerminalType::BlockContents),
+            children: Vec::new(),
+        }))
+        .range(),
+        None
+    );
+}
+
+#[test]
+fn test_cst_wraps_every_non_terminal() {
+    for (symbol, _) in GNU_RULES.iter().chain(C89_RULES.iter()) {
+        assert_eq!(
+            KINDS.contains(symbol),
+            !symbol.is_hidden(),
+            "{:?} has a wrapper if and only if it is not hidden",
+            symbol
+        );
+    }
+}
+
+#[test]
+fn test_repetitions_belong_to_their_node() {
+    let source = "[[a, , gnu::b(1)]] int x __asm__(\"x\" \"y\" \"z\");";
+    let tokens = Lexer::with_standard(source.chars(), Standard::Gnu11);
+    let tree = parse_with(tokens, Standard::Gnu11).unwrap();
+    let mut stack = vec![tree];
+    let mut lists = Vec::new();
+    while let Some(node) = stack.pop() {
+        if let ParseNodeType::NonTerminal(symbol) = node.node_type {
+            assert!(!symbol.is_hidden(), "{:?} is in the tree", symbol);
+            if symbol == NonTerminalType::AttributeList || symbol == NonTerminalType::StringLiterals
+            {
+                lists.push((symbol, node.children.len()));
+            }
+        }
+        stack.extend(node.children.iter().cloned());
+    }
+    lists.sort_by_key(|&(symbol, _)| symbol == NonTerminalType::StringLiterals);
+    assert_eq!(
+        lists,
+        vec![
+            (NonTerminalType::AttributeList, 5),
+            (NonTerminalType::StringLiterals, 3)
+        ]
+    );
+
+    let specifier = cst::AttributeSpecifier::from_root(
+        parse(Lexer::new("[[a, b]] int x;".chars()))
+            .unwrap()
+            .children[0]
+            .children[0]
+            .children[0]
+            .clone(),
+    )
+    .unwrap();
+    assert_eq!(specifier.list().items().len(), 2);
+}
+
+#[test]
+fn test_top_level_yields_finished_declarations() {
+    let source = "typedef int T; T x; int f(void) { return 0; } int y;";
+    let read = Cell::new(0);
+    let tokens = Lexer::new(source.chars()).inspect(|_| read.set(read.get() + 1));
+    let mut statements = parse_top_level(tokens, Standard::default());
+
+    // Each declaration is finished by the token after it
+    let first = statements.next().unwrap().unwrap();
+    assert_eq!(
+        first.children[0].node_type,
+        ParseNodeType::NonTerminal(NonTerminalType::Typedef)
+    );
+    assert_eq!(read.get(), 5);
+    assert!(statements.is_typedef_name("T"));
+    statements.next().unwrap().unwrap();
+    assert_eq!(read.get(), 8);
+    statements.next().unwrap().unwrap();
+    assert_eq!(read.get(), 18);
+    statements.next().unwrap().unwrap();
+    assert!(statements.next().is_none());
+
+    let collected = parse_top_level(Lexer::new(source.chars()), Standard::default())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        translation_unit(collected),
+        parse(Lexer::new(source.chars())).unwrap()
+    );
+}
+
+#[test]
+fn test_top_level_errors() {
+    let source = "int a; int b = ; int c; int d(";
+    let parsed: Vec<_> = parse_top_level(Lexer::new(source.chars()), Standard::default()).collect();
+    let (statements, errors): (Vec<_>, Vec<_>) = parsed.iter().partition(|parsed| parsed.is_ok());
+    assert_eq!(statements.len(), 4);
+    assert_eq!(errors.len(), 2);
+
+    // Errors come before the declaration they are in
+    let kinds: Vec<bool> = parsed.iter().map(Result::is_ok).collect();
+    assert_eq!(kinds, vec![true, false, true, true, false, true]);
+    let error = |index: usize| match &parsed[index] {
+        Ok(statement) => statement.children[0].node_type == ParseNodeType::Error,
+        Err(_) => unreachable!(),
+    };
+    assert!(!error(0) && error(2) && !error(3) && error(5));
+    assert_eq!(parsed[4].as_ref().unwrap_err().found, Found::EndOfFile);
+
+    let (tree, errors) = parse_recovering(Lexer::new(source.chars()));
+    let statements = parsed.iter().cloned().filter_map(Result::ok).collect();
+    assert_eq!(translation_unit(statements), tree);
+    let streamed: Vec<_> = parsed.into_iter().filter_map(Result::err).collect();
+    assert_eq!(streamed, errors);
+}
+
+#[test]
+fn test_parse_fragments() {
+    let fragment = |non_terminal, text: &str| parse_as(non_terminal, Lexer::new(text.chars()));
+    for (non_terminal, text) in [
+        (NonTerminalType::Expression, "a + b * c, d"),
+        (NonTerminalType::ExpressionWithoutComma, "a ? b : c"),
+        (NonTerminalType::Statement, "while (x) x--;"),
+        (NonTerminalType::TypeName, "char *[4]"),
+        (NonTerminalType::TopStatement, "int f(void) { return 0; }"),
+        (NonTerminalType::Block, "{ typedef int T; T * x; }"),
+    ] {
+        let tree = fragment(non_terminal, text).unwrap();
+        assert_eq!(tree.node_type, ParseNodeType::NonTerminal(non_terminal));
+    }
+
+    // A fragment must be all of the input, and may end once it is complete
+    let error = fragment(NonTerminalType::Expression, "a b").unwrap_err();
+    assert!(matches!(error.found, Found::Token(_)));
+    assert!(error.expected.contains(&Expected::EndOfFile));
+    let error = fragment(NonTerminalType::Statement, "x = 1").unwrap_err();
+    assert_eq!(error.found, Found::EndOfFile);
+    assert_eq!(error.span.start, Location { character: 5 });
+    assert!(!error.expected.contains(&Expected::EndOfFile));
+    let error = fragment(NonTerminalType::TypeName, "").unwrap_err();
+    assert_eq!(error.found, Found::EndOfFile);
+}
+
+#[test]
+fn test_dangling_else() {
+    // An `else` belongs to the nearest `if` without one
+    let statement = |text: &str| {
+        let tree = parse_as(NonTerminalType::Statement, Lexer::new(text.chars())).unwrap();
+        cst::Statement::from_root(tree)
+            .unwrap()
+            .if_statement()
+            .unwrap()
+    };
+    let source = "if (a) if (b) x; else y;";
+    let outer = statement(source);
+    assert!(outer.else_branch().is_none());
+    let inner = outer.then_branch().if_statement().unwrap();
+    assert_eq!(inner.else_branch().unwrap().syntax().text(source), "y;");
+
+    let source = "if (a) { if (b) x; } else y;";
+    let outer = statement(source);
+    assert_eq!(outer.else_branch().unwrap().syntax().text(source), "y;");
+}
+
+#[test]
+fn test_ambiguity_report() {
+    use crate::parse::types::NonTerminalType::*;
+    use crate::parse::types::RuleType::NonTerminal;
+    // Expression -> Expression '+' Expression | identifier, where nothing says
+    // which `+` comes first
+    let identifier = RuleType::Terminal(discriminant(&LexItem::Identifier(String::new())));
+    let plus = RuleType::from("+");
+    let rules: Vec<(NonTerminalType, &[RuleType])> = vec![
+        (Start, vec![NonTerminal(Expression)].leak()),
+        (
+            Expression,
+            vec![NonTerminal(Expression), plus, NonTerminal(Expression)].leak(),
+        ),
+        (Expression, vec![identifier].leak()),
+    ];
+    let table = Box::leak(Box::new(Table::new(rules.leak(), &Precedence::default())));
+
+    let source = "a + b + c";
+    let error = parse_by(table, Expression, Lexer::new(source.chars())).unwrap_err();
+    assert_eq!(error.to_string(), "ambiguous expression with 2 derivations");
+    let report = match &error.found {
+        Found::Ambiguity(report) => report,
+        found => panic!("{:?}", found),
+    };
+    assert_eq!(report.symbol, Expression);
+    assert_eq!(
+        report.span,
+        Span {
+            start: Location { character: 0 },
+            end: Location {
+                character: source.len()
+            }
+        }
+    );
+
+    // The derivations share the names but group them differently
+    let mut groupings: Vec<String> = report
+        .derivations
+        .iter()
+        .map(|derivation| grouped(&derivation.tree))
+        .collect();
+    groupings.sort();
+    assert_eq!(groupings, ["((a + b) + c)", "(a + (b + c))"]);
+    for derivation in &report.derivations {
+        assert_eq!(
+            derivation.rules,
+            [
+                (0, "Expression -> Expression '+' Expression".to_string()),
+                (1, "Expression -> Expression '+' Expression".to_string()),
+            ]
+        );
+    }
+}
+
+#[test]
+fn test_parameter_named_like_a_type() {
+    // A typedef name in parentheses could be the name of the parameter or the
+    // only parameter of a function, and is the latter (C11 6.7.6.3p11)
+    let source = "typedef int T; void f(int (T), int ((T)), int (*(T)));";
+    let tree = to_s_expression(&parse(Lexer::new(source.chars())).unwrap());
+    assert!(!tree.contains("TypeWithIdentifier"), "{}", tree);
+    let abstract_function = "(DirectAbstractDeclarator \"(\" (Args (TypeWithMaybeIdentifier \
+                             (TypeName (Type T (TypedefSpecifiers))))) \")\")";
+    assert_eq!(tree.matches(abstract_function).count(), 3, "{}", tree);
+
+    // So is a parameter of qualified type with no type specifier in C89
+    let source = "typedef int T; void f(const T);";
+    let tree = to_s_expression(&parse_c89(source).unwrap());
+    assert!(!tree.contains("TypeWithIdentifier"), "{}", tree);
+}
+
+#[test]
+fn test_reparse_keeps_untouched_declarations() {
+    let source = "int a;\nint f(void) { return 1; }\nint b;\n";
+    let parsed = Parsed::new(source, Standard::default());
+    let change = edit(source, "1", "2");
+    let edited = change.apply(source);
+    let reparsed = parsed.reparse(&edited, &change);
+    assert_eq!(reparsed, Parsed::new(&edited, Standard::default()));
+
+    let statements = |tree: &Rc<ParseNode>| {
+        let mut statements = Vec::new();
+        let mut rest = tree.children[0].clone();
+        while rest.children.len() == 2 {
+            statements.push(rest.children[0].clone());
+            rest = rest.children[1].clone();
+        }
+        statements
+    };
+    let (old, new) = (statements(&parsed.tree), statements(&reparsed.tree));
+    assert!(Rc::ptr_eq(&old[0], &new[0]));
+    assert!(!Rc::ptr_eq(&old[1], &new[1]));
+    assert!(Rc::ptr_eq(&old[2], &new[2]));
+}
+
+#[test]
+fn test_reparse_follows_typedefs_and_braces() {
+    let cases: &[(&str, &str, &str)] = &[
+        (
+            "int T;\nint f(void) { T * x; }\n",
+            "int T;",
+            "typedef int T;",
+        ),
+        (
+            "typedef int T;\nint f(void) { T * x; }\n",
+            "typedef int T;",
+            "int T;",
+        ),
+        ("int f(void) { return 0; }\nint g;\nint h;\n", "0; }", "0;"),
+        ("int f(void) { return 0;\nint g;\n}\n", "0;", "0; }"),
+        ("int a;\nint b;\n", "int a;", "int a; \"unclosed"),
+        ("int a = ;\nint b;\n", ";\nint b", "\nint b"),
+    ];
+    for (source, old, new) in cases {
+        let parsed = Parsed::new(source, Standard::default());
+        let change = edit(source, old, new);
+        let edited = change.apply(source);
+        assert_eq!(
+            parsed.reparse(&edited, &change),
+            Parsed::new(&edited, Standard::default()),
+            "{:?} after {:?}",
+            edited,
+            change
+        );
+    }
+}
+
+/// A small pseudorandom number generator, so that the property test below is
+/// the same on every run
+struct XorShift(u64);
+
+impl XorShift {
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+/// Reparsing after any edit gives the same tokens, tree and errors as parsing
+/// the edited source from scratch
+#[test]
+fn test_reparse_equals_full_parse() {
+    const DECLARATIONS: &[&str] = &[
+        "typedef int T;",
+        "int T;",
+        "T * x;",
+        "int f(int a) { return a * 2; }",
+        "struct s { int a; T b; };",
+        "int g(void) { T * p; if (p) { return 1; } return 0; }",
+        "enum { A, B = 2 };",
+        "static char *s = \"str\";",
+        "_Static_assert(1, \"x\");",
+        "int x = 1 +;",
+        "int (;",
+        "void h(void) { int a; a = 3;",
+        "}",
+        ";",
+    ];
+    const REPLACEMENTS: &[&str] = &[
+        "", " ", "\n", "T", "x", ";", "{", "}", "(", ")", "*", "1", "\"", "int ", "typedef ",
+        "int y;", "@",
+    ];
+
+    let mut random = XorShift(0x2545_f491_4f6c_dd1d);
+    for _ in 0..40 {
+        let mut source = String::new();
+        for _ in 0..random.below(8) {
+            source.push_str(DECLARATIONS[random.below(DECLARATIONS.len())]);
+            source.push(if random.below(2) == 0 { ' ' } else { '\n' });
+        }
+        let mut parsed = Parsed::new(&source, Standard::default());
+        for _ in 0..8 {
+            let length = source.chars().count();
+            let start = random.below(length + 1);
+            let end = start + random.below((length - start).min(12) + 1);
+            let replacement = match random.below(3) {
+                0 => DECLARATIONS[random.below(DECLARATIONS.len())],
+                _ => REPLACEMENTS[random.below(REPLACEMENTS.len())],
+            };
+            let change = Edit {
+                span: Span {
+                    start: Location { character: start },
+                    end: Location { character: end },
+                },
+                replacement: replacement.to_string(),
+            };
+            let edited = change.apply(&source);
+            let reparsed = parsed.reparse(&edited, &change);
+            assert_eq!(
+                reparsed,
+                Parsed::new(&edited, Standard::default()),
+                "{:?} after {:?} on {:?}",
+                edited,
+                change,
+                source
+            );
+            source = edited;
+            parsed = reparsed;
+        }
+    }
+}
+
+#[test]
+fn test_dump_s_expression() {
+    let source = "char *s = \"a\\\"b\";";
+    let tree = parse(Lexer::new(source.chars())).unwrap();
+    assert_eq!(
+        to_s_expression(&tree),
+        "(Start (TopStatements (TopStatement (Declaration (Type (TypeSpecifier char) \
+         (NumberType)) (InitDeclarators (InitDeclarator (Declarator (Pointer \"*\" \
+         (PointerQualifiers)) (DirectDeclarator s)) \"=\" (Initializer (ExpressionWithoutComma \
+         (Expression2_5 (Expression2 (Expression1 (Literal \"\\\"a\\\\\\\"b\\\"\"))))))))) \
+         \";\") (TopStatements)))"
+    );
+    // Like the other formats, a dump ends in a newline
+    let dumped = dump(&tree, TreeFormat::SExpression, source);
+    assert_eq!(dumped, to_s_expression(&tree) + "\n");
+}
+
+#[test]
+fn test_dump_indented() {
+    let source = "int x;\n) ;";
+    let (tree, errors) = parse_recovering(Lexer::new(source.chars()));
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        to_indented(&tree, source),
+        "Start 1:1-2:4
+  TopStatements 1:1-2:4
+    TopStatement 1:1-1:7
+      Declaration 1:1-1:6
+        Type 1:1-1:4
+          TypeSpecifier 1:1-1:4
+            int 1:1-1:4
+          NumberType
+        InitDeclarators 1:5-1:6
+          InitDeclarator 1:5-1:6
+            Declarator 1:5-1:6
+              DirectDeclarator 1:5-1:6
+                x 1:5-1:6
+      ; 1:6-1:7
+    TopStatements 2:1-2:4
+      TopStatement 2:1-2:4
+        Error 2:1-2:4
+          ) 2:1-2:2
+          ; 2:3-2:4
+      TopStatements
+"
+    );
+}
+
+#[test]
+fn test_dump_dot() {
+    let (tree, _) = parse_recovering(Lexer::new(")".chars()));
+    assert_eq!(
+        to_dot(&tree),
+        "digraph parse {
+    ordering=out;
+    node [shape=box];
+    n0 [label=\"Start\"];
+    n1 [label=\"TopStatements\"];
+    n0 -> n1;
+    n2 [label=\"TopStatement\"];
+    n1 -> n2;
+    n3 [label=\"Error\", color=red];
+    n2 -> n3;
+    n4 [label=\")\", shape=plaintext];
+    n3 -> n4;
+    n5 [label=\"TopStatements\"];
+    n1 -> n5;
+}
+"
+    );
+}
+
+/// The identifiers in a subtree, in order
+fn identifiers(node: &ParseNode) -> Vec<String> {
+    match &node.node_type {
+        ParseNodeType::Terminal(token) => match &token.item {
+            LexItem::Identifier(name) => vec![name.clone()],
+            _ => Vec::new(),
+        },
+        _ => node.children.iter().flat_map(|c| identifiers(c)).collect(),
+    }
+}
+
+fn variability(text: &str) -> crate::parse::variability::VariabilityTree {
+    let preprocessed: String = Preprocessor::new(text.chars())
+        .keep_conditionals()
+        .collect();
+    parse_variability(&preprocessed, Standard::default())
+}
+
+#[test]
+fn test_variability_choice() {
+    let text = "typedef int T;\n#ifdef DEBUG\nint debug = ;\n#else\nT release;\n#endif\nT * b;\n";
+    let tree = variability(text);
+    assert!(matches!(
+        tree.variants[..],
+        [Variant::Common(_), Variant::Choice(_), Variant::Common(_)]
+    ));
+    let debug = PresenceCondition::Defined("DEBUG".to_string());
+    let declarations: Vec<(String, Vec<String>)> = tree
+        .declarations()
+        .iter()
+        .map(|(condition, declaration)| (condition.to_string(), identifiers(declaration)))
+        .collect();
+    assert_eq!(
+        declarations,
+        [
+            ("1".to_string(), vec!["T".to_string()]),
+            ("defined(DEBUG)".to_string(), vec!["debug".to_string()]),
+            ("!defined(DEBUG)".to_string(), vec!["release".to_string()]),
+            ("1".to_string(), vec!["b".to_string()]),
+        ]
+    );
+    // Only the branch with the mistake has the error
+    assert_eq!(tree.errors.len(), 1);
+    let (condition, error) = &tree.errors[0];
+    assert_eq!(*condition, debug);
+    assert_eq!(error.span.start.character, text.find(" ;").unwrap() + 1);
+}
+
+#[test]
+fn test_variability_split() {
+    let text = "int f(void) {\n#ifdef LOG\n  log(1)\n#endif\n  return 0;\n}\nint g;\n";
+    let tree = variability(text);
+    let alternatives = match &tree.variants[..] {
+        [Variant::Split(alternatives), Variant::Common(g)] => {
+            assert_eq!(identifiers(g), ["g"]);
+            alternatives
+        }
+        variants => panic!("unexpected variants {:?}", variants),
+    };
+    let log = PresenceCondition::Defined("LOG".to_string());
+    assert_eq!(alternatives.len(), 2);
+    assert_eq!(
+        (&alternatives[0].condition, &alternatives[1].condition),
+        (&log, &log.not())
+    );
+    assert!(alternatives.iter().all(|a| a.variants.len() == 1));
+
+    // The missing `;` is only a mistake with LOG defined
+    assert_eq!(tree.errors.len(), 1);
+    let (condition, error) = &tree.errors[0];
+    assert_eq!(*condition, log);
+    assert_eq!(error.span.start.character, text.find("return").unwrap());
+
+    // A typedef in one branch is a typedef name after the conditional
+    let tree = variability("#if X\ntypedef int T;\n#endif\nvoid h(void) { T * p; }\n");
+    assert!(tree.errors.is_empty());
+}
@@ -1,11 +1,11 @@
-use crate::lex::constants::LITERAL_TOKENS;
+use crate::lex::constants::{C99_TOKENS, GNU_TOKENS, LITERAL_TOKENS};
 use crate::lex::errors::LexSuccess;
 use crate::lex::types::LexItem;
 use std::mem::discriminant;
 use std::mem::Discriminant;
 use std::rc::Rc;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum RuleType {
     Terminal(Discriminant<LexItem>),
     NonTerminal(NonTerminalType),
@@ -15,86 +15,23 @@ pub enum RuleType {
 pub enum ParseNodeType {
     Terminal(LexSuccess),
     NonTerminal(NonTerminalType),
+    /// Tokens skipped while recovering from a syntax error
+    Error,
 }
 
-#[derive(Debug, PartialEq, Clone, Copy, Hash, Eq)]
-pub enum NonTerminalType {
-    Start, // There must only be one Start rule
-    TopStatements,
-    TopStatement,
-    FunctionPointer,
-    Type,
-    NumberType,
-    TypeSpecifier,
-    TypeQualifier,
-    MaybeType,
-    TypeWithIdentifier,
-    TypeWithMaybeIdentifier,
-    BasicDeclaration,
-    FunctionDeclaration,
-    ForwardDeclaration,
-    StructOrUnionDeclaration,
-    StructOrUnion,
-    MaybeIdentifier,
-    StructMembers,
-    StructMember,
-    MaybeBitfield,
-    Block,
-    BlockContents,
-    Statement,
-    Declaration,
-    ExpressionOrDeclaration,
-    ForLoop,
-    WhileLoop,
-    DoWhileLoop,
-    Return,
-    Expression,
-    ExpressionWithoutComma,
-    Expression15,
-    Expression14,
-    Operator14,
-    Expression13,
-    Expression12,
-    Expression11,
-    Expression10,
-    Expression9,
-    Expression8,
-    Expression7,
-    Expression6,
-    Expression5,
-    Expression4,
-    Expression3,
-    Expression2,
-    Expression1,
-    Operator7,
-    Operator6,
-    Operator5,
-    Operator4,
-    Operator3,
-    Expression2_5,
-    Operator2,
-    UnaryOperator1,
-    StructOperator,
-    CallArguments,
-    NonEmptyCallArguments,
-    Literal,
-    Typedef,
-    If,
-    MaybeElse,
-    TypeWithoutIdentifier,
-    Args,
-    Members,
-}
+include!(concat!(env!("OUT_DIR"), "/non_terminals.rs"));
 
 impl From<&str> for RuleType {
     fn from(value: &str) -> Self {
         let match_: Option<&(&str, LexItem)> = LITERAL_TOKENS
             .iter()
+            .chain(C99_TOKENS)
+            .chain(GNU_TOKENS)
             .find(|(key, _)| key.trim_end_matches('\x00') == value);
         if let Some(match_) = match_ {
             RuleType::Terminal(discriminant(&match_.1))
         } else {
-            panic!(format!(r#"The string "{}" does not match a token"#, value))
+            panic!(r#"The string "{}" does not match a token"#, value)
         }
     }
 }
@@ -122,3 +59,12 @@ pub struct ParseNode {
     pub node_type: ParseNodeType,
     pub children: Vec<Rc<ParseNode>>,
 }
+
+impl From<LexSuccess> for ParseNode {
+    fn from(value: LexSuccess) -> Self {
+        ParseNode {
+            node_type: ParseNodeType::Terminal(value),
+            children: Vec::new(),
+        }
+    }
+}
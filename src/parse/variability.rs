@@ -0,0 +1,314 @@
+use crate::cpp::conditional::{conditional_segments, configurations, PresenceCondition, Segment};
+use crate::lex::errors::LexResult;
+use crate::lex::Lexer;
+use crate::parse::errors::{Found, ParseError};
+use crate::parse::incremental::{shift, top_statements};
+use crate::parse::parser::parse_after;
+use crate::parse::symbols::TypedefTable;
+use crate::parse::types::ParseNode;
+use crate::standard::Standard;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// How many configurations of a region split by conditionals are parsed at most
+const MAX_CONFIGURATIONS: usize = 64;
+
+/// Top-level declarations in some of the configurations of a source
+#[derive(Clone, Debug, PartialEq)]
+pub enum Variant {
+    /// A declaration in every configuration of the enclosing alternative
+    Common(Rc<ParseNode>),
+    /// A conditional whose branches each hold whole declarations, one
+    /// alternative for each branch
+    Choice(Vec<Alternative>),
+    /// Declarations that conditionals split, such as a function body with an
+    /// `#ifdef` in it, parsed once for each way of choosing the branches. Only
+    /// the first `MAX_CONFIGURATIONS` of them are parsed.
+    Split(Vec<Alternative>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alternative {
+    /// When the declarations are there, given that the variant is
+    pub condition: PresenceCondition,
+    pub variants: Vec<Variant>,
+}
+
+/// A source parsed in every configuration of its conditional directives
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariabilityTree {
+    pub variants: Vec<Variant>,
+    /// The syntax errors of every configuration, each once with the
+    /// configurations it is in
+    pub errors: Vec<(PresenceCondition, ParseError)>,
+}
+
+impl VariabilityTree {
+    /// Every top-level declaration with the configurations it is in
+    pub fn declarations(&self) -> Vec<(PresenceCondition, Rc<ParseNode>)> {
+        let mut declarations = Vec::new();
+        collect(
+            &self.variants,
+            &PresenceCondition::Always,
+            &mut declarations,
+        );
+        declarations
+    }
+}
+
+fn collect(
+    variants: &[Variant],
+    condition: &PresenceCondition,
+    declarations: &mut Vec<(PresenceCondition, Rc<ParseNode>)>,
+) {
+    for variant in variants {
+        match variant {
+            Variant::Common(declaration) => {
+                declarations.push((condition.clone(), declaration.clone()))
+            }
+            Variant::Choice(alternatives) | Variant::Split(alternatives) => {
+                for alternative in alternatives {
+                    let condition = condition.and(&alternative.condition);
+                    collect(&alternative.variants, &condition, declarations);
+                }
+            }
+        }
+    }
+}
+
+/// Parses text from a `Preprocessor` that keeps conditional directives, taking
+/// every branch of them. A conditional whose branches hold whole declarations
+/// is a choice between them. Otherwise the declarations around it are parsed
+/// once for each configuration.
+pub fn parse_variability(text: &str, standard: Standard) -> VariabilityTree {
+    let parser = VariabilityParser {
+        text: text.chars().collect(),
+        standard,
+    };
+    let parsed = parser.segments(&conditional_segments(text), TypedefTable::new());
+    VariabilityTree {
+        variants: parsed.variants,
+        errors: parsed.errors,
+    }
+}
+
+struct VariabilityParser {
+    text: Vec<char>,
+    standard: Standard,
+}
+
+/// Segments parsed in the configurations of some alternative
+struct Parsed {
+    variants: Vec<Variant>,
+    /// With the configurations they are in, given the alternative's
+    errors: Vec<(PresenceCondition, ParseError)>,
+    /// The typedef names at the end, in any configuration
+    typedefs: TypedefTable,
+    /// Whether the last declaration ends in the segments
+    closed: bool,
+}
+
+impl VariabilityParser {
+    fn segments(&self, segments: &[Segment], typedefs: TypedefTable) -> Parsed {
+        let mut parsed = Parsed {
+            variants: Vec::new(),
+            errors: Vec::new(),
+            typedefs,
+            closed: true,
+        };
+        let mut start = 0;
+        while start < segments.len() {
+            if self.alone(&segments[start], &mut parsed) {
+                start += 1;
+                continue;
+            }
+
+            // The declarations go on after the segment, so parse every
+            // configuration of as many segments as it takes to end them
+            let mut end = start + 1;
+            loop {
+                let chosen = configurations(&segments[start..end], MAX_CONFIGURATIONS);
+                let results: Vec<_> = chosen
+                    .into_iter()
+                    .map(|(condition, text)| (condition, self.parse(&text, &parsed.typedefs)))
+                    .collect();
+                let closed = results.iter().all(|(_, (_, errors, _))| is_closed(errors));
+                if !closed && end < segments.len() {
+                    end += 1;
+                    continue;
+                }
+
+                parsed.typedefs = merge(
+                    &parsed.typedefs,
+                    results.iter().map(|(_, (_, _, after))| after),
+                );
+                // Errors in every configuration are reported once
+                let (_, (_, first_errors, _)) = &results[0];
+                let everywhere: Vec<ParseError> = first_errors
+                    .iter()
+                    .filter(|&error| {
+                        results
+                            .iter()
+                            .all(|(_, (_, errors, _))| errors.contains(error))
+                    })
+                    .cloned()
+                    .collect();
+                for (condition, (_, errors, _)) in &results {
+                    parsed.errors.extend(
+                        errors
+                            .iter()
+                            .filter(|&error| !everywhere.contains(error))
+                            .map(|error| (condition.clone(), error.clone())),
+                    );
+                }
+                let always = PresenceCondition::Always;
+                parsed
+                    .errors
+                    .extend(everywhere.into_iter().map(|error| (always.clone(), error)));
+
+                let (conditions, statements): (Vec<_>, Vec<_>) = results
+                    .into_iter()
+                    .map(|(condition, (statements, _, _))| (condition, statements))
+                    .unzip();
+                let (leading, trailing) = shared(&statements);
+                let first = &statements[0];
+                parsed
+                    .variants
+                    .extend(first[..leading].iter().cloned().map(Variant::Common));
+                if statements.len() > 1 {
+                    let alternatives = conditions
+                        .into_iter()
+                        .zip(&statements)
+                        .map(|(condition, statements)| Alternative {
+                            condition,
+                            variants: statements[leading..statements.len() - trailing]
+                                .iter()
+                                .cloned()
+                                .map(Variant::Common)
+                                .collect(),
+                        })
+                        .collect();
+                    parsed.variants.push(Variant::Split(alternatives));
+                }
+                let after = &first[first.len() - trailing..];
+                parsed
+                    .variants
+                    .extend(after.iter().cloned().map(Variant::Common));
+                parsed.closed = closed;
+                start = end;
+                break;
+            }
+        }
+        parsed
+    }
+
+    /// Parses a segment on its own if it holds whole declarations
+    fn alone(&self, segment: &Segment, parsed: &mut Parsed) -> bool {
+        match segment {
+            Segment::Text(range) => {
+                let (statements, errors, after) =
+                    self.parse(std::slice::from_ref(range), &parsed.typedefs);
+                if !is_closed(&errors) {
+                    return false;
+                }
+                let always = PresenceCondition::Always;
+                parsed
+                    .variants
+                    .extend(statements.into_iter().map(Variant::Common));
+                parsed
+                    .errors
+                    .extend(errors.into_iter().map(|error| (always.clone(), error)));
+                parsed.typedefs = after;
+                true
+            }
+            Segment::Conditional(branches) => {
+                let results: Vec<Parsed> = branches
+                    .iter()
+                    .map(|branch| self.segments(&branch.segments, parsed.typedefs.clone()))
+                    .collect();
+                if !results.iter().all(|result| result.closed) {
+                    return false;
+                }
+                parsed.typedefs = merge(&parsed.typedefs, results.iter().map(|r| &r.typedefs));
+                let mut alternatives = Vec::new();
+                for (branch, result) in branches.iter().zip(results) {
+                    parsed.errors.extend(
+                        result
+                            .errors
+                            .into_iter()
+                            .map(|(condition, error)| (branch.condition.and(&condition), error)),
+                    );
+                    alternatives.push(Alternative {
+                        condition: branch.condition.clone(),
+                        variants: result.variants,
+                    });
+                }
+                parsed.variants.push(Variant::Choice(alternatives));
+                true
+            }
+        }
+    }
+
+    /// Parses the text in `ranges` with everything between them left out
+    fn parse(
+        &self,
+        ranges: &[Range<usize>],
+        typedefs: &TypedefTable,
+    ) -> (Vec<Rc<ParseNode>>, Vec<ParseError>, TypedefTable) {
+        let (start, end) = match (ranges.first(), ranges.last()) {
+            (Some(first), Some(last)) => (first.start, last.end),
+            _ => return (Vec::new(), Vec::new(), typedefs.clone()),
+        };
+        // Newlines stay so that the lexer sees the same lines
+        let mut kept = vec![false; end - start];
+        for range in ranges {
+            kept[range.start - start..range.end - start].fill(true);
+        }
+        let text: String = self.text[start..end]
+            .iter()
+            .zip(kept)
+            .map(|(&ch, kept)| if kept || ch == '\n' { ch } else { ' ' })
+            .collect();
+        let tokens = Lexer::with_standard(text.chars(), self.standard)
+            .map(|token: LexResult| shift(&token, start as isize));
+        let (tree, errors, after) = parse_after(tokens, self.standard, typedefs.clone());
+        (top_statements(&tree), errors, after)
+    }
+}
+
+/// Whether parsing stopped at the end of a declaration
+fn is_closed(errors: &[ParseError]) -> bool {
+    !errors.iter().any(|error| error.found == Found::EndOfFile)
+}
+
+/// How many declarations at the start and at the end are the same in every
+/// configuration, because they are in the text around the conditionals
+fn shared(configurations: &[Vec<Rc<ParseNode>>]) -> (usize, usize) {
+    let first = &configurations[0];
+    let shortest = configurations.iter().map(Vec::len).min().unwrap_or(0);
+    let leading = (0..shortest)
+        .take_while(|&n| configurations.iter().all(|other| other[n] == first[n]))
+        .count();
+    let trailing = (0..shortest - leading)
+        .take_while(|&n| {
+            configurations
+                .iter()
+                .all(|other| other[other.len() - 1 - n] == first[first.len() - 1 - n])
+        })
+        .count();
+    (leading, trailing)
+}
+
+/// Names that are typedef names in any configuration stay typedef names
+fn merge<'a>(
+    before: &TypedefTable,
+    afters: impl Iterator<Item = &'a TypedefTable>,
+) -> TypedefTable {
+    let mut merged = before.clone();
+    for after in afters {
+        for name in after.file_scope_typedefs() {
+            merged.declare_typedef(name.to_string());
+        }
+    }
+    merged
+}
@@ -0,0 +1,42 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// The dialect of C being compiled, as chosen with `-std=`
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Standard {
+    #[default]
+    C11,
+    /// C11 with the GNU extensions that system headers rely on
+    Gnu11,
+    /// ANSI C, with old-style function definitions and implicit `int`
+    C89,
+}
+
+impl Standard {
+    pub fn is_gnu(self) -> bool {
+        self == Standard::Gnu11
+    }
+}
+
+impl FromStr for Standard {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "c11" | "c1x" | "iso9899:2011" => Ok(Standard::C11),
+            "gnu11" | "gnu1x" => Ok(Standard::Gnu11),
+            "c89" | "c90" | "ansi" | "iso9899:1990" => Ok(Standard::C89),
+            _ => Err(format!("unknown standard '{}'", name)),
+        }
+    }
+}
+
+impl Display for Standard {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Standard::C11 => "c11",
+            Standard::Gnu11 => "gnu11",
+            Standard::C89 => "c89",
+        })
+    }
+}
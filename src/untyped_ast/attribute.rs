@@ -0,0 +1,195 @@
+use crate::lex::errors::LexSuccess;
+use crate::lex::types::LexItem;
+use crate::parse::types::NonTerminalType;
+use crate::parse::types::ParseNode;
+use crate::parse::types::ParseNodeType;
+use crate::untyped_ast::constant::evaluate;
+use crate::untyped_ast::context::Context;
+use crate::untyped_ast::errors::{span_of, SemanticError, SemanticErrorType};
+use crate::untyped_ast::types::{Attribute, AttributeArgument, Specifiers};
+use std::rc::Rc;
+
+/// Reads every attribute of an `__attribute__((...))`, leaving out empty ones.
+/// They all belong to the `gnu` prefix.
+pub(super) fn read_attributes(node: Rc<ParseNode>, context: &mut Context) -> Vec<Attribute> {
+    require_non_terminal!(node, NonTerminalType::Attribute);
+    require_len!(node, |len| len == 6);
+    require_terminal!(node, 0, LexItem::Attribute);
+
+    let mut attributes = Vec::new();
+    read_attribute_list(node.children[3].clone(), &mut attributes, context);
+    for attribute in &mut attributes {
+        attribute.prefix.get_or_insert_with(|| "gnu".to_string());
+    }
+    attributes
+}
+
+/// Reads every attribute of a `[[...]]`, leaving out empty ones
+pub(super) fn read_attribute_specifier(
+    node: Rc<ParseNode>,
+    context: &mut Context,
+) -> Vec<Attribute> {
+    require_non_terminal!(node, NonTerminalType::AttributeSpecifier);
+    require_len!(node, |len| len == 5);
+    require_terminal!(node, 0, LexItem::LeftBracket);
+    context.not_in_c89("an attribute", &node);
+
+    let mut attributes = Vec::new();
+    read_attribute_list(node.children[2].clone(), &mut attributes, context);
+    attributes
+}
+
+/// Puts the attributes at the start of a declaration before those among its
+/// specifiers
+pub(super) fn prepend_attributes(specifiers: &mut Specifiers, mut leading: Vec<Attribute>) {
+    leading.append(&mut specifiers.attributes);
+    specifiers.attributes = leading;
+}
+
+/// Reads a sequence of `[[...]]`, in order
+pub(super) fn read_attribute_specifiers(
+    node: Rc<ParseNode>,
+    context: &mut Context,
+) -> Vec<Attribute> {
+    require_non_terminal!(node, NonTerminalType::AttributeSpecifiers);
+
+    node.children
+        .iter()
+        .flat_map(|specifier| read_attribute_specifier(specifier.clone(), context))
+        .collect()
+}
+
+/// Reads the items of an attribute list, which are separated by commas
+fn read_attribute_list(
+    node: Rc<ParseNode>,
+    attributes: &mut Vec<Attribute>,
+    context: &mut Context,
+) {
+    require_non_terminal!(node, NonTerminalType::AttributeList);
+
+    for item in node.children.iter().step_by(2) {
+        require_non_terminal!(item, NonTerminalType::AttributeItem);
+        require_len!(item, |len| len == 0 || len == 1 || len == 4);
+
+        let (prefix, name) = match item.children.first() {
+            Some(name) => read_attribute_name(name),
+            None => continue,
+        };
+        let mut arguments = Vec::new();
+        if let Some(list) = item.children.get(2) {
+            read_arguments(list.clone(), &mut arguments, context);
+        }
+        attributes.push(Attribute {
+            prefix,
+            name,
+            arguments,
+        });
+    }
+}
+
+/// The prefix and name of an attribute, where `__name__` is the same as `name`
+fn read_attribute_name(node: &Rc<ParseNode>) -> (Option<String>, String) {
+    require_non_terminal!(node, NonTerminalType::AttributeName);
+    require_len!(node, |len| len == 1 || len == 4);
+
+    let name = read_name(&node.children[node.children.len() - 1]);
+    let prefix = match node.children.len() {
+        4 => Some(read_name(&node.children[0])),
+        _ => None,
+    };
+    (prefix, name)
+}
+
+fn read_name(node: &Rc<ParseNode>) -> String {
+    let name = match &node.node_type {
+        ParseNodeType::Terminal(LexSuccess { item, .. }) => match item {
+            LexItem::Identifier(name) | LexItem::TypedefName(name) => name.clone(),
+            LexItem::Const => "const".to_string(),
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    };
+    match name
+        .strip_prefix("__")
+        .and_then(|name| name.strip_suffix("__"))
+    {
+        Some(stripped) if !stripped.is_empty() => stripped.to_string(),
+        _ => name,
+    }
+}
+
+fn read_arguments(
+    node: Rc<ParseNode>,
+    arguments: &mut Vec<AttributeArgument>,
+    context: &mut Context,
+) {
+    match node.node_type {
+        ParseNodeType::NonTerminal(NonTerminalType::CallArguments) => {
+            if let Some(list) = node.children.first() {
+                read_arguments(list.clone(), arguments, context);
+            }
+        }
+        ParseNodeType::NonTerminal(NonTerminalType::NonEmptyCallArguments) => {
+            arguments.extend(read_argument(&node.children[0], context));
+            if let Some(rest) = node.children.get(2) {
+                read_arguments(rest.clone(), arguments, context);
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// An argument is a name, a string literal or an integer constant
+fn read_argument(node: &Rc<ParseNode>, context: &mut Context) -> Option<AttributeArgument> {
+    let mut inner = node;
+    while inner.children.len() == 1 {
+        inner = &inner.children[0];
+    }
+    match &inner.node_type {
+        ParseNodeType::Terminal(LexSuccess {
+            item: LexItem::Identifier(name),
+            ..
+        }) if !context.constants.contains_key(name) => {
+            return Some(AttributeArgument::Identifier(name.clone()))
+        }
+        ParseNodeType::Terminal(LexSuccess {
+            item: LexItem::StringLiteral(string),
+            ..
+        }) => return Some(AttributeArgument::String(string.clone())),
+        _ => {}
+    }
+    let value = evaluate(node, context);
+    if value.is_none() {
+        context.errors.push(SemanticError {
+            error_type: SemanticErrorType::NotConstant,
+            span: span_of(node),
+        });
+    }
+    value.map(AttributeArgument::Constant)
+}
+
+/// The assembler name of an asm label
+pub(super) fn read_asm_label(node: Rc<ParseNode>) -> Vec<u8> {
+    require_non_terminal!(node, NonTerminalType::AsmLabel);
+    require_len!(node, |len| len == 4);
+    require_terminal!(node, 0, LexItem::Asm);
+
+    read_string_literals(&node.children[2])
+}
+
+/// Concatenates adjacent string literals
+fn read_string_literals(node: &Rc<ParseNode>) -> Vec<u8> {
+    require_non_terminal!(node, NonTerminalType::StringLiterals);
+
+    let mut string = Vec::new();
+    for literal in &node.children {
+        match &literal.node_type {
+            ParseNodeType::Terminal(LexSuccess {
+                item: LexItem::StringLiteral(literal),
+                ..
+            }) => string.extend(literal),
+            _ => unreachable!(),
+        }
+    }
+    string
+}
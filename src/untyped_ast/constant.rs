@@ -0,0 +1,167 @@
+use crate::lex::errors::LexSuccess;
+use crate::lex::types::{LexItem, NumberType};
+use crate::parse::types::NonTerminalType;
+use crate::parse::types::ParseNode;
+use crate::parse::types::ParseNodeType;
+use crate::untyped_ast::context::Context;
+use crate::untyped_ast::declarator::read_type_name;
+use crate::untyped_ast::errors::{span_of, SemanticError, SemanticErrorType};
+use crate::untyped_ast::layout::size_and_align;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+/// The value of an integer constant expression, or `None` if `node` is not
+/// one. Enumerators and tags are looked up in `context`.
+pub(super) fn evaluate(node: &ParseNode, context: &mut Context) -> Option<i128> {
+    let token = |index: usize| match node.children.get(index).map(|child| &child.node_type) {
+        Some(ParseNodeType::Terminal(token)) => Some(&token.item),
+        _ => None,
+    };
+    // Operators are either tokens or `OperatorN` nodes holding one
+    let operator = |index: usize| {
+        token(index).or_else(
+            || match &node.children.get(index)?.children.first()?.node_type {
+                ParseNodeType::Terminal(token) => Some(&token.item),
+                _ => None,
+            },
+        )
+    };
+    let mut operand = |index: usize| evaluate(&node.children[index], context);
+
+    match &node.node_type {
+        ParseNodeType::Terminal(LexSuccess { item, .. }) => {
+            return match item {
+                LexItem::NumericLiteral(number) => integer(number),
+                LexItem::Identifier(name) => {
+                    context.constants.get(name).map(|value| i128::from(*value))
+                }
+                _ => None,
+            }
+        }
+        ParseNodeType::Error => return None,
+        ParseNodeType::NonTerminal(_) => {}
+    }
+
+    match node.children.len() {
+        1 => operand(0),
+        2 => {
+            let value = operand(1)?;
+            match operator(0)? {
+                LexItem::Plus => Some(value),
+                LexItem::Minus => value.checked_neg(),
+                LexItem::Not => Some(!value),
+                LexItem::LogicalNot => Some((value == 0) as i128),
+                LexItem::Extension => Some(value),
+                _ => None,
+            }
+        }
+        3 if token(0) == Some(&LexItem::LeftParen) => operand(1),
+        3 => {
+            let item = operator(1)?;
+            let left = operand(0)?;
+            // The right operand of `&&` and `||` is only evaluated if needed
+            match item {
+                LexItem::LogicalAnd if left == 0 => return Some(0),
+                LexItem::LogicalOr if left != 0 => return Some(1),
+                _ => {}
+            }
+            let right = operand(2)?;
+            match item {
+                LexItem::Mul => left.checked_mul(right),
+                LexItem::Div => left.checked_div(right),
+                LexItem::Mod => left.checked_rem(right),
+                LexItem::Plus => left.checked_add(right),
+                LexItem::Minus => left.checked_sub(right),
+                LexItem::LShift => left.checked_shl(u32::try_from(right).ok()?),
+                LexItem::RShift => left.checked_shr(u32::try_from(right).ok()?),
+                LexItem::LessThan => Some((left < right) as i128),
+                LexItem::GreaterThan => Some((left > right) as i128),
+                LexItem::LessOrEqual => Some((left <= right) as i128),
+                LexItem::GreaterOrEqual => Some((left >= right) as i128),
+                LexItem::Equals => Some((left == right) as i128),
+                LexItem::NotEqual => Some((left != right) as i128),
+                LexItem::And => Some(left & right),
+                LexItem::Xor => Some(left ^ right),
+                LexItem::Or => Some(left | right),
+                LexItem::LogicalAnd | LexItem::LogicalOr => Some((right != 0) as i128),
+                _ => None,
+            }
+        }
+        // A cast, whose type is not checked
+        4 if token(0) == Some(&LexItem::LeftParen) => operand(3),
+        4 if token(0) == Some(&LexItem::Sizeof) || token(0) == Some(&LexItem::Alignof) => {
+            let sizeof = token(0) == Some(&LexItem::Sizeof);
+            if !sizeof {
+                context.not_in_c89("_Alignof", node);
+            }
+            let (_, typ, declarator) = read_type_name(Rc::clone(&node.children[2]), context);
+            let (size, align) = size_and_align(&declarator.apply(typ), context)?;
+            Some(i128::from(if sizeof { size } else { align }))
+        }
+        5 if token(1) == Some(&LexItem::Question) => {
+            if operand(0)? != 0 {
+                operand(2)
+            } else {
+                operand(4)
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Checks a `_Static_assert`, returning its condition and message unless the
+/// condition is not an integer constant
+pub(super) fn read_static_assert(
+    node: Rc<ParseNode>,
+    context: &mut Context,
+) -> Option<(i128, Vec<u8>)> {
+    require_non_terminal!(node, NonTerminalType::StaticAssert);
+    require_len!(node, |len| len == 7);
+    require_terminal!(node, 0, LexItem::StaticAssert);
+    context.not_in_c89("_Static_assert", &node);
+
+    let message = match &node.children[4].node_type {
+        ParseNodeType::Terminal(LexSuccess {
+            item: LexItem::StringLiteral(message),
+            ..
+        }) => message.clone(),
+        _ => unreachable!(),
+    };
+    let condition = &node.children[2];
+    match evaluate(condition, context) {
+        Some(value) => {
+            if value == 0 {
+                context.errors.push(SemanticError {
+                    error_type: SemanticErrorType::StaticAssertionFailed(
+                        String::from_utf8_lossy(&message).into_owned(),
+                    ),
+                    span: span_of(&node),
+                });
+            }
+            Some((value, message))
+        }
+        None => {
+            context.errors.push(SemanticError {
+                error_type: SemanticErrorType::NotConstant,
+                span: span_of(condition),
+            });
+            None
+        }
+    }
+}
+
+fn integer(number: &NumberType) -> Option<i128> {
+    Some(match *number {
+        NumberType::SignedChar(value) => value.into(),
+        NumberType::UnsignedChar(value) => value.into(),
+        NumberType::SignedShort(value) => value.into(),
+        NumberType::UnsignedShort(value) => value.into(),
+        NumberType::SignedInt(value) => value.into(),
+        NumberType::UnsignedInt(value) => value.into(),
+        NumberType::SignedLong(value) => value.into(),
+        NumberType::UnsignedLong(value) => value.into(),
+        NumberType::SignedLongLong(value) => value,
+        NumberType::UnsignedLongLong(value) => i128::try_from(value).ok()?,
+        NumberType::Float(_) | NumberType::Double(_) => return None,
+    })
+}
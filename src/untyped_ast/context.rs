@@ -0,0 +1,43 @@
+use crate::parse::types::ParseNode;
+use crate::standard::Standard;
+use crate::untyped_ast::errors::{span_of, SemanticError, SemanticErrorType};
+use crate::untyped_ast::types::{Attribute, Type};
+use std::collections::HashMap;
+
+/// What has been learned about the translation unit while building its AST
+#[derive(Debug, Default)]
+pub(super) struct Context {
+    pub(super) errors: Vec<SemanticError>,
+    /// The value of every enumerator declared so far
+    pub(super) constants: HashMap<String, i64>,
+    /// Every struct, union and enum declared with a body so far, by tag
+    pub(super) tags: HashMap<String, Type>,
+    /// The type of every typedef name in scope
+    pub(super) typedefs: HashMap<String, Type>,
+    /// The attributes of every ordinary name declared with some so far, which
+    /// affect its uses
+    pub(super) attributes: HashMap<String, Vec<Attribute>>,
+    /// Whether the declarations being read are in a block or a parameter
+    /// list, where the size of an array need not be constant
+    pub(super) block_scope: bool,
+    /// The dialect being compiled. The grammar of C89 extends that of C11, so
+    /// the keywords C99 and C11 added, `long long`, designated initializers,
+    /// compound literals, variable length arrays, attributes and declarations
+    /// after statements parse there and are reported as errors. Newer forms
+    /// that the lexer or preprocessor handles, like `//` comments, are not.
+    pub(super) standard: Standard,
+}
+
+impl Context {
+    /// Reports `construct`, which `node` uses, if it is newer than the C89
+    /// being compiled. Nodes read more than once are reported once.
+    pub(super) fn not_in_c89(&mut self, construct: &str, node: &ParseNode) {
+        let error = SemanticError {
+            error_type: SemanticErrorType::NotInC89(construct.to_string()),
+            span: span_of(node),
+        };
+        if self.standard == Standard::C89 && !self.errors.contains(&error) {
+            self.errors.push(error);
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use crate::lex::errors::LexSuccess;
+use crate::lex::types::LexItem;
+use crate::parse::types::NonTerminalType;
+use crate::parse::types::ParseNode;
+use crate::parse::types::ParseNodeType;
+use crate::untyped_ast::attribute::{
+    prepend_attributes, read_asm_label, read_attribute_specifier, read_attribute_specifiers,
+    read_attributes,
+};
+use crate::untyped_ast::constant::evaluate;
+use crate::untyped_ast::context::Context;
+use crate::untyped_ast::errors::{span_of, SemanticError, SemanticErrorType};
+use crate::untyped_ast::expression::read_expression;
+use crate::untyped_ast::registry::{check_attributes, Target};
+use crate::untyped_ast::specifiers::{
+    check_specifiers, forbid_alignment, read_qualifiers, read_type,
+};
+use crate::untyped_ast::types::{
+    Attribute, BaseType, Prototype, Qualifiers, RValue, Specifiers, StorageClass, Type,
+};
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+/// One step from a declared name out to the type it was declared with
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Derivation {
+    Pointer(Qualifiers),
+    Array(Option<usize>),
+    /// An array whose size is the value of an expression
+    VariableArray(RValue),
+    /// The parameters of a function and its prototype, which `()` and an
+    /// identifier list do not give
+    Function(Vec<(Type, Option<String>)>, Prototype),
+}
+
+/// The name a declarator declares, if any, with its derivations starting from
+/// the one closest to the name: `*a[10]` is `[Array(10), Pointer]`, an array
+/// of pointers.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(super) struct Declarator {
+    pub(super) name: Option<String>,
+    pub(super) derivations: Vec<Derivation>,
+    /// Attributes following the declared name, its suffixes or the whole
+    /// declarator
+    pub(super) attributes: Vec<Attribute>,
+    pub(super) asm_label: Option<Vec<u8>>,
+}
+
+impl Declarator {
+    /// The type of the declared name, given the type it was declared with
+    pub(super) fn apply(&self, base: Type) -> Type {
+        apply(base, &self.derivations)
+    }
+
+    /// The specifiers of the declared name, given the specifiers of the
+    /// declaration, with the attributes of the declarator after theirs
+    pub(super) fn specifiers(&self, specifiers: &Specifiers) -> Specifiers {
+        let mut specifiers = specifiers.clone();
+        specifiers
+            .attributes
+            .extend(self.attributes.iter().cloned());
+        if self.asm_label.is_some() {
+            specifiers.asm_label = self.asm_label.clone();
+        }
+        specifiers
+    }
+}
+
+/// Wraps `base` in `derivations`, which start from the declared name.
+///
+/// Functions are represented by `BaseType::FunctionPtr`, so the pointer right
+/// next to a function is the same type as the function, with the qualifiers
+/// of the pointer. Any pointers further out point to that.
+pub(super) fn apply(base: Type, derivations: &[Derivation]) -> Type {
+    let mut typ = base;
+    let mut function = false;
+    for derivation in derivations.iter().rev() {
+        typ = match derivation {
+            Derivation::Pointer(qualifiers) if function => Type {
+                qualifiers: *qualifiers,
+                ..typ
+            },
+            Derivation::Pointer(qualifiers) => Type {
+                base: BaseType::Pointer(Box::new(typ)),
+                qualifiers: *qualifiers,
+            },
+            Derivation::Array(size) => Type::new(BaseType::Array(Box::new(typ), *size)),
+            Derivation::VariableArray(size) => Type::new(BaseType::VariableArray(
+                Box::new(typ),
+                Box::new(size.clone()),
+            )),
+            Derivation::Function(args, prototype) => Type::new(BaseType::FunctionPtr(
+                args.iter().map(|arg| arg.0.clone()).collect(),
+                Box::new(typ),
+                prototype.is_variadic(),
+            )),
+        };
+        function = matches!(derivation, Derivation::Function(..));
+    }
+    typ
+}
+
+pub(super) fn read_type_with_identifier(
+    node: Rc<ParseNode>,
+    context: &mut Context,
+) -> (Specifiers, Type, Declarator) {
+    require_non_terminal!(node, NonTerminalType::TypeWithIdentifier);
+    require_len!(node, |len| len == 2);
+
+    let (specifiers, typ) = read_type(node.children[0].clone(), context);
+    let declarator = read_declarator(node.children[1].clone(), context);
+
+    (specifiers, typ, declarator)
+}
+
+pub(super) fn read_type_with_maybe_identifier(
+    node: Rc<ParseNode>,
+    context: &mut Context,
+) -> (Specifiers, Type, Declarator) {
+    require_non_terminal!(node, NonTerminalType::TypeWithMaybeIdentifier);
+    require_len!(node, |len| len == 1 || len == 2);
+
+    match node.children[0].node_type {
+        ParseNodeType::NonTerminal(NonTerminalType::AttributeSpecifier) => {
+            let leading = read_attribute_specifier(node.children[0].clone(), context);
+            let (mut specifiers, typ, declarator) =
+                read_type_with_maybe_identifier(node.children[1].clone(), context);
+            prepend_attributes(&mut specifiers, leading);
+            (specifiers, typ, declarator)
+        }
+        ParseNodeType::NonTerminal(NonTerminalType::TypeWithIdentifier) => {
+            read_type_with_identifier(node.children[0].clone(), context)
+        }
+        ParseNodeType::NonTerminal(NonTerminalType::TypeName) => {
+            read_type_name(node.children[0].clone(), context)
+        }
+        _ => unreachable!(),
+    }
+}
+
+pub(super) fn read_type_name(
+    node: Rc<ParseNode>,
+    context: &mut Context,
+) -> (Specifiers, Type, Declarator) {
+    require_non_terminal!(node, NonTerminalType::TypeName);
+    require_len!(node, |len| len == 1 || len == 2);
+
+    let (specifiers, typ) = read_type(node.children[0].clone(), context);
+    let declarator = match node.children.get(1) {
+        Some(declarator) => read_declarator(declarator.clone(), context),
+        None => Declarator::default(),
+    };
+
+    (specifiers, typ, declarator)
+}
+
+/// Reads any kind of declarator, abstract or not
+pub(super) fn read_declarator(node: Rc<ParseNode>, context: &mut Context) -> Declarator {
+    match node.node_type {
+        ParseNodeType::NonTerminal(NonTerminalType::Declarator)
+        | ParseNodeType::NonTerminal(NonTerminalType::AbstractDeclarator)
+        | ParseNodeType::NonTerminal(NonTerminalType::DirectDeclarator)
+        | ParseNodeType::NonTerminal(NonTerminalType::DirectAbstractDeclarator)
+        | ParseNodeType::NonTerminal(NonTerminalType::FunctionDeclarator)
+        | ParseNodeType::NonTerminal(NonTerminalType::IdentifierListDeclarator) => {}
+        _ => unreachable!(
+            "Attempted to treat {:?} node as a declarator while building untyped AST",
+            node.node_type
+        ),
+    }
+
+    if let ParseNodeType::Terminal(_) = node.children[0].node_type {
+        if node.children.len() == 1 {
+            return Declarator {
+                name: Some(read_identifier(node.children[0].clone())),
+                ..Declarator::default()
+            };
+        }
+    }
+
+    let mut declarator = Declarator::default();
+    let mut pointers = Vec::new();
+    let mut suffix = None;
+    let mut attributes = Vec::new();
+    let mut asm_label = None;
+    let mut identifiers = Vec::new();
+    for (index, child) in node.children.iter().enumerate() {
+        match &child.node_type {
+            ParseNodeType::NonTerminal(NonTerminalType::Pointer) => {
+                pointers = read_pointer(child.clone(), &mut attributes, context)
+            }
+            ParseNodeType::NonTerminal(NonTerminalType::Args) => {
+                let (args, variadic) = read_args(child.clone(), context);
+                let prototype = match variadic {
+                    true => Prototype::Variadic,
+                    false => Prototype::Fixed,
+                };
+                suffix = Some(Derivation::Function(args, prototype))
+            }
+            ParseNodeType::NonTerminal(NonTerminalType::Attribute) => {
+                attributes.extend(read_attributes(child.clone(), context))
+            }
+            ParseNodeType::NonTerminal(NonTerminalType::AttributeSpecifier) => {
+                attributes.extend(read_attribute_specifier(child.clone(), context))
+            }
+            ParseNodeType::NonTerminal(NonTerminalType::AsmLabel) => {
+                asm_label = Some(read_asm_label(child.clone()))
+            }
+            ParseNodeType::NonTerminal(NonTerminalType::ExpressionWithoutComma) => {
+                suffix = Some(read_array(child.clone(), context))
+            }
+            ParseNodeType::NonTerminal(_) => declarator = read_declarator(child.clone(), context),
+            // The identifier list of an old-style definition
+            ParseNodeType::Terminal(LexSuccess {
+                item: LexItem::Identifier(name),
+                ..
+            }) => identifiers.push(name.clone()),
+            ParseNodeType::Terminal(token) if suffix.is_none() => {
+                let next = node.children.get(index + 1).map(|next| &next.node_type);
+                let closes = |item| match next {
+                    Some(ParseNodeType::Terminal(next)) => next.item == item,
+                    _ => false,
+                };
+                if token.item == LexItem::LeftBracket && closes(LexItem::RightBracket) {
+                    suffix = Some(Derivation::Array(None));
+                } else if token.item == LexItem::LeftParen && closes(LexItem::RightParen) {
+                    suffix = Some(Derivation::Function(Vec::new(), Prototype::Missing));
+                }
+            }
+            _ => {}
+        }
+    }
+    // whose parameters are int until they are declared
+    if !identifiers.is_empty() {
+        let int = Type::new(BaseType::SignedInt);
+        let parameters = identifiers
+            .into_iter()
+            .map(|name| (int.clone(), Some(name)));
+        suffix = Some(Derivation::Function(
+            parameters.collect(),
+            Prototype::Missing,
+        ));
+    }
+
+    declarator.derivations.extend(suffix);
+    declarator.derivations.extend(pointers);
+    declarator.attributes.extend(attributes);
+    if asm_label.is_some() {
+        declarator.asm_label = asm_label;
+    }
+    declarator
+}
+
+/// Reads a list of `Declarators`, in order
+pub(super) fn read_declarators(node: Rc<ParseNode>, context: &mut Context) -> Vec<Declarator> {
+    require_non_terminal!(node, NonTerminalType::Declarators);
+    require_len!(node, |len| len == 1 || len == 3);
+
+    let mut declarators = match node.children.len() {
+        3 => read_declarators(node.children[0].clone(), context),
+        _ => Vec::new(),
+    };
+    declarators.push(read_declarator(
+        node.children[node.children.len() - 1].clone(),
+        context,
+    ));
+    declarators
+}
+
+/// The pointers of a declarator, starting from the one closest to the name.
+/// Types have no attributes here, so those after a `*` are added to
+/// `attributes` and belong to the declared name.
+fn read_pointer(
+    node: Rc<ParseNode>,
+    attributes: &mut Vec<Attribute>,
+    context: &mut Context,
+) -> Vec<Derivation> {
+    require_non_terminal!(node, NonTerminalType::Pointer);
+    require_len!(node, |len| (2..=4).contains(&len));
+    require_terminal!(node, 0, LexItem::Mul);
+
+    let mut children = node.children[1..].iter().peekable();
+    if let Some(specifiers) = children.next_if(|child| {
+        child.node_type == ParseNodeType::NonTerminal(NonTerminalType::AttributeSpecifiers)
+    }) {
+        attributes.extend(read_attribute_specifiers(specifiers.clone(), context));
+    }
+    let qualifiers = read_qualifiers(children.next().unwrap().clone(), context);
+    let mut derivations = match children.next() {
+        Some(inner) => read_pointer(inner.clone(), attributes, context),
+        None => Vec::new(),
+    };
+    derivations.push(Derivation::Pointer(qualifiers));
+    derivations
+}
+
+/// An array with the size `node`, which must be an integer constant outside
+/// of blocks and parameter lists
+fn read_array(node: Rc<ParseNode>, context: &mut Context) -> Derivation {
+    let error_type = match evaluate(&node, context) {
+        Some(size) if size < 0 => SemanticErrorType::NegativeArraySize(size),
+        Some(size) => return Derivation::Array(usize::try_from(size).ok()),
+        None if context.block_scope => {
+            context.not_in_c89("a variable length array", &node);
+            return Derivation::VariableArray(read_expression(&node, context));
+        }
+        None => SemanticErrorType::NotConstant,
+    };
+    context.errors.push(SemanticError {
+        error_type,
+        span: span_of(&node),
+    });
+    Derivation::Array(None)
+}
+
+pub(super) fn read_identifier(node: Rc<ParseNode>) -> String {
+    match node.node_type.clone() {
+        ParseNodeType::Terminal(succ) => match succ.item {
+            LexItem::Identifier(s) | LexItem::TypedefName(s) => s,
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    }
+}
+
+/// Reads a parameter list, where a lone `void` means there are no parameters,
+/// and whether it ends in `...`
+pub(super) fn read_args(
+    node: Rc<ParseNode>,
+    context: &mut Context,
+) -> (Vec<(Type, Option<String>)>, bool) {
+    require_non_terminal!(node, NonTerminalType::Args);
+    require_len!(node, |len| len == 1 || len == 3);
+
+    let parameter = node.children[0].clone();
+    let outer = std::mem::replace(&mut context.block_scope, true);
+    let (specifiers, typ, declarator) = read_type_with_maybe_identifier(parameter.clone(), context);
+    context.block_scope = outer;
+    check_specifiers(
+        &specifiers,
+        &[StorageClass::Register],
+        false,
+        span_of(&parameter),
+        &mut context.errors,
+    );
+    forbid_alignment(&specifiers, span_of(&parameter), &mut context.errors);
+    check_attributes(
+        declarator.specifiers(&specifiers).attributes,
+        Target::Declaration,
+        span_of(&parameter),
+        &mut context.errors,
+    );
+    let typ = declarator.apply(typ);
+    let lone = node.children.len() == 1;
+    if typ.base == BaseType::Void {
+        if lone && declarator.name.is_none() && typ.qualifiers == Qualifiers::default() {
+            return (Vec::new(), false);
+        }
+        context.errors.push(SemanticError {
+            error_type: SemanticErrorType::VoidObject,
+            span: span_of(&parameter),
+        });
+    }
+    let mut args = vec![(typ, declarator.name)];
+    let variadic = match node.children.get(2).map(|rest| &rest.node_type) {
+        Some(ParseNodeType::Terminal(_)) => true,
+        Some(_) => {
+            let (rest, variadic) = read_args(node.children[2].clone(), context);
+            args.extend(rest);
+            variadic
+        }
+        None => false,
+    };
+    (args, variadic)
+}
@@ -0,0 +1,102 @@
+use crate::lex::types::LexItem;
+use crate::parse::types::NonTerminalType;
+use crate::parse::types::ParseNode;
+use crate::parse::types::ParseNodeType;
+use crate::untyped_ast::attribute::read_attribute_specifiers;
+use crate::untyped_ast::constant::evaluate;
+use crate::untyped_ast::context::Context;
+use crate::untyped_ast::declarator::read_identifier;
+use crate::untyped_ast::errors::{span_of, SemanticError, SemanticErrorType};
+use crate::untyped_ast::registry::{check_attributes, record_attributes, Target};
+use crate::untyped_ast::types::{BaseType, Type};
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+/// Reads an enum declaration or a reference to an enum tag, registering the
+/// value of every enumerator as a constant
+pub(super) fn read_enum(node: Rc<ParseNode>, context: &mut Context) -> Type {
+    require_non_terminal!(node, NonTerminalType::EnumDeclaration);
+    require_len!(node, |len| (2..=6).contains(&len));
+    require_terminal!(node, 0, LexItem::Enum);
+
+    let mut tag = None;
+    let mut enumerators = Vec::new();
+    for child in &node.children {
+        match child.node_type {
+            ParseNodeType::NonTerminal(NonTerminalType::Tag) => {
+                tag = Some(read_identifier(child.children[0].clone()))
+            }
+            ParseNodeType::NonTerminal(NonTerminalType::Enumerators) => {
+                read_enumerators(child.clone(), &mut enumerators, context)
+            }
+            _ => {}
+        }
+    }
+    let typ = Type::new(BaseType::Enum(tag.clone(), enumerators));
+    if let (Some(tag), BaseType::Enum(_, enumerators)) = (tag, &typ.base) {
+        if !enumerators.is_empty() {
+            context.tags.insert(tag, typ.clone());
+        }
+    }
+    typ
+}
+
+fn read_enumerators(
+    node: Rc<ParseNode>,
+    enumerators: &mut Vec<(String, i64)>,
+    context: &mut Context,
+) {
+    require_non_terminal!(node, NonTerminalType::Enumerators);
+    require_len!(node, |len| len == 1 || len == 3);
+
+    if node.children.len() == 3 {
+        read_enumerators(node.children[0].clone(), enumerators, context);
+    }
+    let enumerator = node.children[node.children.len() - 1].clone();
+    require_non_terminal!(enumerator, NonTerminalType::Enumerator);
+    require_len!(enumerator, |len| (1..=4).contains(&len));
+
+    let name = read_identifier(enumerator.children[0].clone());
+    let (attributes, expression) = match enumerator.children.len() {
+        2 | 4 => (
+            read_attribute_specifiers(enumerator.children[1].clone(), context),
+            enumerator.children.get(3),
+        ),
+        _ => (Vec::new(), enumerator.children.get(2)),
+    };
+    let attributes = check_attributes(
+        attributes,
+        Target::Declaration,
+        span_of(&enumerator),
+        &mut context.errors,
+    );
+    record_attributes(&name, &attributes, context);
+    let previous = enumerators.last().map(|(_, value)| *value);
+    let value = match expression {
+        Some(expression) => match evaluate(expression, context) {
+            Some(value) => value,
+            None => {
+                context.errors.push(SemanticError {
+                    error_type: SemanticErrorType::NotConstant,
+                    span: span_of(expression),
+                });
+                0
+            }
+        },
+        // Without a value, an enumerator is one more than the previous one
+        None => previous.map_or(0, |previous| i128::from(previous) + 1),
+    };
+    // The value of an enumerator must fit in an int
+    let value = match i32::try_from(value) {
+        Ok(value) => i64::from(value),
+        Err(_) => {
+            context.errors.push(SemanticError {
+                error_type: SemanticErrorType::EnumeratorOutOfRange(name.clone()),
+                span: span_of(&enumerator),
+            });
+            0
+        }
+    };
+    context.constants.insert(name.clone(), value);
+    enumerators.push((name, value));
+}
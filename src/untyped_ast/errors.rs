@@ -0,0 +1,229 @@
+use crate::lex::errors::{Location, Span};
+use crate::lex::types::LexItem;
+use crate::parse::errors::token_name;
+use crate::parse::types::{ParseNode, ParseNodeType};
+use std::fmt::{self, Display, Formatter};
+use std::mem::discriminant;
+
+/// A program which parses but breaks one of the constraints of C, or which is
+/// suspicious enough to warn about
+#[derive(Clone, Debug, PartialEq)]
+pub struct SemanticError {
+    pub error_type: SemanticErrorType,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SemanticErrorType {
+    DuplicateSpecifier(LexItem),
+    ConflictingStorageClasses(LexItem, LexItem),
+    /// Type specifiers which do not name a type together, such as `long char`
+    InvalidTypeSpecifiers(Vec<LexItem>),
+    RestrictWithoutPointer,
+    FunctionSpecifierOnObject(LexItem),
+    StorageClassNotAllowed(LexItem),
+    /// The left operand of an assignment, increment or `&` which is not an
+    /// lvalue
+    NotAnLValue,
+    /// A parameter of a function definition without a name
+    UnnamedParameter,
+    /// A declaration before the body of an old-style definition of a name
+    /// which is not in its identifier list
+    NotAParameter(String),
+    /// A parameter named twice in the parameters of a definition, or declared
+    /// twice before the body of an old-style one
+    DuplicateParameter(String),
+    /// A construct of C99 or C11, such as `_Generic`, in C89
+    NotInC89(String),
+    /// An object or parameter declared with type `void`
+    VoidObject,
+    /// An expression which must be an integer constant but is not one
+    NotConstant,
+    NegativeArraySize(i128),
+    /// An enumerator whose value does not fit in an int
+    EnumeratorOutOfRange(String),
+    /// A declaration without a declarator which does not declare a tag either
+    DeclaresNothing,
+    InvalidBitfieldWidth(i128),
+    /// A bitfield whose type is not `_Bool` or an integer type
+    InvalidBitfieldType,
+    /// A bitfield wider than its type, with its width
+    BitfieldTooWide(u32),
+    DuplicateMember(String),
+    /// A `_Static_assert` whose condition is zero, with its message
+    StaticAssertionFailed(String),
+    /// An alignment which is not a power of two
+    InvalidAlignment(i128),
+    /// `_Alignas` on a typedef, function, parameter, bitfield or `register`
+    AlignmentNotAllowed,
+    UnknownAttribute(String),
+    /// A known attribute where it has no meaning, such as `nodiscard` on a
+    /// variable
+    AttributeIgnored(String),
+    InvalidAttributeArguments(String),
+    /// `[[fallthrough]]` which is not a null statement right before a case
+    /// label
+    MisplacedFallthrough,
+    /// The result of a `nodiscard` function, with its reason, is not used
+    UnusedResult(String, Option<String>),
+    /// A use of a deprecated name, with the reason it was deprecated
+    Deprecated(String, Option<String>),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl SemanticErrorType {
+    /// Whether the program is still valid C
+    pub fn severity(&self) -> Severity {
+        match self {
+            SemanticErrorType::UnknownAttribute(_)
+            | SemanticErrorType::AttributeIgnored(_)
+            | SemanticErrorType::UnusedResult(..)
+            | SemanticErrorType::Deprecated(..) => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+fn name(item: &LexItem) -> String {
+    token_name(discriminant(item))
+}
+
+impl Display for SemanticErrorType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SemanticErrorType::DuplicateSpecifier(item) => write!(f, "duplicate {}", name(item)),
+            SemanticErrorType::ConflictingStorageClasses(first, second) => write!(
+                f,
+                "conflicting storage classes {} and {}",
+                name(first),
+                name(second)
+            ),
+            SemanticErrorType::InvalidTypeSpecifiers(items) => {
+                let names: Vec<String> = items.iter().map(name).collect();
+                write!(
+                    f,
+                    "invalid combination of type specifiers {}",
+                    names.join(" ")
+                )
+            }
+            SemanticErrorType::RestrictWithoutPointer => {
+                f.write_str("'restrict' requires a pointer type")
+            }
+            SemanticErrorType::FunctionSpecifierOnObject(item) => {
+                write!(f, "{} can only be used on functions", name(item))
+            }
+            SemanticErrorType::StorageClassNotAllowed(item) => {
+                write!(f, "storage class {} is not allowed here", name(item))
+            }
+            SemanticErrorType::NotAnLValue => f.write_str("expression is not assignable"),
+            SemanticErrorType::UnnamedParameter => {
+                f.write_str("parameter name omitted in function definition")
+            }
+            SemanticErrorType::NotAParameter(name) => {
+                write!(f, "declaration of '{}' which is not a parameter", name)
+            }
+            SemanticErrorType::DuplicateParameter(name) => {
+                write!(f, "redefinition of parameter '{}'", name)
+            }
+            SemanticErrorType::NotInC89(construct) => {
+                write!(f, "{} is not part of C89", construct)
+            }
+            SemanticErrorType::VoidObject => f.write_str("variable or parameter declared void"),
+            SemanticErrorType::NotConstant => {
+                f.write_str("expression is not an integer constant expression")
+            }
+            SemanticErrorType::NegativeArraySize(size) => {
+                write!(f, "array has negative size {}", size)
+            }
+            SemanticErrorType::EnumeratorOutOfRange(name) => {
+                write!(f, "value of enumerator '{}' is out of range", name)
+            }
+            SemanticErrorType::DeclaresNothing => {
+                f.write_str("declaration does not declare anything")
+            }
+            SemanticErrorType::InvalidBitfieldWidth(width) => {
+                write!(f, "invalid bitfield width {}", width)
+            }
+            SemanticErrorType::InvalidBitfieldType => {
+                f.write_str("bitfield does not have an integer type")
+            }
+            SemanticErrorType::BitfieldTooWide(width) => {
+                write!(f, "bitfield width {} exceeds the width of its type", width)
+            }
+            SemanticErrorType::DuplicateMember(name) => write!(f, "duplicate member '{}'", name),
+            SemanticErrorType::StaticAssertionFailed(message) => {
+                write!(f, "static assertion failed: \"{}\"", message)
+            }
+            SemanticErrorType::InvalidAlignment(alignment) => {
+                write!(f, "requested alignment {} is not a power of two", alignment)
+            }
+            SemanticErrorType::AlignmentNotAllowed => f.write_str("'_Alignas' is not allowed here"),
+            SemanticErrorType::UnknownAttribute(name) => {
+                write!(f, "unknown attribute '{}' ignored", name)
+            }
+            SemanticErrorType::AttributeIgnored(name) => {
+                write!(f, "attribute '{}' does not apply here and is ignored", name)
+            }
+            SemanticErrorType::InvalidAttributeArguments(name) => {
+                write!(f, "wrong number of arguments to attribute '{}'", name)
+            }
+            SemanticErrorType::MisplacedFallthrough => f.write_str(
+                "fallthrough attribute must be a null statement right before a case label",
+            ),
+            SemanticErrorType::UnusedResult(name, reason) => {
+                write!(
+                    f,
+                    "ignoring return value of '{}', declared with attribute nodiscard",
+                    name
+                )?;
+                reason
+                    .iter()
+                    .try_for_each(|reason| write!(f, ": {}", reason))
+            }
+            SemanticErrorType::Deprecated(name, reason) => {
+                write!(f, "'{}' is deprecated", name)?;
+                reason
+                    .iter()
+                    .try_for_each(|reason| write!(f, ": {}", reason))
+            }
+        }
+    }
+}
+
+impl Display for SemanticError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.error_type.fmt(f)
+    }
+}
+
+/// The span of every token below `node`
+pub(super) fn span_of(node: &ParseNode) -> Span {
+    let mut start = None;
+    let mut end = Location { character: 0 };
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        if let ParseNodeType::Terminal(token) = &node.node_type {
+            start = start.or(Some(token.location));
+            end = token.end;
+        }
+        stack.extend(node.children.iter().rev().map(|child| &**child));
+    }
+    Span {
+        start: start.unwrap_or(end),
+        end,
+    }
+}
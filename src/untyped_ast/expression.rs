@@ -0,0 +1,293 @@
+use crate::lex::errors::LexSuccess;
+use crate::lex::types::LexItem;
+use crate::parse::types::NonTerminalType;
+use crate::parse::types::ParseNode;
+use crate::parse::types::ParseNodeType;
+use crate::untyped_ast::context::Context;
+use crate::untyped_ast::declarator::{read_identifier, read_type_name};
+use crate::untyped_ast::errors::{span_of, SemanticError, SemanticErrorType};
+use crate::untyped_ast::initializer::read_initializer_list;
+use crate::untyped_ast::registry::{find, Effect};
+use crate::untyped_ast::statement::read_block;
+use crate::untyped_ast::types::{LValue, RValue, Type};
+use std::rc::Rc;
+
+/// Reads any kind of expression. Names are not resolved yet, so every
+/// identifier is a variable.
+pub(super) fn read_expression(node: &Rc<ParseNode>, context: &mut Context) -> RValue {
+    let token = |index: usize| match node.children.get(index).map(|child| &child.node_type) {
+        Some(ParseNodeType::Terminal(token)) => Some(&token.item),
+        _ => None,
+    };
+    let operand = |index: usize, context: &mut Context| {
+        Box::new(read_expression(&node.children[index], context))
+    };
+
+    if let ParseNodeType::Terminal(LexSuccess { item, .. }) = &node.node_type {
+        return match item {
+            LexItem::NumericLiteral(number) => RValue::NumberLiteral(number.clone()),
+            LexItem::StringLiteral(string) => RValue::StringLiteral(string.clone()),
+            LexItem::Identifier(name) => {
+                warn_if_deprecated(name, node, context);
+                RValue::LValue(LValue::Variable(name.clone()))
+            }
+            _ => unreachable!(),
+        };
+    }
+
+    match node.children.len() {
+        1 => read_expression(&node.children[0], context),
+        2 if token(0) == Some(&LexItem::Sizeof) => RValue::SizeofValue(operand(1, context)),
+        // `__extension__` only silences warnings
+        2 if token(0) == Some(&LexItem::Extension) => read_expression(&node.children[1], context),
+        2 if node.children[0].node_type
+            == ParseNodeType::NonTerminal(NonTerminalType::Operator2) =>
+        {
+            read_prefix(node, context)
+        }
+        2 => read_postfix(node, context),
+        3 if token(0) == Some(&LexItem::LeftParen) => match node.children[1].node_type {
+            ParseNodeType::NonTerminal(NonTerminalType::Block) => {
+                RValue::StatementExpression(read_block(node.children[1].clone(), context))
+            }
+            _ => read_expression(&node.children[1], context),
+        },
+        3 => read_binary(node, context),
+        4 if token(0) == Some(&LexItem::Sizeof) => RValue::SizeofType(read_type(node, 2, context)),
+        4 if token(0) == Some(&LexItem::Alignof) => {
+            context.not_in_c89("_Alignof", node);
+            RValue::AlignofType(read_type(node, 2, context))
+        }
+        4 if token(0) == Some(&LexItem::LeftParen) => {
+            let typ = read_type(node, 1, context);
+            RValue::Cast(typ, operand(3, context))
+        }
+        4 if token(1) == Some(&LexItem::LeftBracket) => {
+            let array = operand(0, context);
+            RValue::LValue(LValue::Subscript(array, operand(2, context)))
+        }
+        4 => {
+            let function = operand(0, context);
+            let mut arguments = Vec::new();
+            read_call_arguments(&node.children[2], &mut arguments, context);
+            RValue::FunctionCall(function, arguments)
+        }
+        5 => {
+            let condition = operand(0, context);
+            let then = operand(2, context);
+            RValue::Ternary(condition, then, operand(4, context))
+        }
+        6 if token(0) == Some(&LexItem::Generic) => read_generic(node, context),
+        // A compound literal, maybe with a trailing comma
+        6 | 7 => {
+            context.not_in_c89("a compound literal", node);
+            RValue::CompoundLiteral(
+                read_type(node, 1, context),
+                read_initializer_list(node.children[4].clone(), context),
+            )
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Reads a `TypeName` child
+fn read_type(node: &Rc<ParseNode>, index: usize, context: &mut Context) -> Type {
+    let (_, typ, declarator) = read_type_name(node.children[index].clone(), context);
+    declarator.apply(typ)
+}
+
+/// The token of an operator, which is either a token or an `Operator2` node
+/// holding one
+fn operator(node: &Rc<ParseNode>) -> &LexItem {
+    let node = node.children.first().unwrap_or(node);
+    match &node.node_type {
+        ParseNodeType::Terminal(token) => &token.item,
+        _ => unreachable!(),
+    }
+}
+
+/// The object an expression designates, if it is an lvalue
+fn lvalue(value: RValue, node: &Rc<ParseNode>, context: &mut Context) -> LValue {
+    match value {
+        RValue::LValue(lvalue) => lvalue,
+        value => {
+            context.errors.push(SemanticError {
+                error_type: SemanticErrorType::NotAnLValue,
+                span: span_of(node),
+            });
+            LValue::Error(Box::new(value))
+        }
+    }
+}
+
+/// Builds an assignment from its target and value
+type Assignment = fn(Box<LValue>, Box<RValue>) -> RValue;
+
+fn read_binary(node: &Rc<ParseNode>, context: &mut Context) -> RValue {
+    let left = read_expression(&node.children[0], context);
+    let right = Box::new(read_expression(&node.children[2], context));
+    let item = operator(&node.children[1]);
+    let assignment: Option<Assignment> = match item {
+        LexItem::Assign => Some(RValue::Assign),
+        LexItem::AddAssign => Some(RValue::AddAssign),
+        LexItem::SubAssign => Some(RValue::SubAssign),
+        LexItem::MulAssign => Some(RValue::MulAssign),
+        LexItem::DivAssign => Some(RValue::DivAssign),
+        LexItem::ModAssign => Some(RValue::ModAssign),
+        LexItem::AndAssign => Some(RValue::BitwiseAndAssign),
+        LexItem::OrAssign => Some(RValue::BitwiseOrAssign),
+        LexItem::XorAssign => Some(RValue::BitwiseXorAssign),
+        LexItem::LShiftAssign => Some(RValue::BitwiseLeftShiftAssign),
+        LexItem::RShiftAssign => Some(RValue::BitwiseRightShiftAssign),
+        _ => None,
+    };
+    if let Some(assignment) = assignment {
+        let target = lvalue(left, &node.children[0], context);
+        return assignment(Box::new(target), right);
+    }
+    let left = Box::new(left);
+    match item {
+        LexItem::Comma => RValue::Comma(left, right),
+        LexItem::Mul => RValue::Mul(left, right),
+        LexItem::Div => RValue::Div(left, right),
+        LexItem::Mod => RValue::Mod(left, right),
+        LexItem::Plus => RValue::Add(left, right),
+        LexItem::Minus => RValue::Sub(left, right),
+        LexItem::LShift => RValue::BitwiseLeftShift(left, right),
+        LexItem::RShift => RValue::BitwiseRightShift(left, right),
+        LexItem::LessThan => RValue::Less(left, right),
+        LexItem::GreaterThan => RValue::Greater(left, right),
+        LexItem::LessOrEqual => RValue::LessOrEqual(left, right),
+        LexItem::GreaterOrEqual => RValue::GreaterOrEqual(left, right),
+        LexItem::Equals => RValue::Equals(left, right),
+        LexItem::NotEqual => RValue::NotEquals(left, right),
+        LexItem::And => RValue::BitwiseAnd(left, right),
+        LexItem::Xor => RValue::BitwiseXor(left, right),
+        LexItem::Or => RValue::BitwiseOr(left, right),
+        LexItem::LogicalAnd => RValue::LogicalAnd(left, right),
+        LexItem::LogicalOr => RValue::LogicalOr(left, right),
+        _ => unreachable!(),
+    }
+}
+
+fn read_prefix(node: &Rc<ParseNode>, context: &mut Context) -> RValue {
+    let value = read_expression(&node.children[1], context);
+    match operator(&node.children[0]) {
+        LexItem::Increment => RValue::PreIncrement(lvalue(value, &node.children[1], context)),
+        LexItem::Decrement => RValue::PreDecrement(lvalue(value, &node.children[1], context)),
+        LexItem::Plus => RValue::Positive(Box::new(value)),
+        LexItem::Minus => RValue::Negative(Box::new(value)),
+        LexItem::LogicalNot => RValue::LogicalNot(Box::new(value)),
+        LexItem::Not => RValue::BitwiseNot(Box::new(value)),
+        LexItem::Mul => RValue::LValue(LValue::PointerDeref(Box::new(value))),
+        LexItem::And => RValue::AddressOf(Box::new(lvalue(value, &node.children[1], context))),
+        _ => unreachable!(),
+    }
+}
+
+/// Reads an increment, decrement or member access after an expression
+fn read_postfix(node: &Rc<ParseNode>, context: &mut Context) -> RValue {
+    let value = read_expression(&node.children[0], context);
+    let suffix = &node.children[1];
+    require_non_terminal!(suffix, NonTerminalType::UnaryOperator1);
+    match &suffix.children[0].node_type {
+        ParseNodeType::Terminal(token) if token.item == LexItem::Increment => {
+            RValue::PostIncrement(lvalue(value, &node.children[0], context))
+        }
+        ParseNodeType::Terminal(_) => {
+            RValue::PostDecrement(lvalue(value, &node.children[0], context))
+        }
+        _ => {
+            let member = read_identifier(suffix.children[1].clone());
+            match (operator(&suffix.children[0]), value) {
+                (LexItem::Period, RValue::LValue(structure)) => {
+                    RValue::LValue(LValue::StructRef(Box::new(structure), member))
+                }
+                // The member of a value which is not an object, such as one
+                // returned by a function
+                (LexItem::Period, value) => RValue::StructRef(Box::new(value), member),
+                (_, value) => RValue::LValue(LValue::StructDeref(Box::new(value), member)),
+            }
+        }
+    }
+}
+
+pub(super) fn read_call_arguments(
+    node: &Rc<ParseNode>,
+    arguments: &mut Vec<RValue>,
+    context: &mut Context,
+) {
+    match node.node_type {
+        ParseNodeType::NonTerminal(NonTerminalType::CallArguments) => {
+            if let Some(list) = node.children.first() {
+                read_call_arguments(list, arguments, context);
+            }
+        }
+        ParseNodeType::NonTerminal(NonTerminalType::NonEmptyCallArguments) => {
+            arguments.push(read_expression(&node.children[0], context));
+            if let Some(rest) = node.children.get(2) {
+                read_call_arguments(rest, arguments, context);
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn read_generic(node: &Rc<ParseNode>, context: &mut Context) -> RValue {
+    context.not_in_c89("_Generic", node);
+    let controlling = Box::new(read_expression(&node.children[2], context));
+    let mut associations = Vec::new();
+    let mut list = node.children[4].clone();
+    loop {
+        require_non_terminal!(list, NonTerminalType::GenericAssociations);
+        let association = list.children[list.children.len() - 1].clone();
+        require_non_terminal!(association, NonTerminalType::GenericAssociation);
+        let typ = match association.children[0].node_type {
+            ParseNodeType::Terminal(_) => None,
+            _ => Some(read_type(&association, 0, context)),
+        };
+        associations.push((typ, read_expression(&association.children[2], context)));
+        if list.children.len() == 1 {
+            break;
+        }
+        list = list.children[0].clone();
+    }
+    associations.reverse();
+    RValue::Generic(controlling, associations)
+}
+
+fn warn_if_deprecated(name: &str, node: &Rc<ParseNode>, context: &mut Context) {
+    let attributes = match context.attributes.get(name) {
+        Some(attributes) => attributes,
+        None => return,
+    };
+    if let Some(deprecated) = find(attributes, Effect::Deprecated) {
+        let error_type = SemanticErrorType::Deprecated(name.to_string(), deprecated.reason());
+        context.errors.push(SemanticError {
+            error_type,
+            span: span_of(node),
+        });
+    }
+}
+
+/// Warns about a call to a `nodiscard` function whose result is thrown away
+pub(super) fn warn_if_discarded(value: &RValue, node: &Rc<ParseNode>, context: &mut Context) {
+    let name = match value {
+        RValue::FunctionCall(function, _) => match &**function {
+            RValue::LValue(LValue::Variable(name)) => name,
+            _ => return,
+        },
+        _ => return,
+    };
+    let nodiscard = context
+        .attributes
+        .get(name)
+        .and_then(|attributes| find(attributes, Effect::Nodiscard));
+    if let Some(nodiscard) = nodiscard {
+        let error_type = SemanticErrorType::UnusedResult(name.clone(), nodiscard.reason());
+        context.errors.push(SemanticError {
+            error_type,
+            span: span_of(node),
+        });
+    }
+}
@@ -0,0 +1,51 @@
+use crate::lex::errors::LexResult;
+use crate::parse::errors::ParseError;
+use crate::parse::parser::parse_as;
+use crate::parse::types::NonTerminalType;
+use crate::untyped_ast::context::Context;
+use crate::untyped_ast::declarator::read_type_name;
+use crate::untyped_ast::errors::SemanticError;
+use crate::untyped_ast::expression::read_expression;
+use crate::untyped_ast::statement::read_statement;
+use crate::untyped_ast::top_statement::read_top_statement;
+use crate::untyped_ast::types::{RValue, Statement, TopStatement, Type};
+
+/// What building the untyped AST of a fragment gives, if it parses
+type Built<T> = Result<(T, Vec<SemanticError>), ParseError>;
+
+/// Parses an expression on its own, such as `a + 1` or `x = 1, y = 2`
+pub fn parse_expression<T: Iterator<Item = LexResult>>(tokens: T) -> Built<RValue> {
+    let node = parse_as(NonTerminalType::Expression, tokens)?;
+    let mut context = Context::default();
+    let value = read_expression(&node, &mut context);
+    Ok((value, context.errors))
+}
+
+/// Parses a single statement, such as `return 0;` or `{ int x; }`. A
+/// declaration of several names gives `Statement::Declarations`.
+pub fn parse_statement<T: Iterator<Item = LexResult>>(tokens: T) -> Built<Statement> {
+    let node = parse_as(NonTerminalType::Statement, tokens)?;
+    let mut context = Context {
+        block_scope: true,
+        ..Context::default()
+    };
+    let statement = read_statement(node, &mut context);
+    Ok((statement, context.errors))
+}
+
+/// Parses a type name, as in a cast or `sizeof`, such as `char *[4]`
+pub fn parse_type_name<T: Iterator<Item = LexResult>>(tokens: T) -> Built<Type> {
+    let node = parse_as(NonTerminalType::TypeName, tokens)?;
+    let mut context = Context::default();
+    let (_, typ, declarator) = read_type_name(node, &mut context);
+    Ok((declarator.apply(typ), context.errors))
+}
+
+/// Parses a top-level declaration with its `;`, or a function definition,
+/// giving a declaration for each name it declares
+pub fn parse_declaration<T: Iterator<Item = LexResult>>(tokens: T) -> Built<Vec<TopStatement>> {
+    let node = parse_as(NonTerminalType::TopStatement, tokens)?;
+    let mut context = Context::default();
+    let declarations = read_top_statement(node, &mut context);
+    Ok((declarations, context.errors))
+}
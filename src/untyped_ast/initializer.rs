@@ -0,0 +1,70 @@
+use crate::lex::types::LexItem;
+use crate::parse::types::NonTerminalType;
+use crate::parse::types::ParseNode;
+use crate::parse::types::ParseNodeType;
+use crate::untyped_ast::context::Context;
+use crate::untyped_ast::declarator::read_identifier;
+use crate::untyped_ast::expression::read_expression;
+use crate::untyped_ast::types::{Designator, Initializer};
+use std::rc::Rc;
+
+pub(super) fn read_initializer(node: Rc<ParseNode>, context: &mut Context) -> Initializer {
+    require_non_terminal!(node, NonTerminalType::Initializer);
+    require_len!(node, |len| len == 1 || len == 3 || len == 4);
+
+    match node.children.len() {
+        1 => Initializer::Expression(read_expression(&node.children[0], context)),
+        _ => Initializer::List(read_initializer_list(node.children[1].clone(), context)),
+    }
+}
+
+/// Reads the initializers of a brace-enclosed list in order, each with its
+/// designators
+pub(super) fn read_initializer_list(
+    node: Rc<ParseNode>,
+    context: &mut Context,
+) -> Vec<(Vec<Designator>, Initializer)> {
+    require_non_terminal!(node, NonTerminalType::InitializerList);
+    require_len!(node, |len| len == 1 || len == 3);
+
+    let mut list = match node.children.len() {
+        3 => read_initializer_list(node.children[0].clone(), context),
+        _ => Vec::new(),
+    };
+    let designated = node.children[node.children.len() - 1].clone();
+    require_non_terminal!(designated, NonTerminalType::DesignatedInitializer);
+    require_len!(designated, |len| len == 1 || len == 3);
+
+    let mut designators = Vec::new();
+    if designated.children.len() == 3 {
+        context.not_in_c89("a designated initializer", &designated);
+        read_designation(designated.children[0].clone(), &mut designators, context);
+    }
+    let initializer = designated.children[designated.children.len() - 1].clone();
+    list.push((designators, read_initializer(initializer, context)));
+    list
+}
+
+fn read_designation(node: Rc<ParseNode>, designators: &mut Vec<Designator>, context: &mut Context) {
+    require_non_terminal!(node, NonTerminalType::Designation);
+    require_len!(node, |len| len == 1 || len == 2);
+
+    let designator = node.children[0].clone();
+    require_non_terminal!(designator, NonTerminalType::Designator);
+    require_len!(designator, |len| len == 2 || len == 3 || len == 5);
+
+    designators.push(match designator.children.len() {
+        2 => Designator::Member(read_identifier(designator.children[1].clone())),
+        3 => Designator::Index(read_expression(&designator.children[1], context)),
+        _ => {
+            require_terminal!(designator, 2, LexItem::Ellipsis);
+            Designator::Range(
+                read_expression(&designator.children[1], context),
+                read_expression(&designator.children[3], context),
+            )
+        }
+    });
+    if let Some(rest) = node.children.get(1) {
+        read_designation(rest.clone(), designators, context);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::untyped_ast::context::Context;
+use crate::untyped_ast::registry::{find, Effect};
+use crate::untyped_ast::types::{BaseType, Member, Type};
+
+/// The size and alignment of `typ` in bytes, as laid out by the x86-64 System V
+/// ABI, or `None` if the type is incomplete.
+///
+/// Functions are represented by `BaseType::FunctionPtr`, so they have the size
+/// of a pointer.
+pub(super) fn size_and_align(typ: &Type, context: &Context) -> Option<(u64, u64)> {
+    let scalar = |size| Some((size, size));
+    match &typ.base {
+        BaseType::Void => None,
+        BaseType::Bool | BaseType::SignedChar | BaseType::UnsignedChar => scalar(1),
+        BaseType::SignedShort | BaseType::UnsignedShort => scalar(2),
+        BaseType::SignedInt | BaseType::UnsignedInt | BaseType::Float => scalar(4),
+        BaseType::SignedLong
+        | BaseType::UnsignedLong
+        | BaseType::SignedLongLong
+        | BaseType::UnsignedLongLong
+        | BaseType::Double
+        | BaseType::Pointer(_)
+        | BaseType::FunctionPtr(..) => scalar(8),
+        BaseType::SignedInt128 | BaseType::UnsignedInt128 => scalar(16),
+        // An array of one `struct __va_list_tag`
+        BaseType::VaList => Some((24, 8)),
+        BaseType::FloatComplex => Some((8, 4)),
+        BaseType::DoubleComplex => Some((16, 8)),
+        BaseType::Array(element, Some(length)) => {
+            let (size, align) = size_and_align(element, context)?;
+            Some((size.checked_mul(*length as u64)?, align))
+        }
+        BaseType::Array(_, None) | BaseType::VariableArray(..) | BaseType::TypeOf(_) => None,
+        BaseType::Enum(_, enumerators) if !enumerators.is_empty() => scalar(4),
+        BaseType::Struct(_, Some(members)) => struct_layout(members, context),
+        BaseType::Union(_, Some(members)) => union_layout(members, context),
+        // A tag declared elsewhere, which is complete once it has a body
+        BaseType::Enum(Some(tag), _)
+        | BaseType::Struct(Some(tag), None)
+        | BaseType::Union(Some(tag), None) => {
+            let declared = context.tags.get(tag)?;
+            let same_kind = matches!(
+                (&typ.base, &declared.base),
+                (BaseType::Enum(..), BaseType::Enum(..))
+                    | (BaseType::Struct(..), BaseType::Struct(..))
+                    | (BaseType::Union(..), BaseType::Union(..))
+            );
+            if same_kind {
+                size_and_align(declared, context)
+            } else {
+                None
+            }
+        }
+        BaseType::Enum(None, _) | BaseType::Struct(None, None) | BaseType::Union(None, None) => {
+            None
+        }
+    }
+}
+
+/// The size and alignment of a member, where `_Alignas` can only make the
+/// alignment stricter. A packed member has no alignment of its own. A flexible
+/// array member has no size.
+fn member_layout(member: &Member, context: &Context) -> Option<(u64, u64)> {
+    let (size, mut align) = match &member.typ.base {
+        BaseType::Array(element, None) => (0, size_and_align(element, context)?.1),
+        _ => size_and_align(&member.typ, context)?,
+    };
+    if is_packed(member) {
+        align = 1;
+    }
+    Some((
+        size,
+        member
+            .alignment
+            .map_or(align, |alignment| alignment.max(align)),
+    ))
+}
+
+fn is_packed(member: &Member) -> bool {
+    find(&member.attributes, Effect::Packed).is_some()
+}
+
+fn round_up(value: u64, multiple: u64) -> u64 {
+    value.div_ceil(multiple) * multiple
+}
+
+/// Members are laid out in order, each at the next multiple of its alignment.
+/// A bitfield shares the storage unit of its type with the previous bitfields
+/// unless it would straddle the end of the unit, while a packed bitfield
+/// follows straight on. A bitfield of a type without a size, such as an empty
+/// GNU struct, has no layout.
+fn struct_layout(members: &[Member], context: &Context) -> Option<(u64, u64)> {
+    let mut bits = 0;
+    let mut align = 1;
+    for member in members {
+        let (size, member_align) = member_layout(member, context)?;
+        let unit = size * 8;
+        match member.bitfield {
+            None => bits = round_up(bits, member_align * 8) + unit,
+            Some(_) if unit == 0 => return None,
+            // A zero width bitfield only moves on to the next unit
+            Some(0) => bits = round_up(bits, unit),
+            Some(width) => {
+                let width = u64::from(width);
+                if !is_packed(member) && bits / unit != (bits + width - 1) / unit {
+                    bits = round_up(bits, unit);
+                }
+                bits += width;
+            }
+        }
+        // Unnamed bitfields do not affect the alignment of the struct
+        if member.name.is_some() || member.bitfield.is_none() {
+            align = align.max(member_align);
+        }
+    }
+    Some((round_up(bits.div_ceil(8), align), align))
+}
+
+fn union_layout(members: &[Member], context: &Context) -> Option<(u64, u64)> {
+    let mut size = 0;
+    let mut align = 1;
+    for member in members {
+        let (member_size, member_align) = member_layout(member, context)?;
+        size = size.max(match member.bitfield {
+            Some(width) => u64::from(width).div_ceil(8),
+            None => member_size,
+        });
+        if member.name.is_some() || member.bitfield.is_none() {
+            align = align.max(member_align);
+        }
+    }
+    Some((round_up(size, align), align))
+}
@@ -4,9 +4,25 @@
 mod util;
 mod types;
 
+mod attribute;
+mod constant;
+mod context;
+mod declarator;
+mod enumeration;
+pub mod errors;
+mod expression;
+pub mod fragment;
+mod initializer;
+mod layout;
+pub mod registry;
 mod root;
+mod specifiers;
+mod statement;
+mod structure;
 mod top_statement;
+#[allow(unused_imports)]
 pub use self::root::build_untyped_ast;
+pub use self::root::build_untyped_ast_with;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,292 @@
+use crate::lex::errors::Span;
+use crate::untyped_ast::context::Context;
+use crate::untyped_ast::errors::{SemanticError, SemanticErrorType};
+use crate::untyped_ast::types::{Attribute, AttributeArgument, Specifiers};
+
+/// What an attribute can belong to
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Target {
+    /// Any declared name, including functions
+    Declaration,
+    Function,
+    /// A struct or union with a body, from attributes after its keyword
+    StructOrUnion,
+    /// A member of a struct or union, which takes the attributes of both
+    Member,
+    Statement,
+    /// An attribute declaration such as `[[deprecated]];` at file scope,
+    /// which has nothing to belong to
+    Nothing,
+}
+
+/// What a known attribute does besides being accepted
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Effect {
+    /// Every use of the name is warned about
+    Deprecated,
+    /// Calls which throw the result away are warned about
+    Nodiscard,
+    /// The function never returns, as with `_Noreturn`
+    Noreturn,
+    /// Falling through to the next case label is deliberate
+    Fallthrough,
+    /// Members are not padded to their alignment
+    Packed,
+}
+
+/// An attribute the compiler knows, which is accepted without a warning
+#[derive(Clone, Copy, Debug)]
+pub struct KnownAttribute {
+    pub prefix: Option<&'static str>,
+    pub name: &'static str,
+    pub target: Target,
+    /// The fewest and most arguments it takes
+    pub arguments: (usize, usize),
+    pub effect: Option<Effect>,
+}
+
+const fn standard(
+    name: &'static str,
+    target: Target,
+    arguments: (usize, usize),
+    effect: Option<Effect>,
+) -> KnownAttribute {
+    KnownAttribute {
+        prefix: None,
+        name,
+        target,
+        arguments,
+        effect,
+    }
+}
+
+const fn gnu(
+    name: &'static str,
+    target: Target,
+    arguments: (usize, usize),
+    effect: Option<Effect>,
+) -> KnownAttribute {
+    KnownAttribute {
+        prefix: Some("gnu"),
+        name,
+        target,
+        arguments,
+        effect,
+    }
+}
+
+const ANY: usize = usize::MAX;
+
+/// Every known attribute. Most GNU attributes only matter to code generation,
+/// so they are accepted without an effect for now.
+const KNOWN: &[KnownAttribute] = &[
+    standard(
+        "deprecated",
+        Target::Declaration,
+        (0, 1),
+        Some(Effect::Deprecated),
+    ),
+    standard(
+        "fallthrough",
+        Target::Statement,
+        (0, 0),
+        Some(Effect::Fallthrough),
+    ),
+    standard("maybe_unused", Target::Declaration, (0, 0), None),
+    standard(
+        "nodiscard",
+        Target::Function,
+        (0, 1),
+        Some(Effect::Nodiscard),
+    ),
+    standard("noreturn", Target::Function, (0, 0), Some(Effect::Noreturn)),
+    standard(
+        "_Noreturn",
+        Target::Function,
+        (0, 0),
+        Some(Effect::Noreturn),
+    ),
+    standard("reproducible", Target::Function, (0, 0), None),
+    standard("unsequenced", Target::Function, (0, 0), None),
+    gnu("access", Target::Function, (2, 3), None),
+    gnu("alias", Target::Declaration, (1, 1), None),
+    gnu("aligned", Target::Declaration, (0, 1), None),
+    gnu("alloc_align", Target::Function, (1, 1), None),
+    gnu("alloc_size", Target::Function, (1, 2), None),
+    gnu("always_inline", Target::Function, (0, 0), None),
+    gnu("artificial", Target::Function, (0, 0), None),
+    gnu("assume_aligned", Target::Function, (1, 2), None),
+    gnu("cleanup", Target::Declaration, (1, 1), None),
+    gnu("cold", Target::Function, (0, 0), None),
+    gnu("const", Target::Function, (0, 0), None),
+    gnu("constructor", Target::Function, (0, 1), None),
+    gnu("copy", Target::Declaration, (1, 1), None),
+    gnu("counted_by", Target::Declaration, (1, 1), None),
+    gnu(
+        "deprecated",
+        Target::Declaration,
+        (0, 1),
+        Some(Effect::Deprecated),
+    ),
+    gnu("destructor", Target::Function, (0, 1), None),
+    gnu("error", Target::Function, (1, 1), None),
+    gnu("externally_visible", Target::Declaration, (0, 0), None),
+    gnu(
+        "fallthrough",
+        Target::Statement,
+        (0, 0),
+        Some(Effect::Fallthrough),
+    ),
+    gnu("fd_arg", Target::Function, (1, 1), None),
+    gnu("fd_arg_read", Target::Function, (1, 1), None),
+    gnu("fd_arg_write", Target::Function, (1, 1), None),
+    gnu("flatten", Target::Function, (0, 0), None),
+    gnu("format", Target::Function, (3, 3), None),
+    gnu("format_arg", Target::Function, (1, 1), None),
+    gnu("gnu_inline", Target::Function, (0, 0), None),
+    gnu("hot", Target::Function, (0, 0), None),
+    gnu("leaf", Target::Function, (0, 0), None),
+    gnu("malloc", Target::Function, (0, 2), None),
+    gnu("may_alias", Target::Declaration, (0, 0), None),
+    gnu("mode", Target::Declaration, (1, 1), None),
+    gnu("no_instrument_function", Target::Function, (0, 0), None),
+    gnu("noinline", Target::Function, (0, 0), None),
+    gnu("nonnull", Target::Function, (0, ANY), None),
+    gnu("nonstring", Target::Declaration, (0, 0), None),
+    gnu("noreturn", Target::Function, (0, 0), Some(Effect::Noreturn)),
+    gnu("nothrow", Target::Function, (0, 0), None),
+    gnu("optimize", Target::Function, (1, ANY), None),
+    gnu(
+        "packed",
+        Target::StructOrUnion,
+        (0, 0),
+        Some(Effect::Packed),
+    ),
+    gnu("pure", Target::Function, (0, 0), None),
+    gnu("returns_nonnull", Target::Function, (0, 0), None),
+    gnu("returns_twice", Target::Function, (0, 0), None),
+    gnu("section", Target::Declaration, (1, 1), None),
+    gnu("sentinel", Target::Function, (0, 1), None),
+    gnu("target", Target::Function, (1, ANY), None),
+    gnu("tls_model", Target::Declaration, (1, 1), None),
+    gnu("transparent_union", Target::Declaration, (0, 0), None),
+    gnu("unavailable", Target::Declaration, (0, 1), None),
+    gnu("unused", Target::Declaration, (0, 0), None),
+    gnu("used", Target::Declaration, (0, 0), None),
+    gnu("vector_size", Target::Declaration, (1, 1), None),
+    gnu("visibility", Target::Declaration, (1, 1), None),
+    gnu(
+        "warn_unused_result",
+        Target::Function,
+        (0, 0),
+        Some(Effect::Nodiscard),
+    ),
+    gnu("warning", Target::Function, (1, 1), None),
+    gnu("weak", Target::Declaration, (0, 0), None),
+];
+
+/// Looks an attribute up by its prefix and name
+pub fn lookup(prefix: Option<&str>, name: &str) -> Option<&'static KnownAttribute> {
+    KNOWN
+        .iter()
+        .find(|known| known.prefix == prefix && known.name == name)
+}
+
+impl Target {
+    fn accepts(self, target: Target) -> bool {
+        self == target
+            || (self == Target::Declaration && matches!(target, Target::Function | Target::Member))
+            || (self == Target::StructOrUnion && target == Target::Member)
+    }
+}
+
+impl Attribute {
+    /// The name of the attribute with its prefix, for diagnostics
+    fn full_name(&self) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}::{}", prefix, self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    /// What the attribute does, if it is known
+    pub(super) fn effect(&self) -> Option<Effect> {
+        lookup(self.prefix.as_deref(), &self.name).and_then(|known| known.effect)
+    }
+
+    /// The string argument of an attribute, such as the reason of `deprecated`
+    pub(super) fn reason(&self) -> Option<String> {
+        self.arguments.iter().find_map(|argument| match argument {
+            AttributeArgument::String(reason) => Some(String::from_utf8_lossy(reason).into_owned()),
+            _ => None,
+        })
+    }
+}
+
+/// The first attribute with `effect`
+pub(super) fn find(attributes: &[Attribute], effect: Effect) -> Option<&Attribute> {
+    attributes
+        .iter()
+        .find(|attribute| attribute.effect() == Some(effect))
+}
+
+/// Keeps the attributes which are known and belong to `target`, warning about
+/// the rest. Known attributes with the wrong number of arguments are errors.
+pub(super) fn check_attributes(
+    attributes: Vec<Attribute>,
+    target: Target,
+    span: Span,
+    errors: &mut Vec<SemanticError>,
+) -> Vec<Attribute> {
+    attributes
+        .into_iter()
+        .filter(|attribute| {
+            let error_type = match lookup(attribute.prefix.as_deref(), &attribute.name) {
+                None => SemanticErrorType::UnknownAttribute(attribute.full_name()),
+                Some(known) if !known.target.accepts(target) => {
+                    SemanticErrorType::AttributeIgnored(attribute.full_name())
+                }
+                Some(KnownAttribute {
+                    arguments: (least, most),
+                    ..
+                }) if attribute.arguments.len() < *least || attribute.arguments.len() > *most => {
+                    SemanticErrorType::InvalidAttributeArguments(attribute.full_name())
+                }
+                Some(_) => return true,
+            };
+            errors.push(SemanticError { error_type, span });
+            false
+        })
+        .collect()
+}
+
+/// Checks the attributes of a declared name and applies their effects. Those
+/// which matter to later uses of the name are remembered by name.
+pub(super) fn apply_attributes(
+    name: Option<&str>,
+    specifiers: &mut Specifiers,
+    target: Target,
+    span: Span,
+    context: &mut Context,
+) {
+    let attributes = std::mem::take(&mut specifiers.attributes);
+    specifiers.attributes = check_attributes(attributes, target, span, &mut context.errors);
+    if find(&specifiers.attributes, Effect::Noreturn).is_some() {
+        specifiers.noreturn = true;
+    }
+    if let Some(name) = name {
+        record_attributes(name, &specifiers.attributes, context);
+    }
+}
+
+/// Remembers the attributes of a declared name, which add up over all of its
+/// declarations
+pub(super) fn record_attributes(name: &str, attributes: &[Attribute], context: &mut Context) {
+    if !attributes.is_empty() {
+        context
+            .attributes
+            .entry(name.to_string())
+            .or_default()
+            .extend(attributes.iter().cloned());
+    }
+}
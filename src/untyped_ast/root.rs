@@ -1,13 +1,33 @@
 use crate::parse::types::NonTerminalType;
 use crate::parse::types::ParseNode;
 use crate::parse::types::ParseNodeType;
+use crate::standard::Standard;
+use crate::untyped_ast::context::Context;
+use crate::untyped_ast::errors::SemanticError;
 use crate::untyped_ast::top_statement::read_top_statements;
 use crate::untyped_ast::types::Root;
 use std::rc::Rc;
 
-pub fn build_untyped_ast(node: Rc<ParseNode>) -> Root {
+/// Builds the untyped AST of a parse tree, reporting every declaration which
+/// breaks a constraint of C. The tree is complete even if there are errors.
+#[allow(dead_code)]
+pub fn build_untyped_ast(node: Rc<ParseNode>) -> (Root, Vec<SemanticError>) {
+    build_untyped_ast_with(node, Standard::default())
+}
+
+/// Builds the untyped AST of a tree parsed in the grammar of `standard`, like
+/// `build_untyped_ast`
+pub fn build_untyped_ast_with(
+    node: Rc<ParseNode>,
+    standard: Standard,
+) -> (Root, Vec<SemanticError>) {
     require_non_terminal!(node, NonTerminalType::Start);
     require_len!(node, |len| len == 1);
 
-    Root(read_top_statements(node.children[0].clone()))
+    let mut context = Context {
+        standard,
+        ..Context::default()
+    };
+    let root = Root(read_top_statements(node.children[0].clone(), &mut context));
+    (root, context.errors)
 }
@@ -0,0 +1,451 @@
+use crate::lex::errors::{LexSuccess, Span};
+use crate::lex::types::LexItem;
+use crate::parse::types::NonTerminalType;
+use crate::parse::types::ParseNode;
+use crate::parse::types::ParseNodeType;
+use crate::untyped_ast::attribute::{read_attribute_specifiers, read_attributes};
+use crate::untyped_ast::constant::evaluate;
+use crate::untyped_ast::context::Context;
+use crate::untyped_ast::declarator::read_type_name;
+use crate::untyped_ast::enumeration::read_enum;
+use crate::untyped_ast::errors::{span_of, SemanticError, SemanticErrorType};
+use crate::untyped_ast::expression::read_expression;
+use crate::untyped_ast::layout::size_and_align;
+use crate::untyped_ast::structure::read_struct_or_union;
+use crate::untyped_ast::types::{BaseType, Qualifiers, Specifiers, StorageClass, Type};
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+/// Everything a `Type` node says, before it is checked
+#[derive(Default)]
+struct Collected {
+    specifiers: Specifiers,
+    qualifiers: Qualifiers,
+    /// The keyword of every type specifier, including `struct`, `enum` and
+    /// `_Atomic`
+    keywords: Vec<LexItem>,
+    /// The type given by a struct, union, enum, `_Atomic(...)` or
+    /// `__typeof__(...)` specifier, or by a typedef name
+    typ: Option<Type>,
+}
+
+/// Reads the specifiers and qualifiers of a declaration. They may come in any
+/// order, and invalid combinations are reported.
+pub(super) fn read_type(node: Rc<ParseNode>, context: &mut Context) -> (Specifiers, Type) {
+    require_non_terminal!(node, NonTerminalType::Type);
+
+    let mut collected = Collected::default();
+    collect(node.clone(), &mut collected, context);
+
+    let longs = collected
+        .keywords
+        .iter()
+        .filter(|&keyword| *keyword == LexItem::Long);
+    if longs.count() > 1 {
+        context.not_in_c89("long long", &node);
+    }
+
+    let mut qualifiers = collected.qualifiers;
+    let base = match collected.typ.take() {
+        // The qualifiers of a typedef name or `__typeof__` add to those here
+        Some(typ) if collected.keywords.len() == 1 => {
+            qualifiers.constant |= typ.qualifiers.constant;
+            qualifiers.volatile |= typ.qualifiers.volatile;
+            qualifiers.restrict |= typ.qualifiers.restrict;
+            qualifiers.atomic |= typ.qualifiers.atomic;
+            Some(typ.base)
+        }
+        Some(_) => None,
+        // Implicit int, which only C89 parses
+        None if collected.keywords.is_empty() => Some(BaseType::SignedInt),
+        None => base_type(&collected.keywords),
+    };
+    let base = base.unwrap_or_else(|| {
+        context.errors.push(SemanticError {
+            error_type: SemanticErrorType::InvalidTypeSpecifiers(collected.keywords.clone()),
+            span: span_of(&node),
+        });
+        BaseType::SignedInt
+    });
+
+    let typ = Type { base, qualifiers };
+    let is_pointer = matches!(typ.base, BaseType::Pointer(_) | BaseType::FunctionPtr(..));
+    if typ.qualifiers.restrict && !is_pointer {
+        context.errors.push(SemanticError {
+            error_type: SemanticErrorType::RestrictWithoutPointer,
+            span: span_of(&node),
+        });
+    }
+    (collected.specifiers, typ)
+}
+
+fn collect(node: Rc<ParseNode>, collected: &mut Collected, context: &mut Context) {
+    for child in &node.children {
+        match &child.node_type {
+            ParseNodeType::NonTerminal(NonTerminalType::Type)
+            | ParseNodeType::NonTerminal(NonTerminalType::NumberType)
+            | ParseNodeType::NonTerminal(NonTerminalType::TypedefSpecifiers)
+            | ParseNodeType::NonTerminal(NonTerminalType::DeclarationSpecifier) => {
+                collect(child.clone(), collected, context)
+            }
+            ParseNodeType::NonTerminal(NonTerminalType::TypeQualifier) => {
+                read_qualifier(child.clone(), &mut collected.qualifiers, context)
+            }
+            ParseNodeType::NonTerminal(NonTerminalType::StorageClass) => {
+                read_storage_class(child.clone(), &mut collected.specifiers, context)
+            }
+            ParseNodeType::NonTerminal(NonTerminalType::AlignmentSpecifier) => {
+                context.not_in_c89("_Alignas", child);
+                if let Some(alignment) = read_alignment(child.clone(), context) {
+                    let strictest = collected.specifiers.alignment.unwrap_or(0).max(alignment);
+                    collected.specifiers.alignment = Some(strictest);
+                }
+            }
+            ParseNodeType::NonTerminal(NonTerminalType::Attribute) => collected
+                .specifiers
+                .attributes
+                .extend(read_attributes(child.clone(), context)),
+            // Attributes after the specifiers belong to the type, but like
+            // GNU ones among the specifiers they are kept for what is declared
+            ParseNodeType::NonTerminal(NonTerminalType::AttributeSpecifiers) => collected
+                .specifiers
+                .attributes
+                .extend(read_attribute_specifiers(child.clone(), context)),
+            ParseNodeType::NonTerminal(NonTerminalType::FunctionSpecifier) => {
+                // Only `__inline` is a keyword in C89
+                match keyword(&child.children[0]).item {
+                    LexItem::Inline => collected.specifiers.inline = true,
+                    LexItem::Noreturn => {
+                        context.not_in_c89("_Noreturn", child);
+                        collected.specifiers.noreturn = true
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            ParseNodeType::NonTerminal(NonTerminalType::TypeSpecifier) => {
+                match &child.children[0].node_type {
+                    ParseNodeType::Terminal(LexSuccess {
+                        item: LexItem::Atomic,
+                        ..
+                    }) => {
+                        require_len!(child, |len| len == 4);
+                        context.not_in_c89("_Atomic", child);
+                        let (_, typ, declarator) =
+                            read_type_name(child.children[2].clone(), context);
+                        collected.keywords.push(LexItem::Atomic);
+                        collected.qualifiers.atomic = true;
+                        collected.typ = Some(declarator.apply(typ));
+                    }
+                    ParseNodeType::Terminal(LexSuccess {
+                        item: LexItem::Typeof,
+                        ..
+                    }) => {
+                        require_len!(child, |len| len == 4);
+                        let argument = child.children[2].clone();
+                        let typ = match argument.node_type {
+                            ParseNodeType::NonTerminal(NonTerminalType::TypeName) => {
+                                let (_, typ, declarator) = read_type_name(argument, context);
+                                declarator.apply(typ)
+                            }
+                            _ => Type::new(BaseType::TypeOf(Box::new(read_expression(
+                                &argument, context,
+                            )))),
+                        };
+                        collected.keywords.push(LexItem::Typeof);
+                        collected.typ = Some(typ);
+                    }
+                    ParseNodeType::Terminal(token) => {
+                        match token.item {
+                            LexItem::Bool => context.not_in_c89("_Bool", child),
+                            LexItem::Complex => context.not_in_c89("_Complex", child),
+                            _ => {}
+                        }
+                        collected.keywords.push(token.item.clone())
+                    }
+                    ParseNodeType::NonTerminal(NonTerminalType::EnumDeclaration) => {
+                        collected.keywords.push(LexItem::Enum);
+                        collected.typ = Some(read_enum(child.children[0].clone(), context));
+                    }
+                    ParseNodeType::NonTerminal(NonTerminalType::StructOrUnionDeclaration) => {
+                        let typ = read_struct_or_union(child.children[0].clone(), context);
+                        collected.keywords.push(match typ.base {
+                            BaseType::Union(..) => LexItem::Union,
+                            _ => LexItem::Struct,
+                        });
+                        collected.typ = Some(typ);
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            // `__extension__` only silences warnings
+            ParseNodeType::Terminal(LexSuccess {
+                item: LexItem::Extension,
+                ..
+            }) => {}
+            // The parser only reads a name as a typedef name where one is in scope
+            ParseNodeType::Terminal(LexSuccess {
+                item: LexItem::TypedefName(name),
+                ..
+            }) => {
+                collected.keywords.push(LexItem::TypedefName(name.clone()));
+                collected.typ = context.typedefs.get(name).cloned();
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// The alignment requested by `_Alignas`, which has no effect if it is zero
+fn read_alignment(node: Rc<ParseNode>, context: &mut Context) -> Option<u64> {
+    require_non_terminal!(node, NonTerminalType::AlignmentSpecifier);
+    require_len!(node, |len| len == 4);
+    require_terminal!(node, 0, LexItem::Alignas);
+
+    let argument = node.children[2].clone();
+    let alignment = match argument.node_type {
+        ParseNodeType::NonTerminal(NonTerminalType::TypeName) => {
+            let (_, typ, declarator) = read_type_name(argument, context);
+            return size_and_align(&declarator.apply(typ), context).map(|(_, align)| align);
+        }
+        _ => evaluate(&argument, context),
+    };
+    let error_type = match alignment {
+        Some(0) => return None,
+        Some(alignment) if alignment > 0 && alignment.count_ones() == 1 => {
+            return u64::try_from(alignment).ok()
+        }
+        Some(alignment) => SemanticErrorType::InvalidAlignment(alignment),
+        None => SemanticErrorType::NotConstant,
+    };
+    context.errors.push(SemanticError {
+        error_type,
+        span: span_of(&argument),
+    });
+    None
+}
+
+fn keyword(node: &Rc<ParseNode>) -> &LexSuccess {
+    match &node.node_type {
+        ParseNodeType::Terminal(token) => token,
+        _ => unreachable!(),
+    }
+}
+
+fn read_storage_class(node: Rc<ParseNode>, specifiers: &mut Specifiers, context: &mut Context) {
+    require_non_terminal!(node, NonTerminalType::StorageClass);
+    require_len!(node, |len| len == 1);
+
+    let token = keyword(&node.children[0]);
+    if token.item == LexItem::ThreadLocal {
+        context.not_in_c89("_Thread_local", &node);
+    }
+    let span = Span {
+        start: token.location,
+        end: token.end,
+    };
+    let error_type = match (&token.item, specifiers.storage) {
+        (LexItem::ThreadLocal, _) if specifiers.thread_local => {
+            Some(SemanticErrorType::DuplicateSpecifier(LexItem::ThreadLocal))
+        }
+        (LexItem::ThreadLocal, Some(storage @ StorageClass::Auto))
+        | (LexItem::ThreadLocal, Some(storage @ StorageClass::Register)) => {
+            Some(SemanticErrorType::ConflictingStorageClasses(
+                storage_item(storage),
+                LexItem::ThreadLocal,
+            ))
+        }
+        (LexItem::ThreadLocal, _) => {
+            specifiers.thread_local = true;
+            None
+        }
+        (item, Some(storage)) if *item == storage_item(storage) => {
+            Some(SemanticErrorType::DuplicateSpecifier(item.clone()))
+        }
+        (item, Some(storage)) => Some(SemanticErrorType::ConflictingStorageClasses(
+            storage_item(storage),
+            item.clone(),
+        )),
+        (item, None) => {
+            let storage = match item {
+                LexItem::Static => StorageClass::Static,
+                LexItem::Extern => StorageClass::Extern,
+                LexItem::Auto => StorageClass::Auto,
+                LexItem::Register => StorageClass::Register,
+                _ => unreachable!(),
+            };
+            if specifiers.thread_local
+                && (storage == StorageClass::Auto || storage == StorageClass::Register)
+            {
+                Some(SemanticErrorType::ConflictingStorageClasses(
+                    LexItem::ThreadLocal,
+                    item.clone(),
+                ))
+            } else {
+                specifiers.storage = Some(storage);
+                None
+            }
+        }
+    };
+    context
+        .errors
+        .extend(error_type.map(|error_type| SemanticError { error_type, span }));
+}
+
+/// The keyword of a storage class, for diagnostics
+pub(super) fn storage_item(storage: StorageClass) -> LexItem {
+    match storage {
+        StorageClass::Static => LexItem::Static,
+        StorageClass::Extern => LexItem::Extern,
+        StorageClass::Auto => LexItem::Auto,
+        StorageClass::Register => LexItem::Register,
+    }
+}
+
+/// The type named by a set of type specifier keywords, or `None` if they do
+/// not name a type together
+fn base_type(keywords: &[LexItem]) -> Option<BaseType> {
+    let count = |item: LexItem| keywords.iter().filter(|k| **k == item).count();
+    let (signed, unsigned) = (count(LexItem::Signed), count(LexItem::Unsigned));
+    let (int, long) = (count(LexItem::Int), count(LexItem::Long));
+    let complex = count(LexItem::Complex);
+    let signs = signed + unsigned;
+    let total = keywords.len();
+    if signs > 1 || int > 1 || complex > 1 {
+        return None;
+    }
+    let unsigned = unsigned > 0;
+
+    Some(if count(LexItem::Void) == 1 && total == 1 {
+        BaseType::Void
+    } else if count(LexItem::Bool) == 1 && total == 1 {
+        BaseType::Bool
+    } else if count(LexItem::BuiltinVaList) == 1 && total == 1 {
+        BaseType::VaList
+    } else if count(LexItem::Int128) == 1 && total == 1 + signs {
+        if unsigned {
+            BaseType::UnsignedInt128
+        } else {
+            BaseType::SignedInt128
+        }
+    } else if count(LexItem::Float) == 1 && total == 1 + complex {
+        if complex > 0 {
+            BaseType::FloatComplex
+        } else {
+            BaseType::Float
+        }
+    } else if count(LexItem::Double) == 1 && long <= 1 && total == 1 + long + complex {
+        if complex > 0 {
+            BaseType::DoubleComplex
+        } else {
+            BaseType::Double
+        }
+    } else if complex > 0 {
+        return None;
+    } else if count(LexItem::Char) == 1 && total == 1 + signs {
+        if unsigned {
+            BaseType::UnsignedChar
+        } else {
+            BaseType::SignedChar
+        }
+    } else if count(LexItem::Short) == 1 && total == 1 + signs + int {
+        if unsigned {
+            BaseType::UnsignedShort
+        } else {
+            BaseType::SignedShort
+        }
+    } else if total > 0 && total == signs + int + long {
+        match (long, unsigned) {
+            (0, false) => BaseType::SignedInt,
+            (0, true) => BaseType::UnsignedInt,
+            (1, false) => BaseType::SignedLong,
+            (1, true) => BaseType::UnsignedLong,
+            (2, false) => BaseType::SignedLongLong,
+            (2, true) => BaseType::UnsignedLongLong,
+            _ => return None,
+        }
+    } else {
+        return None;
+    })
+}
+
+fn read_qualifier(node: Rc<ParseNode>, qualifiers: &mut Qualifiers, context: &mut Context) {
+    require_non_terminal!(node, NonTerminalType::TypeQualifier);
+    require_len!(node, |len| len == 1);
+
+    match keyword(&node.children[0]).item {
+        LexItem::Const => qualifiers.constant = true,
+        LexItem::Volatile => qualifiers.volatile = true,
+        LexItem::Restrict => qualifiers.restrict = true,
+        LexItem::Atomic => {
+            context.not_in_c89("_Atomic", &node);
+            qualifiers.atomic = true
+        }
+        _ => unreachable!(),
+    }
+}
+
+pub(super) fn read_qualifiers(node: Rc<ParseNode>, context: &mut Context) -> Qualifiers {
+    require_non_terminal!(node, NonTerminalType::PointerQualifiers);
+
+    let mut qualifiers = Qualifiers::default();
+    let mut node = node;
+    while let Some(qualifier) = node.children.first().cloned() {
+        read_qualifier(qualifier, &mut qualifiers, context);
+        node = node.children[1].clone();
+    }
+    qualifiers
+}
+
+/// Reports `_Alignas` on a declaration which cannot have it
+pub(super) fn forbid_alignment(
+    specifiers: &Specifiers,
+    span: Span,
+    errors: &mut Vec<SemanticError>,
+) {
+    if specifiers.alignment.is_some() {
+        errors.push(SemanticError {
+            error_type: SemanticErrorType::AlignmentNotAllowed,
+            span,
+        });
+    }
+}
+
+/// Reports specifiers which only make sense on some declarations
+pub(super) fn check_specifiers(
+    specifiers: &Specifiers,
+    allowed: &[StorageClass],
+    function: bool,
+    span: Span,
+    errors: &mut Vec<SemanticError>,
+) {
+    let mut error = |error_type| errors.push(SemanticError { error_type, span });
+    if let Some(storage) = specifiers.storage {
+        if !allowed.contains(&storage) {
+            error(SemanticErrorType::StorageClassNotAllowed(storage_item(
+                storage,
+            )));
+        }
+    }
+    if specifiers.thread_local && (function || !allowed.contains(&StorageClass::Static)) {
+        error(SemanticErrorType::StorageClassNotAllowed(
+            LexItem::ThreadLocal,
+        ));
+    }
+    if specifiers.alignment.is_some()
+        && (function || specifiers.storage == Some(StorageClass::Register))
+    {
+        error(SemanticErrorType::AlignmentNotAllowed);
+    }
+    if !function {
+        if specifiers.inline {
+            error(SemanticErrorType::FunctionSpecifierOnObject(
+                LexItem::Inline,
+            ));
+        }
+        if specifiers.noreturn {
+            error(SemanticErrorType::FunctionSpecifierOnObject(
+                LexItem::Noreturn,
+            ));
+        }
+    }
+}
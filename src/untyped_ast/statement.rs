@@ -0,0 +1,305 @@
+use crate::lex::errors::Span;
+use crate::lex::types::NumberType;
+use crate::parse::cst::{
+    self, AstNode, Case, DoWhileLoop, ForLoop, Goto, If, Label, Return, Switch, SyntaxNode,
+    WhileLoop,
+};
+use crate::parse::types::NonTerminalType;
+use crate::parse::types::ParseNode;
+use crate::parse::types::ParseNodeType;
+use crate::untyped_ast::attribute::read_attribute_specifier;
+use crate::untyped_ast::constant::read_static_assert;
+use crate::untyped_ast::context::Context;
+use crate::untyped_ast::declarator::read_identifier;
+use crate::untyped_ast::errors::{span_of, SemanticError, SemanticErrorType};
+use crate::untyped_ast::expression::{read_expression, warn_if_discarded};
+use crate::untyped_ast::registry::{check_attributes, find, Effect, Target};
+use crate::untyped_ast::top_statement::{read_declaration, read_typedef};
+use crate::untyped_ast::types::{
+    Attribute, BaseType, Block, RValue, Statement, StorageClass, TopStatement, Type,
+};
+use std::rc::Rc;
+
+/// The storage classes a declaration in a block may have
+const LOCAL: &[StorageClass] = &[
+    StorageClass::Static,
+    StorageClass::Extern,
+    StorageClass::Auto,
+    StorageClass::Register,
+];
+
+/// Reads a compound statement, checking that each `[[fallthrough]];` is
+/// followed by a case label
+pub(super) fn read_block(node: Rc<ParseNode>, context: &mut Context) -> Block {
+    require_non_terminal!(node, NonTerminalType::Block);
+    require_len!(node, |len| len == 3);
+
+    let outer = std::mem::replace(&mut context.block_scope, true);
+    // Typedef names declared in the block end with it
+    let typedefs = context.typedefs.clone();
+    let mut statements = Vec::new();
+    let mut fallthrough: Option<Span> = None;
+    let mut after_statement = false;
+    let mut contents = node.children[1].clone();
+    while let Some(child) = contents.children.first().cloned() {
+        if !is_declaration(&child) {
+            after_statement = true;
+        } else if after_statement {
+            context.not_in_c89("a declaration after a statement", &child);
+        }
+        let read = read_statements(child.clone(), context);
+        if let Some(span) = fallthrough.take() {
+            if !matches!(
+                read.first(),
+                Some(Statement::Case(..)) | Some(Statement::Default(_))
+            ) {
+                misplaced_fallthrough(span, context);
+            }
+        }
+        if read.last().is_some_and(ends_in_fallthrough) {
+            fallthrough = Some(span_of(&child));
+        }
+        statements.extend(read);
+        contents = contents.children[1].clone();
+    }
+    if let Some(span) = fallthrough {
+        misplaced_fallthrough(span, context);
+    }
+    context.block_scope = outer;
+    context.typedefs = typedefs;
+    Block(statements)
+}
+
+/// Whether a statement declares names, maybe after some attributes
+fn is_declaration(node: &ParseNode) -> bool {
+    match node.children[0].node_type {
+        ParseNodeType::NonTerminal(NonTerminalType::Declaration)
+        | ParseNodeType::NonTerminal(NonTerminalType::Typedef) => true,
+        ParseNodeType::NonTerminal(NonTerminalType::AttributeSpecifier) => {
+            is_declaration(&node.children[1])
+        }
+        _ => false,
+    }
+}
+
+/// Whether a statement is `[[fallthrough]];`, maybe after some labels
+fn ends_in_fallthrough(statement: &Statement) -> bool {
+    match statement {
+        Statement::Attributed(attributes, _) => find(attributes, Effect::Fallthrough).is_some(),
+        Statement::Label(_, statement)
+        | Statement::Case(_, statement)
+        | Statement::Default(statement) => ends_in_fallthrough(statement),
+        _ => false,
+    }
+}
+
+fn misplaced_fallthrough(span: Span, context: &mut Context) {
+    context.errors.push(SemanticError {
+        error_type: SemanticErrorType::MisplacedFallthrough,
+        span,
+    });
+}
+
+/// Reads a statement where only one is allowed, such as the body of a loop
+pub(super) fn read_statement(node: Rc<ParseNode>, context: &mut Context) -> Statement {
+    single(read_statements(node, context))
+}
+
+/// A declaration statement with other than one declarator keeps them together
+fn single(mut statements: Vec<Statement>) -> Statement {
+    match statements.len() {
+        1 => statements.pop().unwrap(),
+        _ => Statement::Declarations(statements),
+    }
+}
+
+/// Reads a statement. A declaration is a statement for each declarator.
+fn read_statements(node: Rc<ParseNode>, context: &mut Context) -> Vec<Statement> {
+    require_non_terminal!(node, NonTerminalType::Statement);
+    require_len!(node, |len| len == 1 || len == 2);
+
+    let child = node.children[0].clone();
+    match &child.node_type {
+        ParseNodeType::NonTerminal(NonTerminalType::AttributeSpecifier) => {
+            read_attributed(node, context)
+        }
+        ParseNodeType::NonTerminal(NonTerminalType::Declaration) => {
+            read_local_declaration(child, Vec::new(), context)
+        }
+        ParseNodeType::NonTerminal(NonTerminalType::Typedef) => {
+            read_typedef(child, Vec::new(), context);
+            Vec::new()
+        }
+        ParseNodeType::NonTerminal(NonTerminalType::Expression) => {
+            vec![read_expression_statement(&child, context)]
+        }
+        ParseNodeType::NonTerminal(NonTerminalType::StaticAssert) => {
+            read_static_assert(child, context)
+                .map(|(condition, message)| {
+                    let condition = RValue::NumberLiteral(NumberType::SignedLongLong(condition));
+                    Statement::StaticAssert(condition, message)
+                })
+                .into_iter()
+                .collect()
+        }
+        ParseNodeType::NonTerminal(NonTerminalType::Block) => {
+            vec![Statement::Block(read_block(child, context))]
+        }
+        ParseNodeType::NonTerminal(_) => vec![read_control(child, context)],
+        ParseNodeType::Terminal(_) => vec![Statement::Empty],
+        ParseNodeType::Error => vec![Statement::Error],
+    }
+}
+
+/// Reads a statement with attributes. Those of a declaration belong to each
+/// declared name instead.
+fn read_attributed(node: Rc<ParseNode>, context: &mut Context) -> Vec<Statement> {
+    let mut node = node;
+    let mut leading = Vec::new();
+    while let ParseNodeType::NonTerminal(NonTerminalType::AttributeSpecifier) =
+        node.children[0].node_type
+    {
+        leading.extend(read_attribute_specifier(node.children[0].clone(), context));
+        node = node.children[1].clone();
+    }
+
+    let child = node.children[0].clone();
+    match child.node_type {
+        ParseNodeType::NonTerminal(NonTerminalType::Declaration) => {
+            return read_local_declaration(child, leading, context)
+        }
+        ParseNodeType::NonTerminal(NonTerminalType::Typedef) => {
+            read_typedef(child, leading, context);
+            return Vec::new();
+        }
+        _ => {}
+    }
+    let span = span_of(&node);
+    let attributes = check_attributes(leading, Target::Statement, span, &mut context.errors);
+    let statement = read_statement(node, context);
+    if attributes.is_empty() {
+        return vec![statement];
+    }
+    if find(&attributes, Effect::Fallthrough).is_some() && statement != Statement::Empty {
+        misplaced_fallthrough(span, context);
+    }
+    vec![Statement::Attributed(attributes, Box::new(statement))]
+}
+
+/// Reads a declaration in a block, where a function declaration declares a
+/// function pointer and a declaration of a tag alone is left out
+fn read_local_declaration(
+    node: Rc<ParseNode>,
+    leading: Vec<Attribute>,
+    context: &mut Context,
+) -> Vec<Statement> {
+    read_declaration(node, leading, LOCAL, context)
+        .into_iter()
+        .filter_map(|declaration| match declaration {
+            TopStatement::Declaration(specifiers, typ, name, initializer) => {
+                Some(Statement::Declaration(specifiers, typ, name, initializer))
+            }
+            TopStatement::ForwardDeclaration(specifiers, returns, name, parameters, prototype) => {
+                let typ = Type::new(BaseType::FunctionPtr(
+                    parameters,
+                    Box::new(returns),
+                    prototype.is_variadic(),
+                ));
+                Some(Statement::Declaration(specifiers, typ, name, None))
+            }
+            TopStatement::StructOrUnionDeclaration(_) | TopStatement::EnumDeclaration(_) => None,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+fn read_expression_statement(node: &Rc<ParseNode>, context: &mut Context) -> Statement {
+    let value = read_expression(node, context);
+    warn_if_discarded(&value, node, context);
+    Statement::Expression(value)
+}
+
+/// Reads a loop, selection or jump statement
+fn read_control(node: Rc<ParseNode>, context: &mut Context) -> Statement {
+    let syntax = SyntaxNode::new_root(node);
+    let statement = |node: cst::Statement, context: &mut Context| {
+        Box::new(read_statement(node.node().clone(), context))
+    };
+    let name = |token: Option<SyntaxNode>| read_identifier(token.unwrap().node().clone());
+
+    if let Some(for_loop) = ForLoop::cast(syntax.clone()) {
+        let initial = for_loop.initial();
+        let initial = match (initial.declaration(), initial.expression()) {
+            (Some(declaration), _) => {
+                context.not_in_c89("a declaration in a for-loop", declaration.node());
+                Some(single(read_local_declaration(
+                    declaration.node().clone(),
+                    Vec::new(),
+                    context,
+                )))
+            }
+            (None, Some(clause)) => Some(read_expression_statement(clause.node(), context)),
+            (None, None) => None,
+        };
+        let condition = for_loop
+            .condition()
+            .expression()
+            .map(|condition| read_expression(condition.node(), context));
+        let step = for_loop.step().expression().map(|step| {
+            let value = read_expression(step.node(), context);
+            warn_if_discarded(&value, step.node(), context);
+            value
+        });
+        return Statement::For(
+            initial.map(Box::new),
+            condition,
+            step,
+            statement(for_loop.body(), context),
+        );
+    }
+    if let Some(while_loop) = WhileLoop::cast(syntax.clone()) {
+        let condition = read_expression(while_loop.condition().node(), context);
+        return Statement::While(condition, statement(while_loop.body(), context));
+    }
+    if let Some(do_while) = DoWhileLoop::cast(syntax.clone()) {
+        let body = statement(do_while.body(), context);
+        return Statement::DoWhile(read_expression(do_while.condition().node(), context), body);
+    }
+    if let Some(if_statement) = If::cast(syntax.clone()) {
+        let condition = read_expression(if_statement.condition().node(), context);
+        let then = statement(if_statement.then_branch(), context);
+        let otherwise = match if_statement.else_branch() {
+            Some(otherwise) => statement(otherwise, context),
+            None => Box::new(Statement::Empty),
+        };
+        return Statement::If(condition, then, otherwise);
+    }
+    if let Some(switch) = Switch::cast(syntax.clone()) {
+        let controlling = read_expression(switch.controlling().node(), context);
+        return Statement::Switch(controlling, statement(switch.body(), context));
+    }
+    if let Some(return_statement) = Return::cast(syntax.clone()) {
+        let value = return_statement.value();
+        return Statement::Return(value.map(|value| read_expression(value.node(), context)));
+    }
+    if let Some(label) = Label::cast(syntax.clone()) {
+        return Statement::Label(name(label.name()), statement(label.statement(), context));
+    }
+    if let Some(case) = Case::cast(syntax.clone()) {
+        let value = case.value();
+        let value = value.map(|value| read_expression(value.node(), context));
+        let body = statement(case.statement(), context);
+        return match value {
+            Some(value) => Statement::Case(value, body),
+            None => Statement::Default(body),
+        };
+    }
+    if let Some(goto) = Goto::cast(syntax.clone()) {
+        return Statement::Goto(name(goto.label()));
+    }
+    match syntax.node_type() {
+        ParseNodeType::NonTerminal(NonTerminalType::Break) => Statement::Break,
+        ParseNodeType::NonTerminal(NonTerminalType::Continue) => Statement::Continue,
+        _ => unreachable!(),
+    }
+}
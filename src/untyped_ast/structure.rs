@@ -0,0 +1,292 @@
+use crate::lex::errors::Span;
+use crate::lex::types::LexItem;
+use crate::parse::types::NonTerminalType;
+use crate::parse::types::ParseNode;
+use crate::parse::types::ParseNodeType;
+use crate::untyped_ast::attribute::{
+    prepend_attributes, read_attribute_specifier, read_attribute_specifiers, read_attributes,
+};
+use crate::untyped_ast::constant::{evaluate, read_static_assert};
+use crate::untyped_ast::context::Context;
+use crate::untyped_ast::declarator::{read_declarator, read_identifier};
+use crate::untyped_ast::errors::{span_of, SemanticError, SemanticErrorType};
+use crate::untyped_ast::layout::size_and_align;
+use crate::untyped_ast::registry::{check_attributes, find, Effect, Target};
+use crate::untyped_ast::specifiers::{check_specifiers, read_type};
+use crate::untyped_ast::types::{BaseType, Member, Specifiers, Type};
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+/// Reads a struct or union declaration, or a reference to a tag declared
+/// elsewhere, which has no members
+pub(super) fn read_struct_or_union(node: Rc<ParseNode>, context: &mut Context) -> Type {
+    require_non_terminal!(node, NonTerminalType::StructOrUnionDeclaration);
+    require_len!(node, |len| len >= 2);
+
+    let mut tag = None;
+    let mut members = None;
+    let mut attributes = Vec::new();
+    for child in &node.children {
+        match child.node_type {
+            ParseNodeType::NonTerminal(NonTerminalType::AttributeSpecifiers) => {
+                attributes.extend(read_attribute_specifiers(child.clone(), context))
+            }
+            ParseNodeType::NonTerminal(NonTerminalType::Attribute) => {
+                attributes.extend(read_attributes(child.clone(), context))
+            }
+            ParseNodeType::NonTerminal(NonTerminalType::Tag) => {
+                tag = Some(read_identifier(child.children[0].clone()))
+            }
+            ParseNodeType::NonTerminal(NonTerminalType::Members) => {
+                // Members can never have a size only known at run time
+                let outer = std::mem::replace(&mut context.block_scope, false);
+                let mut list = Vec::new();
+                read_members(child.clone(), &mut list, context);
+                context.block_scope = outer;
+                check_duplicates(&list, &mut Vec::new(), child, context);
+                members = Some(list);
+            }
+            _ => {}
+        }
+    }
+
+    // Packing a struct or union packs each of its members
+    let attributes = check_attributes(
+        attributes,
+        Target::StructOrUnion,
+        span_of(&node.children[0]),
+        &mut context.errors,
+    );
+    if let (Some(packed), Some(members)) = (find(&attributes, Effect::Packed), &mut members) {
+        for member in members.iter_mut() {
+            member.attributes.push(packed.clone());
+        }
+    }
+
+    let keyword = &node.children[0].children[0].node_type;
+    let typ = Type::new(match keyword {
+        ParseNodeType::Terminal(token) if token.item == LexItem::Union => {
+            BaseType::Union(tag.clone(), members)
+        }
+        _ => BaseType::Struct(tag.clone(), members),
+    });
+    if let (Some(tag), BaseType::Struct(_, Some(_)) | BaseType::Union(_, Some(_))) =
+        (tag, &typ.base)
+    {
+        context.tags.insert(tag, typ.clone());
+    }
+    typ
+}
+
+fn read_members(node: Rc<ParseNode>, members: &mut Vec<Member>, context: &mut Context) {
+    require_non_terminal!(node, NonTerminalType::Members);
+    require_len!(node, |len| len == 0 || len == 2);
+
+    let mut node = node;
+    while let Some(member) = node.children.first().cloned() {
+        read_struct_member(member, members, context);
+        node = node.children[1].clone();
+    }
+}
+
+fn read_struct_member(node: Rc<ParseNode>, members: &mut Vec<Member>, context: &mut Context) {
+    require_non_terminal!(node, NonTerminalType::StructMember);
+    require_len!(node, |len| (1..=3).contains(&len));
+
+    // Attributes at the start of a member belong to each of its declarators
+    let mut node = node;
+    let mut leading = Vec::new();
+    while let ParseNodeType::NonTerminal(NonTerminalType::AttributeSpecifier) =
+        node.children[0].node_type
+    {
+        leading.extend(read_attribute_specifier(node.children[0].clone(), context));
+        node = node.children[1].clone();
+    }
+
+    if node.children.len() == 1 {
+        check_attributes(
+            leading,
+            Target::Nothing,
+            span_of(&node),
+            &mut context.errors,
+        );
+        read_static_assert(node.children[0].clone(), context);
+        return;
+    }
+    let (mut specifiers, typ) = read_type(node.children[0].clone(), context);
+    prepend_attributes(&mut specifiers, leading);
+    check_specifiers(&specifiers, &[], false, span_of(&node), &mut context.errors);
+    if node.children.len() == 3 {
+        read_member_declarators(
+            node.children[1].clone(),
+            &typ,
+            &specifiers,
+            members,
+            context,
+        );
+        return;
+    }
+
+    // Only a struct or union without a tag can be an anonymous member
+    match typ.base {
+        BaseType::Struct(None, Some(_)) | BaseType::Union(None, Some(_)) => members.push(Member {
+            name: None,
+            typ,
+            bitfield: None,
+            alignment: specifiers.alignment,
+            attributes: check_attributes(
+                specifiers.attributes,
+                Target::Member,
+                span_of(&node),
+                &mut context.errors,
+            ),
+        }),
+        _ => context.errors.push(SemanticError {
+            error_type: SemanticErrorType::DeclaresNothing,
+            span: span_of(&node),
+        }),
+    }
+}
+
+fn read_member_declarators(
+    node: Rc<ParseNode>,
+    typ: &Type,
+    specifiers: &Specifiers,
+    members: &mut Vec<Member>,
+    context: &mut Context,
+) {
+    require_non_terminal!(node, NonTerminalType::MemberDeclarators);
+    require_len!(node, |len| len == 1 || len == 3);
+
+    if node.children.len() == 3 {
+        read_member_declarators(node.children[0].clone(), typ, specifiers, members, context);
+    }
+    let member = node.children[node.children.len() - 1].clone();
+    require_non_terminal!(member, NonTerminalType::MemberDeclarator);
+    require_len!(member, |len| len == 2);
+
+    let (name, typ, width, attributes) = match &member.children[0].node_type {
+        ParseNodeType::Terminal(_) => (
+            None,
+            typ.clone(),
+            Some(member.children[1].clone()),
+            specifiers.attributes.clone(),
+        ),
+        _ => {
+            let declarator = read_declarator(member.children[0].clone(), context);
+            let bitfield = &member.children[1];
+            (
+                declarator.name.clone(),
+                declarator.apply(typ.clone()),
+                bitfield.children.get(1).cloned(),
+                declarator.specifiers(specifiers).attributes,
+            )
+        }
+    };
+    let alignment = specifiers.alignment;
+    if typ.base == BaseType::Void {
+        context.errors.push(SemanticError {
+            error_type: SemanticErrorType::VoidObject,
+            span: span_of(&member),
+        });
+    }
+    if width.is_some() && alignment.is_some() {
+        context.errors.push(SemanticError {
+            error_type: SemanticErrorType::AlignmentNotAllowed,
+            span: span_of(&member),
+        });
+    }
+    let bitfield = width.map(|width| read_bitfield_width(width, context));
+    if let Some(width) = bitfield {
+        check_bitfield_type(&typ, width, span_of(&member), context);
+    }
+    members.push(Member {
+        name,
+        typ,
+        bitfield,
+        alignment,
+        attributes: check_attributes(
+            attributes,
+            Target::Member,
+            span_of(&member),
+            &mut context.errors,
+        ),
+    });
+}
+
+fn read_bitfield_width(node: Rc<ParseNode>, context: &mut Context) -> u32 {
+    let error_type = match evaluate(&node, context) {
+        Some(width) => match u32::try_from(width) {
+            Ok(width) => return width,
+            Err(_) => SemanticErrorType::InvalidBitfieldWidth(width),
+        },
+        None => SemanticErrorType::NotConstant,
+    };
+    context.errors.push(SemanticError {
+        error_type,
+        span: span_of(&node),
+    });
+    0
+}
+
+/// Reports a bitfield whose type is not `_Bool` or an integer type, or which is
+/// wider than its type
+fn check_bitfield_type(typ: &Type, width: u32, span: Span, context: &mut Context) {
+    let bits = match typ.base {
+        BaseType::Bool => 1,
+        BaseType::SignedChar
+        | BaseType::UnsignedChar
+        | BaseType::SignedShort
+        | BaseType::UnsignedShort
+        | BaseType::SignedInt
+        | BaseType::UnsignedInt
+        | BaseType::SignedLong
+        | BaseType::UnsignedLong
+        | BaseType::SignedLongLong
+        | BaseType::UnsignedLongLong
+        | BaseType::SignedInt128
+        | BaseType::UnsignedInt128
+        | BaseType::Enum(..) => match size_and_align(typ, context) {
+            Some((size, _)) => size * 8,
+            None => return,
+        },
+        // Only known once expressions have types
+        BaseType::TypeOf(_) => return,
+        _ => {
+            context.errors.push(SemanticError {
+                error_type: SemanticErrorType::InvalidBitfieldType,
+                span,
+            });
+            return;
+        }
+    };
+    if u64::from(width) > bits {
+        context.errors.push(SemanticError {
+            error_type: SemanticErrorType::BitfieldTooWide(width),
+            span,
+        });
+    }
+}
+
+/// Reports members declared twice, including the members of anonymous
+/// structs and unions, which belong to the enclosing one
+fn check_duplicates(
+    members: &[Member],
+    names: &mut Vec<String>,
+    node: &Rc<ParseNode>,
+    context: &mut Context,
+) {
+    for member in members {
+        match (&member.name, &member.typ.base) {
+            (Some(name), _) if names.contains(name) => context.errors.push(SemanticError {
+                error_type: SemanticErrorType::DuplicateMember(name.clone()),
+                span: span_of(node),
+            }),
+            (Some(name), _) => names.push(name.clone()),
+            (None, BaseType::Struct(_, Some(inner))) | (None, BaseType::Union(_, Some(inner))) => {
+                check_duplicates(inner, names, node, context)
+            }
+            (None, _) => {}
+        }
+    }
+}
@@ -1,30 +1,1800 @@
+use crate::lex::types::{LexItem, NumberType};
 use crate::lex::Lexer;
-use crate::parse::parser::parse;
-use crate::untyped_ast::build_untyped_ast;
-use crate::untyped_ast::types::TopStatement;
-use crate::untyped_ast::types::Type;
-use crate::untyped_ast::types::{BaseType, Root};
+use crate::parse::parser::{parse, parse_with};
+use crate::standard::Standard;
+use crate::untyped_ast::errors::{SemanticErrorType, Severity};
+use crate::untyped_ast::fragment::{
+    parse_declaration, parse_expression, parse_statement, parse_type_name,
+};
+use crate::untyped_ast::types::{Attribute, AttributeArgument, RValue, TopStatement};
+use crate::untyped_ast::types::{BaseType, Member, Qualifiers, Root, Specifiers, StorageClass};
+use crate::untyped_ast::types::{
+    Block, Designator, Initializer, LValue, Prototype, Statement, Type,
+};
+use crate::untyped_ast::{build_untyped_ast, build_untyped_ast_with};
 
 #[test]
 fn test_forward_declaration() {
     let text = "int a(int arg); int b(int, int a2); \n\n";
-    let ast = build_untyped_ast(parse(Lexer::new(text.chars())).unwrap());
+    let (ast, errors) = build_untyped_ast(parse(Lexer::new(text.chars())).unwrap());
+    assert_eq!(errors, vec![]);
     assert_eq!(
         ast,
         Root(vec![
             TopStatement::ForwardDeclaration(
+                Specifiers::default(),
                 Type::new(BaseType::SignedInt),
                 "a".to_string(),
-                vec![Type::new(BaseType::SignedInt)]
+                vec![Type::new(BaseType::SignedInt)],
+                Prototype::Fixed
             ),
             TopStatement::ForwardDeclaration(
+                Specifiers::default(),
                 Type::new(BaseType::SignedInt),
                 "b".to_string(),
                 vec![
                     Type::new(BaseType::SignedInt),
                     Type::new(BaseType::SignedInt)
-                ]
+                ],
+                Prototype::Fixed
             ),
         ])
     )
 }
+
+fn build(text: &str) -> Vec<TopStatement> {
+    let (ast, errors) = build_untyped_ast(parse(Lexer::new(text.chars())).unwrap());
+    assert_eq!(errors, vec![]);
+    ast.0
+}
+
+fn build_errors(text: &str) -> Vec<SemanticErrorType> {
+    let (_, errors) = build_untyped_ast(parse(Lexer::new(text.chars())).unwrap());
+    errors.into_iter().map(|error| error.error_type).collect()
+}
+
+fn pointer(typ: Type) -> Type {
+    Type::new(BaseType::Pointer(Box::new(typ)))
+}
+
+#[test]
+fn test_array_declaration() {
+    let int = Type::new(BaseType::SignedInt);
+    assert_eq!(
+        build("int a[10]; int b[2][3]; int *c[4]; int (*d)[5];"),
+        vec![
+            TopStatement::Declaration(
+                Specifiers::default(),
+                Type::new(BaseType::Array(Box::new(int.clone()), Some(10))),
+                "a".to_string(),
+                None
+            ),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                Type::new(BaseType::Array(
+                    Box::new(Type::new(BaseType::Array(Box::new(int.clone()), Some(3)))),
+                    Some(2)
+                )),
+                "b".to_string(),
+                None
+            ),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                Type::new(BaseType::Array(Box::new(pointer(int.clone())), Some(4))),
+                "c".to_string(),
+                None
+            ),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                pointer(Type::new(BaseType::Array(Box::new(int), Some(5)))),
+                "d".to_string(),
+                None
+            ),
+        ]
+    )
+}
+
+#[test]
+fn test_function_pointer_declaration() {
+    assert_eq!(
+        build("int (*cb)(int, void *);"),
+        vec![TopStatement::Declaration(
+            Specifiers::default(),
+            Type::new(BaseType::FunctionPtr(
+                vec![
+                    Type::new(BaseType::SignedInt),
+                    pointer(Type::new(BaseType::Void))
+                ],
+                Box::new(Type::new(BaseType::SignedInt)),
+                false
+            )),
+            "cb".to_string(),
+            None
+        )]
+    )
+}
+
+#[test]
+fn test_pointers_to_function_pointers() {
+    let callback = Type::new(BaseType::FunctionPtr(
+        vec![Type::new(BaseType::SignedInt)],
+        Box::new(Type::new(BaseType::SignedInt)),
+        false,
+    ));
+    let constant = Qualifiers {
+        constant: true,
+        ..Qualifiers::default()
+    };
+    assert_eq!(
+        build("int (**pp)(int); int (*const *cp)(int); int (* volatile c)(int);"),
+        vec![
+            TopStatement::Declaration(
+                Specifiers::default(),
+                pointer(callback.clone()),
+                "pp".to_string(),
+                None
+            ),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                pointer(Type {
+                    qualifiers: constant,
+                    ..callback.clone()
+                }),
+                "cp".to_string(),
+                None
+            ),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                Type {
+                    qualifiers: Qualifiers {
+                        volatile: true,
+                        ..Qualifiers::default()
+                    },
+                    ..callback
+                },
+                "c".to_string(),
+                None
+            ),
+        ]
+    )
+}
+
+#[test]
+fn test_variable_length_arrays() {
+    let int = Type::new(BaseType::SignedInt);
+    let n = || Box::new(RValue::LValue(LValue::Variable("n".to_string())));
+    assert_eq!(
+        body("void f(int n) { int a[n]; }"),
+        vec![Statement::Declaration(
+            Specifiers::default(),
+            Type::new(BaseType::VariableArray(Box::new(int.clone()), n())),
+            "a".to_string(),
+            None
+        )]
+    );
+    assert_eq!(
+        build("void g(int n, int a[n]);"),
+        vec![TopStatement::ForwardDeclaration(
+            Specifiers::default(),
+            Type::new(BaseType::Void),
+            "g".to_string(),
+            vec![
+                int.clone(),
+                Type::new(BaseType::VariableArray(Box::new(int), n()))
+            ],
+            Prototype::Fixed
+        )]
+    );
+    // Only a block or a parameter list can have one, not a struct
+    assert_eq!(
+        build_errors("int n; int a[n]; void f(void) { struct { int b[n]; } s; }"),
+        vec![
+            SemanticErrorType::NotConstant,
+            SemanticErrorType::NotConstant
+        ]
+    );
+}
+
+#[test]
+fn test_array_parameter() {
+    assert_eq!(
+        build("int main(int argc, char *argv[]);"),
+        vec![TopStatement::ForwardDeclaration(
+            Specifiers::default(),
+            Type::new(BaseType::SignedInt),
+            "main".to_string(),
+            vec![
+                Type::new(BaseType::SignedInt),
+                Type::new(BaseType::Array(
+                    Box::new(pointer(Type::new(BaseType::SignedChar))),
+                    None
+                ))
+            ],
+            Prototype::Fixed
+        )]
+    )
+}
+
+#[test]
+fn test_function_returning_function_pointer() {
+    let handler = Type::new(BaseType::FunctionPtr(
+        vec![Type::new(BaseType::SignedInt)],
+        Box::new(Type::new(BaseType::Void)),
+        false,
+    ));
+    assert_eq!(
+        build("void (*signal(int, void (*)(int)))(int); unsigned long f(void);"),
+        vec![
+            TopStatement::ForwardDeclaration(
+                Specifiers::default(),
+                handler.clone(),
+                "signal".to_string(),
+                vec![Type::new(BaseType::SignedInt), handler],
+                Prototype::Fixed
+            ),
+            TopStatement::ForwardDeclaration(
+                Specifiers::default(),
+                Type::new(BaseType::UnsignedLong),
+                "f".to_string(),
+                vec![],
+                Prototype::Fixed
+            ),
+        ]
+    )
+}
+
+#[test]
+fn test_function_definition() {
+    let int = Type::new(BaseType::SignedInt);
+    let variable = |name: &str| RValue::LValue(LValue::Variable(name.to_string()));
+    let number = |value| RValue::NumberLiteral(NumberType::SignedInt(value));
+    assert_eq!(
+        build(
+            "int sum(int *values, int count) {
+                 int total = 0, i;
+                 for (i = 0; i < count; i++)
+                     total += values[i];
+                 if (!count) return -1;
+                 return total;
+             }"
+        ),
+        vec![TopStatement::FunctionDeclaration(
+            Specifiers::default(),
+            int.clone(),
+            "sum".to_string(),
+            vec![
+                (pointer(int.clone()), "values".to_string()),
+                (int.clone(), "count".to_string()),
+            ],
+            Prototype::Fixed,
+            Block(vec![
+                Statement::Declaration(
+                    Specifiers::default(),
+                    int.clone(),
+                    "total".to_string(),
+                    Some(Box::new(Initializer::Expression(number(0))))
+                ),
+                Statement::Declaration(Specifiers::default(), int, "i".to_string(), None),
+                Statement::For(
+                    Some(Box::new(Statement::Expression(RValue::Assign(
+                        Box::new(LValue::Variable("i".to_string())),
+                        Box::new(number(0))
+                    )))),
+                    Some(RValue::Less(
+                        Box::new(variable("i")),
+                        Box::new(variable("count"))
+                    )),
+                    Some(RValue::PostIncrement(LValue::Variable("i".to_string()))),
+                    Box::new(Statement::Expression(RValue::AddAssign(
+                        Box::new(LValue::Variable("total".to_string())),
+                        Box::new(RValue::LValue(LValue::Subscript(
+                            Box::new(variable("values")),
+                            Box::new(variable("i"))
+                        )))
+                    )))
+                ),
+                Statement::If(
+                    RValue::LogicalNot(Box::new(variable("count"))),
+                    Box::new(Statement::Return(Some(RValue::Negative(Box::new(number(
+                        1
+                    )))))),
+                    Box::new(Statement::Empty)
+                ),
+                Statement::Return(Some(variable("total"))),
+            ])
+        )]
+    );
+}
+
+#[test]
+fn test_typedef_names() {
+    let constant = Type {
+        base: BaseType::SignedInt,
+        qualifiers: Qualifiers {
+            constant: true,
+            ..Qualifiers::default()
+        },
+    };
+    let to_constant = pointer(constant.clone());
+    let statements = build(
+        "typedef const int C;
+         typedef C *P;
+         P p;
+         int f(C c) {
+             volatile P q = (P) 0;
+             { typedef char C; C d; }
+             C e;
+             return c;
+         }",
+    );
+    assert_eq!(
+        statements[..3],
+        [
+            TopStatement::TypedefDeclaration("C".to_string(), constant.clone()),
+            TopStatement::TypedefDeclaration("P".to_string(), to_constant.clone()),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                to_constant.clone(),
+                "p".to_string(),
+                None
+            ),
+        ]
+    );
+    let variable = |typ: Type, name: &str, initializer| {
+        Statement::Declaration(Specifiers::default(), typ, name.to_string(), initializer)
+    };
+    let cast = RValue::Cast(
+        to_constant.clone(),
+        Box::new(RValue::NumberLiteral(NumberType::SignedInt(0))),
+    );
+    assert_eq!(
+        statements[3],
+        TopStatement::FunctionDeclaration(
+            Specifiers::default(),
+            Type::new(BaseType::SignedInt),
+            "f".to_string(),
+            vec![(constant.clone(), "c".to_string())],
+            Prototype::Fixed,
+            Block(vec![
+                variable(
+                    Type {
+                        qualifiers: Qualifiers {
+                            volatile: true,
+                            ..Qualifiers::default()
+                        },
+                        ..to_constant
+                    },
+                    "q",
+                    Some(Box::new(Initializer::Expression(cast)))
+                ),
+                Statement::Block(Block(vec![variable(
+                    Type::new(BaseType::SignedChar),
+                    "d",
+                    None
+                )])),
+                variable(constant, "e", None),
+                Statement::Return(Some(RValue::LValue(LValue::Variable("c".to_string())))),
+            ])
+        )
+    );
+}
+
+/// The body of the only function defined in `text`
+fn body(text: &str) -> Vec<Statement> {
+    match build(text).pop() {
+        Some(TopStatement::FunctionDeclaration(.., body)) => body.0,
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_statements() {
+    let n = || RValue::LValue(LValue::Variable("n".to_string()));
+    let number = |value| RValue::NumberLiteral(NumberType::SignedInt(value));
+    assert_eq!(
+        body(
+            "int f(int n) {
+                 for (;;) {
+                     switch (n) {
+                     case 1:
+                         continue;
+                     default:
+                         break;
+                     }
+                     ;
+                 }
+                 while (n) n--;
+                 do n++; while (n < 10);
+                 if (n) n = 1; else goto end;
+             end:
+                 return;
+             }"
+        ),
+        vec![
+            Statement::For(
+                None,
+                None,
+                None,
+                Box::new(Statement::Block(Block(vec![
+                    Statement::Switch(
+                        n(),
+                        Box::new(Statement::Block(Block(vec![
+                            Statement::Case(number(1), Box::new(Statement::Continue)),
+                            Statement::Default(Box::new(Statement::Break)),
+                        ])))
+                    ),
+                    Statement::Empty,
+                ])))
+            ),
+            Statement::While(
+                n(),
+                Box::new(Statement::Expression(RValue::PostDecrement(
+                    LValue::Variable("n".to_string())
+                )))
+            ),
+            Statement::DoWhile(
+                RValue::Less(Box::new(n()), Box::new(number(10))),
+                Box::new(Statement::Expression(RValue::PostIncrement(
+                    LValue::Variable("n".to_string())
+                )))
+            ),
+            Statement::If(
+                n(),
+                Box::new(Statement::Expression(RValue::Assign(
+                    Box::new(LValue::Variable("n".to_string())),
+                    Box::new(number(1))
+                ))),
+                Box::new(Statement::Goto("end".to_string()))
+            ),
+            Statement::Label("end".to_string(), Box::new(Statement::Return(None))),
+        ]
+    );
+}
+
+#[test]
+fn test_label_named_like_a_type() {
+    // Labels have their own namespace, so a typedef name can be one
+    assert_eq!(
+        body("typedef int T; void f(void) { goto T; T: ; }"),
+        vec![
+            Statement::Goto("T".to_string()),
+            Statement::Label("T".to_string(), Box::new(Statement::Empty)),
+        ]
+    );
+}
+
+#[test]
+fn test_initializers() {
+    let int = Type::new(BaseType::SignedInt);
+    let array = |typ: &Type, size| Type::new(BaseType::Array(Box::new(typ.clone()), size));
+    let number = |value| RValue::NumberLiteral(NumberType::SignedInt(value));
+    let value = |value| (vec![], Initializer::Expression(number(value)));
+    let index = |value| Designator::Index(number(value));
+    assert_eq!(
+        build(
+            "int a[3] = { 1, 2, 3, };
+             int b[2][2] = { { 1, 2 }, [1] = { 3 }, [1][1] = 4 };
+             int c[10] = { [2] = 1, 7 };
+             int f(void) {
+                 int *p = (int[2]){ [1] = 1 };
+                 return (int[]){ 1, 2, }[1];
+             }"
+        ),
+        vec![
+            TopStatement::Declaration(
+                Specifiers::default(),
+                array(&int, Some(3)),
+                "a".to_string(),
+                Some(Box::new(Initializer::List(vec![
+                    value(1),
+                    value(2),
+                    value(3)
+                ])))
+            ),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                array(&array(&int, Some(2)), Some(2)),
+                "b".to_string(),
+                Some(Box::new(Initializer::List(vec![
+                    (vec![], Initializer::List(vec![value(1), value(2)])),
+                    (vec![index(1)], Initializer::List(vec![value(3)])),
+                    (vec![index(1), index(1)], Initializer::Expression(number(4))),
+                ])))
+            ),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                array(&int, Some(10)),
+                "c".to_string(),
+                Some(Box::new(Initializer::List(vec![
+                    (vec![index(2)], Initializer::Expression(number(1))),
+                    value(7),
+                ])))
+            ),
+            TopStatement::FunctionDeclaration(
+                Specifiers::default(),
+                int.clone(),
+                "f".to_string(),
+                vec![],
+                Prototype::Fixed,
+                Block(vec![
+                    Statement::Declaration(
+                        Specifiers::default(),
+                        pointer(int.clone()),
+                        "p".to_string(),
+                        Some(Box::new(Initializer::Expression(RValue::CompoundLiteral(
+                            array(&int, Some(2)),
+                            vec![(vec![index(1)], Initializer::Expression(number(1)))]
+                        ))))
+                    ),
+                    Statement::Return(Some(RValue::LValue(LValue::Subscript(
+                        Box::new(RValue::CompoundLiteral(
+                            array(&int, None),
+                            vec![value(1), value(2)]
+                        )),
+                        Box::new(number(1))
+                    )))),
+                ])
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_specifiers_in_any_order() {
+    let qualified = |base, qualifiers| Type { base, qualifiers };
+    let constant = Qualifiers {
+        constant: true,
+        ..Qualifiers::default()
+    };
+    let atomic = Qualifiers {
+        atomic: true,
+        ..Qualifiers::default()
+    };
+    let storage = |storage| Specifiers {
+        storage: Some(storage),
+        ..Specifiers::default()
+    };
+    assert_eq!(
+        build(
+            "static const unsigned long a;\n\
+             long _Thread_local const static unsigned b;\n\
+             inline int _Noreturn extern f(register int x);\n\
+             int *const restrict volatile p;\n\
+             _Atomic(int) c;\n\
+             int _Atomic d;"
+        ),
+        vec![
+            TopStatement::Declaration(
+                storage(StorageClass::Static),
+                qualified(BaseType::UnsignedLong, constant),
+                "a".to_string(),
+                None
+            ),
+            TopStatement::Declaration(
+                Specifiers {
+                    thread_local: true,
+                    ..storage(StorageClass::Static)
+                },
+                qualified(BaseType::UnsignedLong, constant),
+                "b".to_string(),
+                None
+            ),
+            TopStatement::ForwardDeclaration(
+                Specifiers {
+                    inline: true,
+                    noreturn: true,
+                    ..storage(StorageClass::Extern)
+                },
+                Type::new(BaseType::SignedInt),
+                "f".to_string(),
+                vec![Type::new(BaseType::SignedInt)],
+                Prototype::Fixed
+            ),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                qualified(
+                    BaseType::Pointer(Box::new(Type::new(BaseType::SignedInt))),
+                    Qualifiers {
+                        constant: true,
+                        restrict: true,
+                        volatile: true,
+                        atomic: false,
+                    }
+                ),
+                "p".to_string(),
+                None
+            ),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                qualified(BaseType::SignedInt, atomic),
+                "c".to_string(),
+                None
+            ),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                qualified(BaseType::SignedInt, atomic),
+                "d".to_string(),
+                None
+            ),
+        ]
+    )
+}
+
+#[test]
+fn test_invalid_specifiers() {
+    assert_eq!(
+        build_errors("static extern int a;"),
+        vec![SemanticErrorType::ConflictingStorageClasses(
+            LexItem::Static,
+            LexItem::Extern
+        )]
+    );
+    assert_eq!(
+        build_errors("static int static a;"),
+        vec![SemanticErrorType::DuplicateSpecifier(LexItem::Static)]
+    );
+    assert_eq!(
+        build_errors("restrict int a;"),
+        vec![SemanticErrorType::RestrictWithoutPointer]
+    );
+    assert_eq!(
+        build_errors("long char a; unsigned signed b; long long long c; short double d;"),
+        vec![
+            SemanticErrorType::InvalidTypeSpecifiers(vec![LexItem::Long, LexItem::Char]),
+            SemanticErrorType::InvalidTypeSpecifiers(vec![LexItem::Unsigned, LexItem::Signed]),
+            SemanticErrorType::InvalidTypeSpecifiers(vec![LexItem::Long; 3]),
+            SemanticErrorType::InvalidTypeSpecifiers(vec![LexItem::Short, LexItem::Double]),
+        ]
+    );
+    assert_eq!(
+        build_errors("inline int a; auto int b; int f(static int x);"),
+        vec![
+            SemanticErrorType::FunctionSpecifierOnObject(LexItem::Inline),
+            SemanticErrorType::StorageClassNotAllowed(LexItem::Auto),
+            SemanticErrorType::StorageClassNotAllowed(LexItem::Static),
+        ]
+    );
+    assert_eq!(
+        build_errors("_Thread_local register int a; typedef static int b;"),
+        vec![
+            SemanticErrorType::ConflictingStorageClasses(LexItem::ThreadLocal, LexItem::Register),
+            SemanticErrorType::StorageClassNotAllowed(LexItem::Static),
+        ]
+    );
+}
+
+#[test]
+fn test_operator_grouping() {
+    let expression = |text: &str| match &build(&format!("void f() {{ {}; }}", text))[0] {
+        TopStatement::FunctionDeclaration(.., Block(statements)) => match &statements[0] {
+            Statement::Expression(value) => value.clone(),
+            statement => panic!("{:?}", statement),
+        },
+        statement => panic!("{:?}", statement),
+    };
+    let lvalue = |name: &str| Box::new(LValue::Variable(name.to_string()));
+    let variable = |name: &str| Box::new(RValue::LValue(*lvalue(name)));
+    assert_eq!(
+        expression("a = b -= c - d - e * f"),
+        RValue::Assign(
+            lvalue("a"),
+            Box::new(RValue::SubAssign(
+                lvalue("b"),
+                Box::new(RValue::Sub(
+                    Box::new(RValue::Sub(variable("c"), variable("d"))),
+                    Box::new(RValue::Mul(variable("e"), variable("f")))
+                ))
+            ))
+        )
+    );
+    assert_eq!(
+        expression("a || b ? c : d ? e : f"),
+        RValue::Ternary(
+            Box::new(RValue::LogicalOr(variable("a"), variable("b"))),
+            variable("c"),
+            Box::new(RValue::Ternary(variable("d"), variable("e"), variable("f")))
+        )
+    );
+    assert_eq!(
+        expression("a, b = c"),
+        RValue::Comma(
+            variable("a"),
+            Box::new(RValue::Assign(lvalue("b"), variable("c")))
+        )
+    );
+}
+
+#[test]
+fn test_fragments() {
+    let int = Type::new(BaseType::SignedInt);
+    let variable = |name: &str| Box::new(RValue::LValue(LValue::Variable(name.to_string())));
+    let lex = |text: &'static str| Lexer::new(text.chars());
+
+    let (value, errors) = parse_expression(lex("a * (b + c)")).unwrap();
+    assert_eq!(
+        value,
+        RValue::Mul(
+            variable("a"),
+            Box::new(RValue::Add(variable("b"), variable("c")))
+        )
+    );
+    assert_eq!(errors, vec![]);
+    let (_, errors) = parse_expression(lex("1 = 2")).unwrap();
+    assert_eq!(errors[0].error_type, SemanticErrorType::NotAnLValue);
+
+    let (statement, _) = parse_statement(lex("return a;")).unwrap();
+    assert_eq!(statement, Statement::Return(Some(*variable("a"))));
+    let (statement, _) = parse_statement(lex("int x, y;")).unwrap();
+    assert!(matches!(statement, Statement::Declarations(declarations) if declarations.len() == 2));
+    // Nor does the for-init clause open a scope of its own
+    let (statement, _) = parse_statement(lex("for (int i, j;;);")).unwrap();
+    let initial = match statement {
+        Statement::For(Some(initial), ..) => *initial,
+        _ => unreachable!(),
+    };
+    assert!(matches!(initial, Statement::Declarations(declarations) if declarations.len() == 2));
+
+    let (typ, _) = parse_type_name(lex("int *[4]")).unwrap();
+    assert_eq!(
+        typ,
+        Type::new(BaseType::Array(Box::new(pointer(int.clone())), Some(4)))
+    );
+
+    let (declarations, _) = parse_declaration(lex("int x, *y;")).unwrap();
+    assert_eq!(
+        declarations,
+        vec![
+            TopStatement::Declaration(Specifiers::default(), int.clone(), "x".to_string(), None),
+            TopStatement::Declaration(Specifiers::default(), pointer(int), "y".to_string(), None),
+        ]
+    );
+
+    // Only all of the input is a fragment
+    assert!(parse_expression(lex("a;")).is_err());
+    assert!(parse_declaration(lex("int x")).is_err());
+}
+
+#[test]
+fn test_invalid_function_definitions() {
+    assert_eq!(
+        build_errors("int f(int) { 1 = 2; return 0; }"),
+        vec![
+            SemanticErrorType::UnnamedParameter,
+            SemanticErrorType::NotAnLValue,
+        ]
+    );
+}
+
+#[test]
+fn test_void_bool_and_complex() {
+    assert_eq!(
+        build("void f(void); _Bool b; double _Complex z; _Complex float w;"),
+        vec![
+            TopStatement::ForwardDeclaration(
+                Specifiers::default(),
+                Type::new(BaseType::Void),
+                "f".to_string(),
+                vec![],
+                Prototype::Fixed
+            ),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                Type::new(BaseType::Bool),
+                "b".to_string(),
+                None
+            ),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                Type::new(BaseType::DoubleComplex),
+                "z".to_string(),
+                None
+            ),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                Type::new(BaseType::FloatComplex),
+                "w".to_string(),
+                None
+            ),
+        ]
+    );
+    assert_eq!(
+        build_errors("void a; int f(void x); _Complex c; _Complex int d;"),
+        vec![
+            SemanticErrorType::VoidObject,
+            SemanticErrorType::VoidObject,
+            SemanticErrorType::InvalidTypeSpecifiers(vec![LexItem::Complex]),
+            SemanticErrorType::InvalidTypeSpecifiers(vec![LexItem::Complex, LexItem::Int]),
+        ]
+    );
+}
+
+#[test]
+fn test_enum_declaration() {
+    let color = BaseType::Enum(
+        Some("color".to_string()),
+        vec![
+            ("RED".to_string(), 0),
+            ("GREEN".to_string(), 5),
+            ("BLUE".to_string(), 6),
+            ("ALIAS".to_string(), 12),
+        ],
+    );
+    assert_eq!(
+        build(
+            "enum color { RED, GREEN = 2 + 3, BLUE, ALIAS = BLUE * 2, };
+             enum color c; int a[BLUE];"
+        ),
+        vec![
+            TopStatement::EnumDeclaration(Type::new(color)),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                Type::new(BaseType::Enum(Some("color".to_string()), vec![])),
+                "c".to_string(),
+                None
+            ),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                Type::new(BaseType::Array(
+                    Box::new(Type::new(BaseType::SignedInt)),
+                    Some(6)
+                )),
+                "a".to_string(),
+                None
+            ),
+        ]
+    );
+    assert_eq!(
+        build("enum { A = (1 < 2) ? -1 : 1, B } e;"),
+        vec![TopStatement::Declaration(
+            Specifiers::default(),
+            Type::new(BaseType::Enum(
+                None,
+                vec![("A".to_string(), -1), ("B".to_string(), 0)]
+            )),
+            "e".to_string(),
+            None
+        )]
+    );
+    assert_eq!(
+        build_errors("int x; enum e { A = x, B = 1 / 0 }; int a[-1]; long enum e l;"),
+        vec![
+            SemanticErrorType::NotConstant,
+            SemanticErrorType::NotConstant,
+            SemanticErrorType::NegativeArraySize(-1),
+            SemanticErrorType::InvalidTypeSpecifiers(vec![LexItem::Long, LexItem::Enum]),
+        ]
+    );
+    assert_eq!(
+        build_errors("enum { A = 0x7fffffff, B, C = -0x7fffffff - 1, D = 0x100000000 };"),
+        vec![
+            SemanticErrorType::EnumeratorOutOfRange("B".to_string()),
+            SemanticErrorType::EnumeratorOutOfRange("D".to_string()),
+        ]
+    );
+    assert_eq!(
+        body("int f(void) { enum { A = 1 }; return 0; }"),
+        vec![Statement::Return(Some(RValue::NumberLiteral(
+            NumberType::SignedInt(0)
+        )))]
+    );
+}
+
+fn member(name: Option<&str>, typ: Type, bitfield: Option<u32>) -> Member {
+    Member {
+        name: name.map(str::to_string),
+        typ,
+        bitfield,
+        alignment: None,
+        attributes: Vec::new(),
+    }
+}
+
+#[test]
+fn test_declarator_lists() {
+    let int = Type::new(BaseType::SignedInt);
+    let declaration = |typ: Type, name: &str| {
+        TopStatement::Declaration(Specifiers::default(), typ, name.to_string(), None)
+    };
+    assert_eq!(
+        build("int a, *b, c[3], f(void); typedef int t, *p;"),
+        vec![
+            declaration(int.clone(), "a"),
+            declaration(pointer(int.clone()), "b"),
+            declaration(
+                Type::new(BaseType::Array(Box::new(int.clone()), Some(3))),
+                "c"
+            ),
+            TopStatement::ForwardDeclaration(
+                Specifiers::default(),
+                int.clone(),
+                "f".to_string(),
+                vec![],
+                Prototype::Fixed
+            ),
+            TopStatement::TypedefDeclaration("t".to_string(), int.clone()),
+            TopStatement::TypedefDeclaration("p".to_string(), pointer(int)),
+        ]
+    );
+}
+
+#[test]
+fn test_struct_declaration() {
+    let int = Type::new(BaseType::SignedInt);
+    let node = |members| Type::new(BaseType::Struct(Some("node".to_string()), members));
+    let anonymous = Type::new(BaseType::Struct(
+        None,
+        Some(vec![
+            member(Some("a"), Type::new(BaseType::SignedChar), None),
+            member(Some("b"), Type::new(BaseType::SignedChar), None),
+        ]),
+    ));
+    assert_eq!(
+        build(
+            "struct node;
+             struct node { int value; struct node *next; } *head;
+             union value { int i; struct { char a, b; }; };
+             struct flags { unsigned ready : 1, : 3, mode : 2 + 2; };"
+        ),
+        vec![
+            TopStatement::StructOrUnionDeclaration(node(None)),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                pointer(node(Some(vec![
+                    member(Some("value"), int.clone(), None),
+                    member(Some("next"), pointer(node(None)), None),
+                ]))),
+                "head".to_string(),
+                None
+            ),
+            TopStatement::StructOrUnionDeclaration(Type::new(BaseType::Union(
+                Some("value".to_string()),
+                Some(vec![
+                    member(Some("i"), int, None),
+                    member(None, anonymous, None)
+                ])
+            ))),
+            TopStatement::StructOrUnionDeclaration(Type::new(BaseType::Struct(
+                Some("flags".to_string()),
+                Some(vec![
+                    member(Some("ready"), Type::new(BaseType::UnsignedInt), Some(1)),
+                    member(None, Type::new(BaseType::UnsignedInt), Some(3)),
+                    member(Some("mode"), Type::new(BaseType::UnsignedInt), Some(4)),
+                ])
+            ))),
+        ]
+    );
+    let point = Type::new(BaseType::Struct(
+        Some("point".to_string()),
+        Some(vec![
+            member(Some("x"), Type::new(BaseType::SignedInt), None),
+            member(Some("y"), Type::new(BaseType::SignedInt), None),
+        ]),
+    ));
+    let number =
+        |value| Initializer::Expression(RValue::NumberLiteral(NumberType::SignedInt(value)));
+    assert_eq!(
+        build("struct point { int x, y; } p = { .y = 2, .x = 1 };"),
+        vec![TopStatement::Declaration(
+            Specifiers::default(),
+            point,
+            "p".to_string(),
+            Some(Box::new(Initializer::List(vec![
+                (vec![Designator::Member("y".to_string())], number(2)),
+                (vec![Designator::Member("x".to_string())], number(1)),
+            ])))
+        )]
+    );
+}
+
+#[test]
+fn test_invalid_struct_declarations() {
+    assert_eq!(
+        build_errors(
+            "int; struct { int x; };
+             struct s { int a; struct { int a; }; int; void v; };
+             struct b { int x : -1; static int y; };"
+        ),
+        vec![
+            SemanticErrorType::DeclaresNothing,
+            SemanticErrorType::DeclaresNothing,
+            SemanticErrorType::DeclaresNothing,
+            SemanticErrorType::VoidObject,
+            SemanticErrorType::DuplicateMember("a".to_string()),
+            SemanticErrorType::InvalidBitfieldWidth(-1),
+            SemanticErrorType::StorageClassNotAllowed(LexItem::Static),
+        ]
+    );
+    assert_eq!(
+        build_errors(
+            "struct c { int a : 40; float f : 3; _Bool b : 2; char c : 8; long l : 64; };"
+        ),
+        vec![
+            SemanticErrorType::BitfieldTooWide(40),
+            SemanticErrorType::InvalidBitfieldType,
+            SemanticErrorType::BitfieldTooWide(2),
+        ]
+    );
+}
+
+#[test]
+fn test_static_assert() {
+    assert_eq!(
+        build("_Static_assert(sizeof(int) == 4, \"int\");"),
+        vec![TopStatement::StaticAssert(
+            RValue::NumberLiteral(NumberType::SignedLongLong(1)),
+            b"int".to_vec()
+        )]
+    );
+    let text = "int a;\n_Static_assert(sizeof(long) < 8, \"long\");";
+    let (_, errors) = build_untyped_ast(parse(Lexer::new(text.chars())).unwrap());
+    assert_eq!(
+        errors
+            .iter()
+            .map(|error| (error.error_type.clone(), error.span.start.character))
+            .collect::<Vec<_>>(),
+        vec![(
+            SemanticErrorType::StaticAssertionFailed("long".to_string()),
+            7
+        )]
+    );
+    assert_eq!(
+        build_errors("int x; _Static_assert(x, \"x\"); struct s { _Static_assert(0, \"m\"); };"),
+        vec![
+            SemanticErrorType::NotConstant,
+            SemanticErrorType::StaticAssertionFailed("m".to_string())
+        ]
+    );
+}
+
+#[test]
+fn test_sizeof_and_alignof() {
+    // Each assertion holds on x86-64
+    build(
+        "struct padded { char c; int i; };
+         struct bits { unsigned a : 1, : 3, b : 4; char c; };
+         struct wide { char c; _Alignas(16) char d; };
+         union either { char c[5]; int i; };
+         struct list { struct list *next; double values[]; };
+         enum e { A };
+         _Static_assert(sizeof(struct padded) == 8, \"padded\");
+         _Static_assert(sizeof(struct bits) == 4, \"bits\");
+         _Static_assert(_Alignof(struct wide) == 16 && sizeof(struct wide) == 32, \"wide\");
+         _Static_assert(sizeof(union either) == 8, \"either\");
+         _Static_assert(sizeof(struct list) == 8, \"list\");
+         _Static_assert(sizeof(enum e) == 4 && sizeof(char *[3]) == 24, \"misc\");
+         _Static_assert(sizeof(long double _Complex) == 16, \"complex\");",
+    );
+    assert_eq!(
+        build_errors("struct opaque; int a[sizeof(struct opaque)]; int b[sizeof(void)];"),
+        vec![
+            SemanticErrorType::NotConstant,
+            SemanticErrorType::NotConstant
+        ]
+    );
+}
+
+#[test]
+fn test_packed_layout() {
+    // Each assertion holds on x86-64
+    build(
+        "struct [[gnu::packed]] p { char c; int i; };
+         struct q { char c; int i [[gnu::packed]]; };
+         struct [[gnu::packed]] r { char c; _Alignas(4) int i; };
+         struct [[gnu::packed]] b { unsigned a : 7; unsigned b : 10; };
+         union [[gnu::packed]] u { char c[5]; int i; };
+         _Static_assert(sizeof(struct p) == 5 && _Alignof(struct p) == 1, \"p\");
+         _Static_assert(sizeof(struct q) == 5, \"q\");
+         _Static_assert(sizeof(struct r) == 8 && _Alignof(struct r) == 4, \"r\");
+         _Static_assert(sizeof(struct b) == 3, \"b\");
+         _Static_assert(sizeof(union u) == 5, \"u\");",
+    );
+    build_gnu(
+        "struct __attribute__((packed)) g { char c; long l; };
+         _Static_assert(sizeof(struct g) == 9, \"g\");",
+    );
+    assert_eq!(
+        build_errors("[[gnu::packed]] int x;"),
+        vec![SemanticErrorType::AttributeIgnored(
+            "gnu::packed".to_string()
+        )]
+    );
+}
+
+#[test]
+fn test_bitfield_of_empty_struct() {
+    // An empty struct is a GNU extension, and has no room for a bitfield
+    let text = "struct e {}; struct s { struct e x : 3; };
+                _Static_assert(sizeof(struct s) == 1, \"z\");";
+    let tokens = Lexer::with_standard(text.chars(), Standard::Gnu11);
+    let (_, errors) = build_untyped_ast(parse_with(tokens, Standard::Gnu11).unwrap());
+    let errors: Vec<SemanticErrorType> = errors.into_iter().map(|error| error.error_type).collect();
+    assert_eq!(
+        errors,
+        vec![
+            SemanticErrorType::InvalidBitfieldType,
+            SemanticErrorType::NotConstant
+        ]
+    );
+}
+
+#[test]
+fn test_generic_and_alignof_expressions() {
+    let number = |value| RValue::NumberLiteral(NumberType::SignedInt(value));
+    assert_eq!(
+        body(
+            "long f(int x) {
+                 _Static_assert(1, \"block\");
+                 return _Generic(x, int: 1, char *: 2, default: 0) + _Alignof(long);
+             }"
+        ),
+        vec![
+            Statement::StaticAssert(
+                RValue::NumberLiteral(NumberType::SignedLongLong(1)),
+                b"block".to_vec()
+            ),
+            Statement::Return(Some(RValue::Add(
+                Box::new(RValue::Generic(
+                    Box::new(RValue::LValue(LValue::Variable("x".to_string()))),
+                    vec![
+                        (Some(Type::new(BaseType::SignedInt)), number(1)),
+                        (Some(pointer(Type::new(BaseType::SignedChar))), number(2)),
+                        (None, number(0)),
+                    ]
+                )),
+                Box::new(RValue::AlignofType(Type::new(BaseType::SignedLong)))
+            ))),
+        ]
+    );
+}
+
+#[test]
+fn test_alignas() {
+    assert_eq!(
+        build("_Alignas(16) int a; _Alignas(double) _Alignas(4) char b;"),
+        vec![
+            TopStatement::Declaration(
+                Specifiers {
+                    alignment: Some(16),
+                    ..Specifiers::default()
+                },
+                Type::new(BaseType::SignedInt),
+                "a".to_string(),
+                None
+            ),
+            TopStatement::Declaration(
+                Specifiers {
+                    alignment: Some(8),
+                    ..Specifiers::default()
+                },
+                Type::new(BaseType::SignedChar),
+                "b".to_string(),
+                None
+            ),
+        ]
+    );
+    assert_eq!(
+        build_errors(
+            "_Alignas(3) int a; typedef _Alignas(8) int t; int f(_Alignas(8) int x);
+             _Alignas(8) int g(void); struct s { _Alignas(8) int x : 3; };"
+        ),
+        vec![
+            SemanticErrorType::InvalidAlignment(3),
+            SemanticErrorType::AlignmentNotAllowed,
+            SemanticErrorType::AlignmentNotAllowed,
+            SemanticErrorType::AlignmentNotAllowed,
+            SemanticErrorType::AlignmentNotAllowed,
+        ]
+    );
+}
+
+fn build_gnu(text: &str) -> Vec<TopStatement> {
+    let tokens = Lexer::with_standard(text.chars(), Standard::Gnu11);
+    let (ast, errors) = build_untyped_ast(parse_with(tokens, Standard::Gnu11).unwrap());
+    assert_eq!(errors, vec![]);
+    ast.0
+}
+
+/// A GNU attribute
+fn attribute(name: &str, arguments: Vec<AttributeArgument>) -> Attribute {
+    Attribute {
+        prefix: Some("gnu".to_string()),
+        name: name.to_string(),
+        arguments,
+    }
+}
+
+#[test]
+fn test_variadic_function() {
+    let char_pointer = pointer(Type::new(BaseType::SignedChar));
+    assert_eq!(
+        build("int printf(char *, ...); void (*log)(int, ...);"),
+        vec![
+            TopStatement::ForwardDeclaration(
+                Specifiers::default(),
+                Type::new(BaseType::SignedInt),
+                "printf".to_string(),
+                vec![char_pointer],
+                Prototype::Variadic
+            ),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                Type::new(BaseType::FunctionPtr(
+                    vec![Type::new(BaseType::SignedInt)],
+                    Box::new(Type::new(BaseType::Void)),
+                    true
+                )),
+                "log".to_string(),
+                None
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_gnu_attributes_and_asm_labels() {
+    let text = "__attribute__((__nothrow__, format(__printf__, 1, 2))) extern int\n\
+                f(char *__restrict, ...) __asm__(\"\" \"f2\") __attribute__((aligned(4 * 2)));\n\
+                int a __attribute__((section(\".data\"),)) __attribute__(()), b;";
+    assert_eq!(
+        build_gnu(text),
+        vec![
+            TopStatement::ForwardDeclaration(
+                Specifiers {
+                    storage: Some(StorageClass::Extern),
+                    attributes: vec![
+                        attribute("nothrow", vec![]),
+                        attribute(
+                            "format",
+                            vec![
+                                AttributeArgument::Identifier("__printf__".to_string()),
+                                AttributeArgument::Constant(1),
+                                AttributeArgument::Constant(2),
+                            ]
+                        ),
+                        attribute("aligned", vec![AttributeArgument::Constant(8)]),
+                    ],
+                    asm_label: Some(b"f2".to_vec()),
+                    ..Specifiers::default()
+                },
+                Type::new(BaseType::SignedInt),
+                "f".to_string(),
+                vec![Type {
+                    base: BaseType::Pointer(Box::new(Type::new(BaseType::SignedChar))),
+                    qualifiers: Qualifiers {
+                        restrict: true,
+                        ..Qualifiers::default()
+                    },
+                }],
+                Prototype::Variadic
+            ),
+            TopStatement::Declaration(
+                Specifiers {
+                    attributes: vec![attribute(
+                        "section",
+                        vec![AttributeArgument::String(b".data".to_vec())]
+                    )],
+                    ..Specifiers::default()
+                },
+                Type::new(BaseType::SignedInt),
+                "a".to_string(),
+                None
+            ),
+            TopStatement::Declaration(
+                Specifiers::default(),
+                Type::new(BaseType::SignedInt),
+                "b".to_string(),
+                None
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_gnu_types() {
+    let text = "__extension__ unsigned __int128 a; __int128 b; __builtin_va_list c;\n\
+                __typeof__(int *) d; const __typeof__(b + 1) e;\n\
+                _Static_assert(sizeof(__int128) == 16 && _Alignof(__int128) == 16, \"int128\");\n\
+                _Static_assert(sizeof(__builtin_va_list) == 24, \"va_list\");\n\
+                _Static_assert(__extension__ 1, \"extension\");";
+    let declaration = |typ, name: &str| {
+        TopStatement::Declaration(Specifiers::default(), typ, name.to_string(), None)
+    };
+    let asserted = |message: &str| {
+        TopStatement::StaticAssert(
+            RValue::NumberLiteral(NumberType::SignedLongLong(1)),
+            message.as_bytes().to_vec(),
+        )
+    };
+    assert_eq!(
+        build_gnu(text),
+        vec![
+            declaration(Type::new(BaseType::UnsignedInt128), "a"),
+            declaration(Type::new(BaseType::SignedInt128), "b"),
+            declaration(Type::new(BaseType::VaList), "c"),
+            declaration(pointer(Type::new(BaseType::SignedInt)), "d"),
+            declaration(
+                Type {
+                    base: BaseType::TypeOf(Box::new(RValue::Add(
+                        Box::new(RValue::LValue(LValue::Variable("b".to_string()))),
+                        Box::new(RValue::NumberLiteral(NumberType::SignedInt(1)))
+                    ))),
+                    qualifiers: Qualifiers {
+                        constant: true,
+                        ..Qualifiers::default()
+                    },
+                },
+                "e"
+            ),
+            asserted("int128"),
+            asserted("va_list"),
+            asserted("extension"),
+        ]
+    );
+}
+
+#[test]
+fn test_stdio_declarations() {
+    // As `gcc -E -std=gnu11` gives them for `#include <stdio.h>`
+    let text = "typedef long unsigned int size_t;
+         typedef __builtin_va_list __gnuc_va_list;
+         struct _IO_FILE;
+         typedef struct _IO_FILE FILE;
+         struct _IO_FILE { int _flags; char *_IO_read_ptr; struct _IO_FILE *_chain; };
+         extern FILE *fopen (const char *__restrict __filename,
+               const char *__restrict __modes)
+           __attribute__ ((__malloc__)) __attribute__ ((__malloc__ (fclose, 1))) ;
+         extern int vprintf (const char *__restrict __format, __gnuc_va_list __arg);
+         extern size_t fread (void *__restrict __ptr, size_t __size,
+               size_t __n, FILE *__restrict __stream);";
+    let restricted = |typ| Type {
+        qualifiers: Qualifiers {
+            restrict: true,
+            ..Qualifiers::default()
+        },
+        ..pointer(typ)
+    };
+    let size = Type::new(BaseType::UnsignedLong);
+    let file = Type::new(BaseType::Struct(Some("_IO_FILE".to_string()), None));
+    let declarations = build_gnu(text);
+    assert_eq!(declarations.len(), 8);
+    assert_eq!(
+        declarations[7],
+        TopStatement::ForwardDeclaration(
+            Specifiers {
+                storage: Some(StorageClass::Extern),
+                ..Specifiers::default()
+            },
+            size.clone(),
+            "fread".to_string(),
+            vec![
+                restricted(Type::new(BaseType::Void)),
+                size.clone(),
+                size,
+                restricted(file),
+            ],
+            Prototype::Fixed
+        )
+    );
+}
+
+#[test]
+fn test_gnu_expressions() {
+    let int = Type::new(BaseType::SignedInt);
+    let number = |value| RValue::NumberLiteral(NumberType::SignedInt(value));
+    let variable = |name: &str| RValue::LValue(LValue::Variable(name.to_string()));
+    assert_eq!(
+        build_gnu(
+            "int c[10] = { [2 ... 5] = 2 };
+             __extension__ typedef int t;
+             int f(int n, ...) { return ({ int m = n; __extension__ m; }); }"
+        ),
+        vec![
+            TopStatement::Declaration(
+                Specifiers::default(),
+                Type::new(BaseType::Array(Box::new(int.clone()), Some(10))),
+                "c".to_string(),
+                Some(Box::new(Initializer::List(vec![(
+                    vec![Designator::Range(number(2), number(5))],
+                    Initializer::Expression(number(2))
+                )])))
+            ),
+            TopStatement::TypedefDeclaration("t".to_string(), int.clone()),
+            TopStatement::FunctionDeclaration(
+                Specifiers::default(),
+                int.clone(),
+                "f".to_string(),
+                vec![(int.clone(), "n".to_string())],
+                Prototype::Variadic,
+                Block(vec![Statement::Return(Some(RValue::StatementExpression(
+                    Block(vec![
+                        Statement::Declaration(
+                            Specifiers::default(),
+                            int,
+                            "m".to_string(),
+                            Some(Box::new(Initializer::Expression(variable("n"))))
+                        ),
+                        Statement::Expression(variable("m")),
+                    ])
+                )))])
+            ),
+        ]
+    );
+}
+
+/// A standard attribute, or one with a vendor prefix
+fn prefixed(prefix: Option<&str>, name: &str, arguments: Vec<AttributeArgument>) -> Attribute {
+    Attribute {
+        prefix: prefix.map(str::to_string),
+        name: name.to_string(),
+        arguments,
+    }
+}
+
+#[test]
+fn test_standard_attributes() {
+    let int = Type::new(BaseType::SignedInt);
+    let nodiscard = prefixed(None, "nodiscard", vec![]);
+    let deprecated = prefixed(
+        None,
+        "deprecated",
+        vec![AttributeArgument::String(b"why".to_vec())],
+    );
+    let unused = prefixed(Some("gnu"), "unused", vec![]);
+    let packed = prefixed(Some("gnu"), "packed", vec![]);
+    assert_eq!(
+        build(
+            "[[nodiscard]] int f(void);
+             [[deprecated(\"why\")]] int a, b [[__gnu__::__unused__]];
+             [[noreturn]] void stop(void);
+             struct s { [[deprecated(\"why\")]] int x; int y [[gnu::packed]]; };"
+        ),
+        vec![
+            TopStatement::ForwardDeclaration(
+                Specifiers {
+                    attributes: vec![nodiscard],
+                    ..Specifiers::default()
+                },
+                int.clone(),
+                "f".to_string(),
+                vec![],
+                Prototype::Fixed
+            ),
+            TopStatement::Declaration(
+                Specifiers {
+                    attributes: vec![deprecated.clone()],
+                    ..Specifiers::default()
+                },
+                int.clone(),
+                "a".to_string(),
+                None
+            ),
+            TopStatement::Declaration(
+                Specifiers {
+                    attributes: vec![deprecated.clone(), unused],
+                    ..Specifiers::default()
+                },
+                int.clone(),
+                "b".to_string(),
+                None
+            ),
+            TopStatement::ForwardDeclaration(
+                Specifiers {
+                    noreturn: true,
+                    attributes: vec![prefixed(None, "noreturn", vec![])],
+                    ..Specifiers::default()
+                },
+                Type::new(BaseType::Void),
+                "stop".to_string(),
+                vec![],
+                Prototype::Fixed
+            ),
+            TopStatement::StructOrUnionDeclaration(Type::new(BaseType::Struct(
+                Some("s".to_string()),
+                Some(vec![
+                    Member {
+                        attributes: vec![deprecated],
+                        ..member(Some("x"), int.clone(), None)
+                    },
+                    Member {
+                        attributes: vec![packed],
+                        ..member(Some("y"), int, None)
+                    },
+                ])
+            ))),
+        ]
+    );
+}
+
+#[test]
+fn test_attributes_after_specifiers_and_pointers() {
+    let int = Type::new(BaseType::SignedInt);
+    assert_eq!(
+        build(
+            "int [[gnu::aligned(8)]] x;
+             int * [[gnu::unused]] p;"
+        ),
+        vec![
+            TopStatement::Declaration(
+                Specifiers {
+                    attributes: vec![prefixed(
+                        Some("gnu"),
+                        "aligned",
+                        vec![AttributeArgument::Constant(8)]
+                    )],
+                    ..Specifiers::default()
+                },
+                int.clone(),
+                "x".to_string(),
+                None
+            ),
+            TopStatement::Declaration(
+                Specifiers {
+                    attributes: vec![prefixed(Some("gnu"), "unused", vec![])],
+                    ..Specifiers::default()
+                },
+                pointer(int),
+                "p".to_string(),
+                None
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_statement_attributes() {
+    let text = "void f(int x) {
+                    switch (x) {
+                    case 1:
+                        x++;
+                        [[fallthrough]];
+                    default:
+                        [[maybe_unused]] int y;
+                    }
+                }";
+    let body = match build(text).pop() {
+        Some(TopStatement::FunctionDeclaration(.., body)) => body,
+        _ => unreachable!(),
+    };
+    let cases = match &body.0[..] {
+        [Statement::Switch(_, cases)] => cases,
+        _ => unreachable!(),
+    };
+    assert_eq!(
+        **cases,
+        Statement::Block(Block(vec![
+            Statement::Case(
+                RValue::NumberLiteral(NumberType::SignedInt(1)),
+                Box::new(Statement::Expression(RValue::PostIncrement(
+                    LValue::Variable("x".to_string())
+                )))
+            ),
+            Statement::Attributed(
+                vec![prefixed(None, "fallthrough", vec![])],
+                Box::new(Statement::Empty)
+            ),
+            Statement::Default(Box::new(Statement::Declaration(
+                Specifiers {
+                    attributes: vec![prefixed(None, "maybe_unused", vec![])],
+                    ..Specifiers::default()
+                },
+                Type::new(BaseType::SignedInt),
+                "y".to_string(),
+                None
+            ))),
+        ]))
+    );
+}
+
+#[test]
+fn test_attribute_diagnostics() {
+    assert_eq!(
+        build_errors(
+            "[[unknown, vendor::thing]] int a;
+             [[nodiscard]] int b;
+             [[noreturn]] int c;
+             [[deprecated(\"a\", \"b\")]] int d;
+             [[fallthrough]] int e;
+             [[deprecated]];
+             void f(int x) {
+                 switch (x) {
+                 case 0:
+                     [[fallthrough]] x++;
+                 case 1:
+                     [[fallthrough]];
+                     x++;
+                 }
+                 [[fallthrough]];
+             }"
+        ),
+        vec![
+            SemanticErrorType::UnknownAttribute("unknown".to_string()),
+            SemanticErrorType::UnknownAttribute("vendor::thing".to_string()),
+            SemanticErrorType::AttributeIgnored("nodiscard".to_string()),
+            SemanticErrorType::AttributeIgnored("noreturn".to_string()),
+            SemanticErrorType::InvalidAttributeArguments("deprecated".to_string()),
+            SemanticErrorType::AttributeIgnored("fallthrough".to_string()),
+            SemanticErrorType::AttributeIgnored("deprecated".to_string()),
+            SemanticErrorType::MisplacedFallthrough,
+            SemanticErrorType::MisplacedFallthrough,
+            SemanticErrorType::MisplacedFallthrough,
+        ]
+    );
+}
+
+#[test]
+fn test_attribute_effects() {
+    let errors = build_errors(
+        "[[nodiscard(\"it may fail\")]] int f(void);
+         [[gnu::warn_unused_result]] int g(void);
+         [[deprecated]] int old;
+         enum { A [[deprecated(\"use B\")]], B };
+         int main(void) {
+             f();
+             g();
+             (void)f();
+             int x = f() + old;
+             return A;
+         }",
+    );
+    assert_eq!(
+        errors,
+        vec![
+            SemanticErrorType::UnusedResult("f".to_string(), Some("it may fail".to_string())),
+            SemanticErrorType::UnusedResult("g".to_string(), None),
+            SemanticErrorType::Deprecated("old".to_string(), None),
+            SemanticErrorType::Deprecated("A".to_string(), Some("use B".to_string())),
+        ]
+    );
+    assert!(errors
+        .iter()
+        .all(|error| error.severity() == Severity::Warning));
+}
+
+fn build_c89(text: &str) -> (Vec<TopStatement>, Vec<SemanticErrorType>) {
+    let tokens = Lexer::with_standard(text.chars(), Standard::C89);
+    let tree = parse_with(tokens, Standard::C89).unwrap();
+    let (ast, errors) = build_untyped_ast_with(tree, Standard::C89);
+    (
+        ast.0,
+        errors.into_iter().map(|error| error.error_type).collect(),
+    )
+}
+
+#[test]
+fn test_old_style_definitions() {
+    let int = Type::new(BaseType::SignedInt);
+    let (ast, errors) = build_c89(
+        "static count;
+         int f();
+         f(a, b, c) char *b; double c; { return a; }
+         g(x) int y; { }",
+    );
+    assert_eq!(
+        errors,
+        vec![SemanticErrorType::NotAParameter("y".to_string())]
+    );
+    let (_, errors) = build_c89("int f(a, a) int a; int a; { } int g(b) int b, b; { }");
+    assert_eq!(
+        errors,
+        vec![
+            SemanticErrorType::DuplicateParameter("a".to_string()),
+            SemanticErrorType::DuplicateParameter("a".to_string()),
+            SemanticErrorType::DuplicateParameter("b".to_string()),
+        ]
+    );
+    assert_eq!(
+        build_errors("int h(int c, char c) { return 0; }"),
+        vec![SemanticErrorType::DuplicateParameter("c".to_string())]
+    );
+    let a = RValue::LValue(LValue::Variable("a".to_string()));
+    assert_eq!(
+        ast,
+        vec![
+            TopStatement::Declaration(
+                Specifiers {
+                    storage: Some(StorageClass::Static),
+                    ..Specifiers::default()
+                },
+                int.clone(),
+                "count".to_string(),
+                None
+            ),
+            // Empty parentheses give no prototype
+            TopStatement::ForwardDeclaration(
+                Specifiers::default(),
+                int.clone(),
+                "f".to_string(),
+                vec![],
+                Prototype::Missing
+            ),
+            TopStatement::FunctionDeclaration(
+                Specifiers::default(),
+                int.clone(),
+                "f".to_string(),
+                vec![
+                    (int.clone(), "a".to_string()),
+                    (pointer(Type::new(BaseType::SignedChar)), "b".to_string()),
+                    (Type::new(BaseType::Double), "c".to_string()),
+                ],
+                Prototype::Missing,
+                Block(vec![Statement::Return(Some(a))])
+            ),
+            TopStatement::FunctionDeclaration(
+                Specifiers::default(),
+                int.clone(),
+                "g".to_string(),
+                vec![(int, "x".to_string())],
+                Prototype::Missing,
+                Block(vec![])
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_c89_rejects_newer_constructs() {
+    let text = "_Static_assert(1, \"one\");
+                int f(int x) {
+                    int a;
+                    a = _Generic(x, int: 1, default: 0);
+                    int b;
+                    for (int i = 0; i < x; i++)
+                        ;
+                    return a;
+                }";
+    let (_, errors) = build_c89(text);
+    assert_eq!(
+        errors,
+        vec![
+            SemanticErrorType::NotInC89("_Static_assert".to_string()),
+            SemanticErrorType::NotInC89("_Generic".to_string()),
+            SemanticErrorType::NotInC89("a declaration after a statement".to_string()),
+            SemanticErrorType::NotInC89("a declaration in a for-loop".to_string()),
+        ]
+    );
+    // The same code is fine in C11
+    assert_eq!(build_errors(text), vec![]);
+
+    let text = "_Bool b;
+                long long l;
+                double _Complex z;
+                _Atomic(int) a;
+                int * _Atomic p;
+                _Noreturn void stop(void);
+                _Thread_local int t;
+                _Alignas(8) char c;
+                int s[_Alignof(int)];
+                struct point { int x, y; };
+                struct point o = { .y = 1 };
+                [[maybe_unused]] int u;
+                int f(int n) {
+                    char vla[n];
+                    return (struct point){ 1, 2 }.x + (int)_Alignof(long);
+                }";
+    let (_, errors) = build_c89(text);
+    let newer = |construct: &str| SemanticErrorType::NotInC89(construct.to_string());
+    assert_eq!(
+        errors,
+        vec![
+            newer("_Bool"),
+            newer("long long"),
+            newer("_Complex"),
+            newer("_Atomic"),
+            newer("_Atomic"),
+            newer("_Noreturn"),
+            newer("_Thread_local"),
+            newer("_Alignas"),
+            newer("_Alignof"),
+            newer("a designated initializer"),
+            newer("an attribute"),
+            newer("a variable length array"),
+            newer("a compound literal"),
+            newer("_Alignof"),
+        ]
+    );
+    assert_eq!(build_errors(text), vec![]);
+
+    // The reserved spellings of C99 keywords are fine
+    let (_, errors) = build_c89("static __inline int f(char *__restrict p) { return *p; }");
+    assert_eq!(errors, vec![]);
+}
@@ -1,129 +1,316 @@
-use crate::lex::types::LexItem;
-use crate::parse::types::NonTerminalType;
+use crate::lex::types::NumberType;
+use crate::parse::cst::{
+    self, AstNode, Declaration, FunctionDeclaration, InitDeclarator, ParameterDeclaration,
+    TopStatements, Typedef,
+};
 use crate::parse::types::ParseNode;
-use crate::parse::types::ParseNodeType;
-use crate::untyped_ast::types::Type;
-use crate::untyped_ast::types::{BaseType, TopStatement};
+use crate::untyped_ast::attribute::{prepend_attributes, read_attribute_specifier};
+use crate::untyped_ast::constant::read_static_assert;
+use crate::untyped_ast::context::Context;
+use crate::untyped_ast::declarator::{
+    apply, read_declarator, read_declarators, read_type_name, Derivation,
+};
+use crate::untyped_ast::errors::{span_of, SemanticError, SemanticErrorType};
+use crate::untyped_ast::initializer::read_initializer;
+use crate::untyped_ast::registry::{apply_attributes, check_attributes, Target};
+use crate::untyped_ast::specifiers::{check_specifiers, forbid_alignment, read_type};
+use crate::untyped_ast::statement::read_block;
+use crate::untyped_ast::types::{
+    Attribute, BaseType, RValue, Specifiers, StorageClass, TopStatement, Type,
+};
 use std::rc::Rc;
 
-pub(super) fn read_top_statements(node: Rc<ParseNode>) -> Vec<TopStatement> {
-    require_non_terminal!(node, NonTerminalType::TopStatements);
-
-    collapse_non_terminal!(node,
-    TopStatement -> read_top_statement,
-    TopStatements => read_top_statements)
+pub(super) fn read_top_statements(node: Rc<ParseNode>, context: &mut Context) -> Vec<TopStatement> {
+    let statements = TopStatements::from_root(node).unwrap().statements();
+    statements
+        .into_iter()
+        .flat_map(|statement| read_top_statement(statement.node().clone(), context))
+        .collect()
 }
 
-pub(super) fn read_top_statement(node: Rc<ParseNode>) -> TopStatement {
-    require_non_terminal!(node, NonTerminalType::TopStatement);
-    require_len!(node, |len| len == 1);
+/// Reads a top-level declaration, which declares a name for each declarator
+pub(super) fn read_top_statement(node: Rc<ParseNode>, context: &mut Context) -> Vec<TopStatement> {
+    let mut statement = cst::TopStatement::from_root(node).unwrap();
 
-    let child = &node.children[0];
+    // Attributes at the start of a declaration belong to each declared name
+    let mut leading = Vec::new();
+    while let (Some(attributes), Some(attributed)) =
+        (statement.attributes(), statement.attributed())
+    {
+        leading.extend(read_attribute_specifier(attributes.node().clone(), context));
+        statement = attributed;
+    }
 
-    match child.node_type {
-        ParseNodeType::NonTerminal(NonTerminalType::Declaration) => unimplemented!(),
-        ParseNodeType::NonTerminal(NonTerminalType::ForwardDeclaration) => {
-            read_forward_declaration(child.clone())
-        }
-        ParseNodeType::NonTerminal(NonTerminalType::FunctionDeclaration) => {
-            read_function_declaration(child.clone())
-        }
-        ParseNodeType::NonTerminal(NonTerminalType::StructOrUnionDeclaration) => unimplemented!(),
-        ParseNodeType::NonTerminal(NonTerminalType::Typedef) => unimplemented!(),
-        _ => unreachable!(),
+    if let Some(declaration) = statement.declaration() {
+        return read_declaration(declaration.node().clone(), leading, GLOBAL, context);
+    }
+    if let Some(function) = statement.function() {
+        return vec![read_function_declaration(function, leading, context)];
+    }
+    if let Some(typedef) = statement.typedef() {
+        return read_typedef(typedef.node().clone(), leading, context);
+    }
+    if statement.syntax().children().any(|child| child.is_error()) {
+        return vec![TopStatement::Error];
+    }
+    check_attributes(
+        leading,
+        Target::Nothing,
+        span_of(statement.node()),
+        &mut context.errors,
+    );
+    match statement.static_assert() {
+        Some(assertion) => read_static_assert(assertion.node().clone(), context)
+            .map(|(condition, message)| {
+                let condition = RValue::NumberLiteral(NumberType::SignedLongLong(condition));
+                TopStatement::StaticAssert(condition, message)
+            })
+            .into_iter()
+            .collect(),
+        // An empty declaration, which may be an attribute declaration
+        None => Vec::new(),
     }
 }
 
-fn read_forward_declaration(node: Rc<ParseNode>) -> TopStatement {
-    require_non_terminal!(node, NonTerminalType::ForwardDeclaration);
-    require_len!(node, |len| len == 2);
-    require_terminal!(node, 1, LexItem::Semicolon);
+/// The storage classes a declaration at file scope may have
+const GLOBAL: &[StorageClass] = &[StorageClass::Static, StorageClass::Extern];
 
-    let (ret_type, name, args) = read_basic_declaration(node.children[0].clone());
-    let args_typ = args.iter().map(|item| item.0.clone()).collect();
-    TopStatement::ForwardDeclaration(ret_type, name, args_typ)
-}
+/// Reads a declaration, where each declarator of a function is a forward
+/// declaration. Without declarators, a declaration can only declare a tag.
+pub(super) fn read_declaration(
+    node: Rc<ParseNode>,
+    leading: Vec<Attribute>,
+    allowed: &[StorageClass],
+    context: &mut Context,
+) -> Vec<TopStatement> {
+    let declaration = Declaration::from_root(node).unwrap();
+    let span = span_of(declaration.node());
 
-fn read_function_declaration(node: Rc<ParseNode>) -> TopStatement {
-    require_non_terminal!(node, NonTerminalType::FunctionDeclaration);
-    require_len!(node, |len| len == 2);
+    if let Some(type_name) = declaration.type_name() {
+        let (specifiers, typ, declarator) = read_type_name(type_name.node().clone(), context);
+        check_specifiers(&specifiers, allowed, false, span, &mut context.errors);
+        let mut attributes = leading;
+        attributes.extend(specifiers.attributes);
+        check_attributes(attributes, Target::Nothing, span, &mut context.errors);
+        if declarator.derivations.is_empty() {
+            match typ.base {
+                BaseType::Enum(..) => return vec![TopStatement::EnumDeclaration(typ)],
+                BaseType::Struct(Some(_), _) | BaseType::Union(Some(_), _) => {
+                    return vec![TopStatement::StructOrUnionDeclaration(typ)]
+                }
+                _ => {}
+            }
+        }
+        context.errors.push(SemanticError {
+            error_type: SemanticErrorType::DeclaresNothing,
+            span,
+        });
+        return Vec::new();
+    }
 
-    unimplemented!()
+    let (specifiers, declarators) = match (declaration.specifiers(), declaration.declarators()) {
+        (Some(specifiers), Some(declarators)) => (specifiers, declarators),
+        _ => unreachable!(),
+    };
+    let (mut specifiers, typ) = read_type(specifiers.node().clone(), context);
+    prepend_attributes(&mut specifiers, leading);
+    declarators
+        .declarators()
+        .into_iter()
+        .map(|declarator| read_init_declarator(declarator, &specifiers, &typ, allowed, context))
+        .collect()
 }
 
-fn read_basic_declaration(node: Rc<ParseNode>) -> (Type, String, Vec<(Type, Option<String>)>) {
-    require_non_terminal!(node, NonTerminalType::BasicDeclaration);
-    require_len!(node, |len| len == 4 || len == 3);
-    require_terminal!(node, 1, LexItem::LeftParen);
-
-    if node.children.len() == 3 {
-        require_terminal!(node, 2, LexItem::RightParen);
-        let (typ, name) = read_type_with_identifier(node.children[0].clone());
-        let args = Vec::new();
-        (typ, name, args)
+fn read_init_declarator(
+    init_declarator: InitDeclarator,
+    specifiers: &Specifiers,
+    typ: &Type,
+    allowed: &[StorageClass],
+    context: &mut Context,
+) -> TopStatement {
+    let declarator = read_declarator(init_declarator.declarator().node().clone(), context);
+    let name = declarator.name.clone().unwrap();
+    let mut specifiers = declarator.specifiers(specifiers);
+    let function = matches!(
+        declarator.derivations.first(),
+        Some(Derivation::Function(..))
+    );
+    let target = if function {
+        Target::Function
     } else {
-        require_terminal!(node, 3, LexItem::RightParen);
-        let (typ, name) = read_type_with_identifier(node.children[0].clone());
-        let args = read_args(node.children[2].clone());
-        (typ, name, args)
+        Target::Declaration
+    };
+    let span = span_of(init_declarator.node());
+    apply_attributes(Some(&name), &mut specifiers, target, span, context);
+    check_specifiers(&specifiers, allowed, function, span, &mut context.errors);
+    let initializer = init_declarator
+        .initializer()
+        .map(|initializer| Box::new(read_initializer(initializer.node().clone(), context)));
+    match declarator.derivations.split_first() {
+        Some((Derivation::Function(args, prototype), rest)) => TopStatement::ForwardDeclaration(
+            specifiers,
+            apply(typ.clone(), rest),
+            name,
+            args.iter().map(|arg| arg.0.clone()).collect(),
+            *prototype,
+        ),
+        _ => {
+            let typ = declarator.apply(typ.clone());
+            if typ.base == BaseType::Void {
+                context.errors.push(SemanticError {
+                    error_type: SemanticErrorType::VoidObject,
+                    span,
+                });
+            }
+            TopStatement::Declaration(specifiers, typ, name, initializer)
+        }
     }
 }
 
-fn read_type_with_identifier(node: Rc<ParseNode>) -> (Type, String) {
-    require_non_terminal!(node, NonTerminalType::TypeWithIdentifier);
-    require_len!(node, |len| len == 2);
+pub(super) fn read_typedef(
+    node: Rc<ParseNode>,
+    leading: Vec<Attribute>,
+    context: &mut Context,
+) -> Vec<TopStatement> {
+    let typedef = Typedef::from_root(node).unwrap();
 
-    //TODO: Function pointer support
-
-    let typ = read_type(node.children[0].clone());
-    let ident = read_identifier(node.children[1].clone());
+    // `__extension__` only silences warnings
+    if let Some(extended) = typedef.extended() {
+        return read_typedef(extended.node().clone(), leading, context);
+    }
+    let (specifiers, declarators) = match (typedef.specifiers(), typedef.declarators()) {
+        (Some(specifiers), Some(declarators)) => (specifiers, declarators),
+        _ => unreachable!(),
+    };
 
-    (typ, ident)
+    let span = span_of(typedef.node());
+    let (mut specifiers, typ) = read_type(specifiers.node().clone(), context);
+    prepend_attributes(&mut specifiers, leading);
+    check_specifiers(&specifiers, &[], false, span, &mut context.errors);
+    forbid_alignment(&specifiers, span, &mut context.errors);
+    read_declarators(declarators.node().clone(), context)
+        .into_iter()
+        .map(|declarator| {
+            let name = declarator.name.clone().unwrap();
+            let mut specifiers = declarator.specifiers(&specifiers);
+            apply_attributes(
+                Some(&name),
+                &mut specifiers,
+                Target::Declaration,
+                span,
+                context,
+            );
+            let typ = declarator.apply(typ.clone());
+            context.typedefs.insert(name.clone(), typ.clone());
+            TopStatement::TypedefDeclaration(name, typ)
+        })
+        .collect()
 }
 
-fn read_type_with_maybe_identifier(node: Rc<ParseNode>) -> (Type, Option<String>) {
-    require_non_terminal!(node, NonTerminalType::TypeWithMaybeIdentifier);
-    require_len!(node, |len| len == 1);
+/// Reads a function definition, whose parameters must all be named. An
+/// old-style definition declares them between its declarator and its body.
+fn read_function_declaration(
+    function: FunctionDeclaration,
+    leading: Vec<Attribute>,
+    context: &mut Context,
+) -> TopStatement {
+    // A function defined without a type returns int
+    let (mut specifiers, typ) = match function.specifiers() {
+        Some(specifiers) => read_type(specifiers.node().clone(), context),
+        None => (Specifiers::default(), Type::new(BaseType::SignedInt)),
+    };
+    prepend_attributes(&mut specifiers, leading);
+    let declarator_node = match (function.declarator(), function.old_style_declarator()) {
+        (Some(declarator), _) => declarator.node().clone(),
+        (None, Some(declarator)) => declarator.node().clone(),
+        (None, None) => unreachable!(),
+    };
+    let span = span_of(&declarator_node);
+    let declarator = read_declarator(declarator_node, context);
+    let name = declarator.name.clone().unwrap();
+    let mut specifiers = declarator.specifiers(&specifiers);
+    apply_attributes(
+        Some(&name),
+        &mut specifiers,
+        Target::Function,
+        span,
+        context,
+    );
+    check_specifiers(&specifiers, GLOBAL, true, span, &mut context.errors);
 
-    match node.children[0].node_type {
-        ParseNodeType::NonTerminal(NonTerminalType::TypeWithIdentifier) => {
-            let (typ, name) = read_type_with_identifier(node.children[0].clone());
-            (typ, Some(name))
-        }
-        ParseNodeType::NonTerminal(NonTerminalType::Type) => {
-            let typ = read_type(node.children[0].clone());
-            (typ, None)
-        }
+    let (args, prototype, rest) = match declarator.derivations.split_first() {
+        Some((Derivation::Function(args, prototype), rest)) => (args, *prototype, rest),
         _ => unreachable!(),
+    };
+    let mut parameters: Vec<(Type, String)> = args
+        .iter()
+        .map(|(typ, name)| {
+            let name = name.clone().unwrap_or_else(|| {
+                context.errors.push(SemanticError {
+                    error_type: SemanticErrorType::UnnamedParameter,
+                    span,
+                });
+                String::new()
+            });
+            (typ.clone(), name)
+        })
+        .collect();
+    for (index, (_, name)) in parameters.iter().enumerate() {
+        if !name.is_empty() && parameters[..index].iter().any(|(_, other)| other == name) {
+            context.errors.push(SemanticError {
+                error_type: SemanticErrorType::DuplicateParameter(name.clone()),
+                span,
+            });
+        }
     }
-}
-
-fn read_type(node: Rc<ParseNode>) -> Type {
-    require_non_terminal!(node, NonTerminalType::Type);
-
-    // TODO: Make this work, currently it's just for testing
-    Type::new(BaseType::SignedInt)
-}
-
-fn read_identifier(node: Rc<ParseNode>) -> String {
-    match node.node_type.clone() {
-        ParseNodeType::Terminal(succ) => match succ.item {
-            LexItem::Identifier(s) => s,
-            _ => unreachable!(),
-        },
-        _ => unreachable!(),
+    let mut declared = Vec::new();
+    for declaration in function.parameter_declarations() {
+        read_parameter_declaration(declaration, &mut parameters, &mut declared, context);
     }
+    let body = read_block(function.body().node().clone(), context);
+    TopStatement::FunctionDeclaration(
+        specifiers,
+        apply(typ, rest),
+        name,
+        parameters,
+        prototype,
+        body,
+    )
 }
 
-fn read_args(node: Rc<ParseNode>) -> Vec<(Type, Option<String>)> {
-    require_non_terminal!(node, NonTerminalType::Args);
-    require_len!(node, |len| len == 1 || len == 3);
-
-    if node.children.len() == 1 {
-        vec![read_type_with_maybe_identifier(node.children[0].clone())]
-    } else {
-        let mut args = vec![read_type_with_maybe_identifier(node.children[0].clone())];
-        args.extend(read_args(node.children[2].clone()));
-        args
+/// Reads a declaration before the body of an old-style definition, which gives
+/// the parameters it names their types. The names are added to `declared`, as
+/// each parameter can only be declared once.
+fn read_parameter_declaration(
+    declaration: ParameterDeclaration,
+    parameters: &mut [(Type, String)],
+    declared: &mut Vec<String>,
+    context: &mut Context,
+) {
+    let (specifiers, typ) = read_type(declaration.specifiers().node().clone(), context);
+    let span = span_of(declaration.node());
+    check_specifiers(
+        &specifiers,
+        &[StorageClass::Register],
+        false,
+        span,
+        &mut context.errors,
+    );
+    forbid_alignment(&specifiers, span, &mut context.errors);
+    for declarator in read_declarators(declaration.declarators().node().clone(), context) {
+        let name = declarator.name.clone().unwrap();
+        let error_type = match parameters
+            .iter_mut()
+            .find(|(_, parameter)| *parameter == name)
+        {
+            Some(_) if declared.contains(&name) => SemanticErrorType::DuplicateParameter(name),
+            Some(parameter) => {
+                parameter.0 = declarator.apply(typ.clone());
+                declared.push(name);
+                continue;
+            }
+            None => SemanticErrorType::NotAParameter(name),
+        };
+        context.errors.push(SemanticError { error_type, span });
     }
 }
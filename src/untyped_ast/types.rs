@@ -5,25 +5,80 @@ use crate::lex::types::NumberType;
 #[derive(Debug, Clone, PartialEq)]
 pub struct Type {
     pub base: BaseType,
-    pub volatile: bool,
-    pub register: bool,
+    pub qualifiers: Qualifiers,
 }
 
 impl Type {
     pub fn new(base: BaseType) -> Self {
         Type {
             base,
-            volatile: false,
-            register: false,
+            qualifiers: Qualifiers::default(),
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Qualifiers {
+    pub constant: bool,
+    pub volatile: bool,
+    pub restrict: bool,
+    pub atomic: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageClass {
+    Static,
+    Extern,
+    Auto,
+    Register,
+}
+
+/// What a declaration says about the declared name rather than its type
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Specifiers {
+    pub storage: Option<StorageClass>,
+    pub thread_local: bool,
+    pub inline: bool,
+    pub noreturn: bool,
+    /// The strictest alignment requested with `_Alignas`, in bytes
+    pub alignment: Option<u64>,
+    /// Attributes of the declared name, from the start of the declaration, its
+    /// specifiers and then its declarator
+    pub attributes: Vec<Attribute>,
+    /// The assembler name given with a GNU asm label
+    pub asm_label: Option<Vec<u8>>,
+}
+
+/// A standard `[[...]]` attribute or a GNU `__attribute__((...))`. Its prefix
+/// and name are kept without surrounding double underscores, so `__packed__`
+/// and `packed` are the same attribute.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+    /// The vendor prefix, as in `gnu::packed`, which is `gnu` for every GNU
+    /// attribute
+    pub prefix: Option<String>,
+    pub name: String,
+    pub arguments: Vec<AttributeArgument>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeArgument {
+    /// A name, such as the `printf` of `format(printf, 1, 2)`
+    Identifier(String),
+    Constant(i128),
+    String(Vec<u8>),
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum BaseType {
+    Void,
+    Bool,
+
     // Numbers
     Float,
     Double,
+    FloatComplex,
+    DoubleComplex,
     SignedChar,
     UnsignedChar,
     SignedShort,
@@ -34,21 +89,102 @@ pub enum BaseType {
     UnsignedLong,
     SignedLongLong,
     UnsignedLongLong,
+    SignedInt128,
+    UnsignedInt128,
+    /// The `__builtin_va_list` of the GNU dialects
+    VaList,
+
+    Pointer(Box<Type>),
+    /// An array with its size, if one was given
+    Array(Box<Type>, Option<usize>),
+    /// An array whose size is only known when the program runs, which only
+    /// declarations in a block or a parameter list can have
+    VariableArray(Box<Type>, Box<RValue>),
+    /// A struct with its members, which are left out by a reference to a tag
+    /// declared elsewhere
+    Struct(Option<String>, Option<Vec<Member>>),
+    /// A function with its parameters and return type, which is variadic if
+    /// its parameters end in `...`
+    FunctionPtr(Vec<Type>, Box<Type>, bool),
+    Union(Option<String>, Option<Vec<Member>>),
+    /// An enum with the value of each enumerator. A reference to a tag
+    /// declared elsewhere has no enumerators.
+    Enum(Option<String>, Vec<(String, i64)>),
+    /// The type of an expression, as given by GNU `__typeof__`, which is only
+    /// known once expressions have types
+    TypeOf(Box<RValue>),
+}
+
+/// A member of a struct or union. Anonymous structs and unions and unnamed
+/// bitfields have no name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub name: Option<String>,
+    pub typ: Type,
+    /// The width of a bitfield
+    pub bitfield: Option<u32>,
+    /// The alignment requested with `_Alignas`, in bytes
+    pub alignment: Option<u64>,
+    pub attributes: Vec<Attribute>,
+}
 
-    Pointer(Option<Box<Type>>),
-    Array(Box<Type>, usize),
-    Struct(Option<String>, Vec<(String, Type)>),
-    FunctionPtr(Vec<Type>, Option<Box<Type>>),
-    Union(Option<String>, Vec<(String, Type)>),
+/// What the declarator of a function says about the types of its parameters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Prototype {
+    /// The parameters have types, as in `int f(int)`
+    Fixed,
+    /// The parameters have types and end in `...`
+    Variadic,
+    /// The parameters have no types, as in `int f();` or an old-style
+    /// definition, so arguments get the default argument promotions
+    Missing,
+}
+
+impl Prototype {
+    pub fn is_variadic(self) -> bool {
+        self == Prototype::Variadic
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TopStatement {
-    Declaration(Type, String, Option<Box<RValue>>),
-    ForwardDeclaration(Type, String, Vec<Type>),
-    FunctionDeclaration(Type, String, Vec<(Type, String)>, Block),
+    Declaration(Specifiers, Type, String, Option<Box<Initializer>>),
+    /// A function declaration with its return type, the types of its
+    /// parameters and its prototype
+    ForwardDeclaration(Specifiers, Type, String, Vec<Type>, Prototype),
+    /// A function definition with its return type, its parameters, its
+    /// prototype and its body
+    FunctionDeclaration(
+        Specifiers,
+        Type,
+        String,
+        Vec<(Type, String)>,
+        Prototype,
+        Block,
+    ),
     StructOrUnionDeclaration(Type),
+    EnumDeclaration(Type),
+    /// An assertion checked while building the AST, with its condition folded
+    /// into a literal
+    StaticAssert(RValue, Vec<u8>),
     TypedefDeclaration(String, Type),
+    /// A declaration that could not be parsed
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Initializer {
+    Expression(RValue),
+    /// A brace-enclosed list, where each initializer may be designated
+    List(Vec<(Vec<Designator>, Initializer)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Designator {
+    Index(RValue),
+    /// A GNU range of indices, both included
+    Range(RValue, RValue),
+    Member(String),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -60,14 +196,43 @@ pub struct Root(pub Vec<TopStatement>);
 #[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
     LValue(Box<LValue>),
-    Declaration(Type, String, Option<Box<RValue>>),
+    /// An expression evaluated for its side effects
+    Expression(RValue),
+    Declaration(Specifiers, Type, String, Option<Box<Initializer>>),
+    /// A declaration of several names, such as `int a, b;`, with a
+    /// `Declaration` for each. Unlike a block it opens no scope.
+    Declarations(Vec<Statement>),
 
     If(RValue, Box<Statement>, Box<Statement>),
     While(RValue, Box<Statement>),
     DoWhile(RValue, Box<Statement>),
-    For(Box<Statement>, RValue, RValue, Box<Statement>),
+    /// Any of the three clauses of a for-loop may be left out
+    For(
+        Option<Box<Statement>>,
+        Option<RValue>,
+        Option<RValue>,
+        Box<Statement>,
+    ),
+    Switch(RValue, Box<Statement>),
+
+    Label(String, Box<Statement>),
+    Case(RValue, Box<Statement>),
+    Default(Box<Statement>),
+
+    StaticAssert(RValue, Vec<u8>),
+
+    Goto(String),
+    Break,
+    Continue,
+    Return(Option<RValue>),
 
     Block(Block),
+    Empty,
+    /// A statement with attributes, such as the `[[fallthrough]];` before a
+    /// case label
+    Attributed(Vec<Attribute>, Box<Statement>),
+    /// A statement that could not be parsed
+    Error,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -77,6 +242,8 @@ pub enum LValue {
     StructRef(Box<LValue>, String),
     Variable(String),
     Subscript(Box<RValue>, Box<RValue>),
+    /// An expression which was used as an lvalue but is not one
+    Error(Box<RValue>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -119,12 +286,18 @@ pub enum RValue {
 
     SizeofType(Type),
     SizeofValue(Box<RValue>),
+    AlignofType(Type),
 
     Cast(Type, Box<RValue>),
+    CompoundLiteral(Type, Vec<(Vec<Designator>, Initializer)>),
 
     FunctionCall(Box<RValue>, Vec<RValue>),
     Comma(Box<RValue>, Box<RValue>),
     Ternary(Box<RValue>, Box<RValue>, Box<RValue>),
+    /// A `_Generic` selection, where the default association has no type
+    Generic(Box<RValue>, Vec<(Option<Type>, RValue)>),
+    /// A GNU statement expression, whose value is that of its last statement
+    StatementExpression(Block),
 
     FunctionName(String),
 